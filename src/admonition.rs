@@ -0,0 +1,204 @@
+use pulldown_cmark::{Event, Tag};
+
+use crate::escape::escape_html;
+
+/// Admonition kinds, as written in the marker, with their default titles.
+const KINDS: &[(&str, &str)] = &[
+    ("note", "Note"),
+    ("tip", "Tip"),
+    ("important", "Important"),
+    ("warning", "Warning"),
+    ("caution", "Caution"),
+];
+
+/// Markdown pass turning GitHub-style alert blockquotes into admonitions:
+///
+/// ```text
+/// > [!WARNING] Optional title
+/// > Body text.
+/// ```
+///
+/// becomes `<div class="admonition admonition-warning">` with a
+/// `<p class="admonition-title">` heading. The marker is case-insensitive and
+/// must start the blockquote; anything after it on the same line replaces the
+/// default title. Other blockquotes are left alone.
+pub fn admonitions<'a, I: Iterator<Item = Event<'a>>>(events: I) -> Vec<Event<'a>> {
+    let events: Vec<Event<'a>> = events.collect();
+    let mut out = Vec::with_capacity(events.len());
+    // One entry per open blockquote: whether it was turned into an admonition.
+    let mut open: Vec<bool> = Vec::new();
+    let mut i = 0;
+
+    while i < events.len() {
+        match &events[i] {
+            Event::Start(Tag::BlockQuote) => {
+                match marker(&events, i + 1) {
+                    Some(found) => {
+                        out.push(Event::Html(format!(
+                            "<div class=\"admonition admonition-{}\">\n<p class=\"admonition-title\">",
+                            found.kind
+                        ).into()));
+                        if found.title.is_empty() {
+                            out.push(Event::Html(escape_html(found.default_title).into()));
+                        } else {
+                            out.extend(found.title);
+                        }
+                        out.push(Event::Html("</p>\n".into()));
+                        if found.paragraph_continues {
+                            out.push(Event::Start(Tag::Paragraph));
+                            out.extend(found.reopened);
+                        }
+                        open.push(true);
+                        i = found.next;
+                        continue;
+                    }
+                    None => {
+                        out.push(events[i].clone());
+                        open.push(false);
+                    }
+                }
+            }
+            Event::End(Tag::BlockQuote) => {
+                if open.pop() == Some(true) {
+                    out.push(Event::Html("</div>\n".into()));
+                } else {
+                    out.push(events[i].clone());
+                }
+            }
+            event => out.push(event.clone()),
+        }
+        i += 1;
+    }
+    out
+}
+
+struct Marker<'a> {
+    kind: &'static str,
+    default_title: &'static str,
+    /// Inline events of a custom title; empty for the default one.
+    title: Vec<Event<'a>>,
+    /// Whether the first paragraph has more lines after the marker line.
+    paragraph_continues: bool,
+    /// Inline tags open at the end of the marker line, such as emphasis
+    /// running onto the next line. The title closes them; the body reopens
+    /// them so both stay balanced.
+    reopened: Vec<Event<'a>>,
+    /// Index of the first event after the marker line.
+    next: usize,
+}
+
+/// Looks for `[!KIND] title` at the start of the paragraph opening at
+/// `start`. pulldown-cmark splits the brackets into separate text events, so
+/// the leading text is joined before matching.
+fn marker<'a>(events: &[Event<'a>], start: usize) -> Option<Marker<'a>> {
+    if !matches!(events.get(start), Some(Event::Start(Tag::Paragraph))) {
+        return None;
+    }
+    let mut i = start + 1;
+    let mut leading = String::new();
+    while let Some(Event::Text(text)) = events.get(i) {
+        leading.push_str(text);
+        i += 1;
+        if leading.contains(']') {
+            break;
+        }
+    }
+
+    let rest = leading.strip_prefix("[!")?;
+    let close = rest.find(']')?;
+    let name = rest[..close].to_ascii_lowercase();
+    let (kind, default_title) = KINDS.iter().find(|(kind, _)| *kind == name)?;
+
+    let mut title = Vec::new();
+    let after = &rest[close + 1..];
+    if !after.is_empty() {
+        title.push(Event::Text(after.to_string().into()));
+    }
+    let mut open_tags: Vec<Tag<'a>> = Vec::new();
+    loop {
+        match events.get(i)? {
+            Event::SoftBreak | Event::HardBreak => {
+                i += 1;
+                break;
+            }
+            Event::End(Tag::Paragraph) => break,
+            event => {
+                match event {
+                    Event::Start(tag) => open_tags.push(tag.clone()),
+                    Event::End(_) => {
+                        open_tags.pop();
+                    }
+                    _ => {}
+                }
+                title.push(event.clone());
+                i += 1;
+            }
+        }
+    }
+    title.extend(open_tags.iter().rev().map(|tag| Event::End(tag.clone())));
+    let reopened = open_tags.into_iter().map(Event::Start).collect();
+    // An empty marker paragraph is dropped entirely.
+    let paragraph_continues = !matches!(events.get(i), Some(Event::End(Tag::Paragraph)));
+    if !paragraph_continues {
+        i += 1;
+    }
+    // Spaces around the title are not part of it.
+    if let Some(Event::Text(first)) = title.first_mut() {
+        let trimmed = first.trim_start().to_string();
+        *first = trimmed.into();
+    }
+    if let Some(Event::Text(last)) = title.last_mut() {
+        let trimmed = last.trim_end().to_string();
+        *last = trimmed.into();
+    }
+    title.retain(|event| !matches!(event, Event::Text(text) if text.is_empty()));
+
+    Some(Marker { kind, default_title, title, paragraph_continues, reopened, next: i })
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::{html, Options, Parser};
+
+    use super::*;
+
+    fn render(markdown: &str) -> String {
+        let mut out = String::new();
+        html::push_html(&mut out, admonitions(Parser::new_ext(markdown, Options::all())).into_iter());
+        out
+    }
+
+    #[test]
+    fn default_and_custom_titles() {
+        assert_eq!(
+            render("> [!NOTE]\n> Body."),
+            "<div class=\"admonition admonition-note\">\n<p class=\"admonition-title\">Note</p>\n<p>Body.</p>\n</div>\n"
+        );
+        assert_eq!(
+            render("> [!warning]   Mind the *gap*  \n> Body."),
+            "<div class=\"admonition admonition-warning\">\n<p class=\"admonition-title\">Mind the <em>gap</em></p>\n<p>Body.</p>\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn emphasis_across_the_marker_line_stays_balanced() {
+        assert_eq!(
+            render("> [!TIP] *Title\n> continued* body"),
+            "<div class=\"admonition admonition-tip\">\n<p class=\"admonition-title\"><em>Title</em></p>\n<p><em>continued</em> body</p>\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn other_blockquotes_are_untouched() {
+        assert_eq!(render("> Just a quote"), "<blockquote>\n<p>Just a quote</p>\n</blockquote>\n");
+        assert_eq!(render("> [!BOGUS] x"), "<blockquote>\n<p>[!BOGUS] x</p>\n</blockquote>\n");
+        assert_eq!(render("> text [!NOTE]"), "<blockquote>\n<p>text [!NOTE]</p>\n</blockquote>\n");
+    }
+
+    #[test]
+    fn nested_blockquotes_close_in_order() {
+        let html = render("> [!NOTE]\n> > inner quote\n>\n> after");
+        assert!(html.starts_with("<div class=\"admonition admonition-note\">"));
+        assert!(html.contains("<blockquote>\n<p>inner quote</p>\n</blockquote>\n<p>after</p>\n</div>\n"), "{}", html);
+    }
+}
@@ -0,0 +1,676 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::SystemTime;
+
+use serde::Serialize;
+use pulldown_cmark::{Event, Parser, Options, Tag, html};
+
+use crate::admonition;
+use crate::date::Date;
+use crate::footnotes;
+use crate::highlight;
+use crate::http;
+use crate::images::ImageContext;
+use crate::math;
+use crate::media;
+use crate::sanitize::sanitize_html;
+use crate::tags;
+use crate::toc::{self, TocEntry};
+
+pub const DEFAULT_PER_PAGE: usize = 10;
+pub const MAX_PER_PAGE: usize = 100;
+pub const MAX_SLUG_LEN: usize = 128;
+pub const WORDS_PER_MINUTE: usize = 200;
+pub const EXCERPT_CHARS: usize = 200;
+
+/// A blog post loaded from a markdown file, with metadata taken from its
+/// front matter or, for older posts without one, from the markdown itself.
+#[derive(Debug, Clone, Serialize)]
+pub struct BlogPost {
+    pub slug: String,
+    pub title: String,
+    pub published_date: String,
+    /// `published_date` parsed into a real date, serialized as ISO 8601.
+    pub date: Option<Date>,
+    pub tags: Vec<String>,
+    pub summary: Option<String>,
+    pub author: Option<String>,
+    pub draft: bool,
+    pub cover_image: Option<String>,
+    pub canonical_url: Option<String>,
+    /// Words of prose in the body; code blocks are not counted.
+    pub word_count: usize,
+    /// Estimated minutes to read at `WORDS_PER_MINUTE`, at least one.
+    pub reading_time_minutes: usize,
+    /// Plain-text teaser: the explicit summary if there is one, otherwise the
+    /// opening paragraph cut at a word boundary.
+    pub excerpt: String,
+    /// Markdown body with the front matter block removed.
+    #[serde(skip)]
+    pub body: String,
+    /// `body` rendered to HTML.
+    #[serde(skip)]
+    pub html: String,
+    /// Headings below the title, nested by level; ids match those in `html`.
+    #[serde(skip)]
+    pub toc: Vec<TocEntry>,
+    /// Modification time of the source file, when loaded from disk.
+    #[serde(skip)]
+    pub modified: Option<SystemTime>,
+}
+
+impl AsRef<BlogPost> for BlogPost {
+    fn as_ref(&self) -> &BlogPost {
+        self
+    }
+}
+
+/// Where a post stands on a given day. Only published posts are listed,
+/// syndicated and indexed; the rest are reachable in preview mode alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PostStatus {
+    Published,
+    Draft,
+    /// Dated after today; published automatically once the date arrives.
+    Scheduled,
+}
+
+/// Raw values found in a `---` (YAML) or `+++` (TOML) front matter block.
+#[derive(Debug, Default)]
+struct FrontMatter {
+    title: Option<String>,
+    date: Option<String>,
+    tags: Vec<String>,
+    summary: Option<String>,
+    author: Option<String>,
+    draft: bool,
+    cover_image: Option<String>,
+    canonical_url: Option<String>,
+}
+
+impl BlogPost {
+    pub fn load(slug: &str, path: &Path, images: Option<&ImageContext>) -> Result<BlogPost, String> {
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read blog file: {}", e))?;
+        let mut post = BlogPost::parse(slug, &source, images);
+        post.modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+        Ok(post)
+    }
+
+    pub fn parse(slug: &str, source: &str, images: Option<&ImageContext>) -> BlogPost {
+        let (front, body) = match split_front_matter(source) {
+            Some((front, body)) => (front, body),
+            None => (FrontMatter::default(), source),
+        };
+
+        let title = front.title.unwrap_or_else(|| heuristic_title(body, slug));
+        let published_date = front.date
+            .or_else(|| heuristic_date(body))
+            .unwrap_or_else(|| "Unknown".to_string());
+        let date = Date::parse(&published_date);
+
+        let mut post = BlogPost {
+            slug: slug.to_string(),
+            title,
+            published_date,
+            date,
+            tags: front.tags,
+            summary: front.summary,
+            author: front.author,
+            draft: front.draft,
+            cover_image: front.cover_image,
+            canonical_url: front.canonical_url,
+            word_count: 0,
+            reading_time_minutes: 0,
+            excerpt: String::new(),
+            body: body.to_string(),
+            html: String::new(),
+            toc: Vec::new(),
+            modified: None,
+        };
+        post.render(images);
+
+        let (words, first_paragraph) = prose_stats(&post.body);
+        post.word_count = words;
+        post.reading_time_minutes = words.div_ceil(WORDS_PER_MINUTE).max(1);
+        post.excerpt = match &post.summary {
+            Some(summary) => summary.clone(),
+            None => truncate_at_word(&first_paragraph.unwrap_or_default(), EXCERPT_CHARS),
+        };
+        post
+    }
+
+    /// `draft: true` wins over any date. Undated posts count as published.
+    pub fn status(&self, today: Date) -> PostStatus {
+        if self.draft {
+            PostStatus::Draft
+        } else if self.date.is_some_and(|date| date > today) {
+            PostStatus::Scheduled
+        } else {
+            PostStatus::Published
+        }
+    }
+
+    pub fn is_published(&self, today: Date) -> bool {
+        self.status(today) == PostStatus::Published
+    }
+
+    /// Renders `body` into `html` and collects the table of contents. Embeds
+    /// and math need the source offsets, so they run first; the other passes
+    /// work on events alone. `images` describes local images for `srcset`
+    /// and intrinsic sizes.
+    pub fn render(&mut self, images: Option<&ImageContext>) {
+        let events = Parser::new_ext(&self.body, Options::all()).into_offset_iter().collect();
+        let events = media::embed_shortcodes(&self.body, events);
+        let events = math::render_math(&self.body, events.into_iter());
+        let events = admonition::admonitions(events.into_iter());
+        let events = media::figures(events.into_iter());
+        let events = footnotes::collect_footnotes(events.into_iter());
+        let events = media::responsive_images(events.into_iter(), &self.slug, images);
+        let events = highlight::highlight_code_blocks(events.into_iter());
+        let (events, toc) = toc::anchor_headings(events.into_iter());
+        let mut html_output = String::new();
+        html::push_html(&mut html_output, events.into_iter());
+        self.html = sanitize_html(&html_output);
+        self.toc = toc;
+    }
+}
+
+/// Slugs are ASCII letters, digits, '-' and '_', starting and ending with a
+/// letter or digit. Anything else (dots, slashes, NUL, unicode look-alikes)
+/// never names a post, so it is rejected before touching the filesystem.
+pub fn is_valid_slug(slug: &str) -> bool {
+    let bytes = slug.as_bytes();
+    !bytes.is_empty()
+        && bytes.len() <= MAX_SLUG_LEN
+        && bytes.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'-' || *b == b'_')
+        && bytes[0].is_ascii_alphanumeric()
+        && bytes[bytes.len() - 1].is_ascii_alphanumeric()
+}
+
+/// Turns the slug segment of a request path into a validated slug. The
+/// segment is percent-decoded first so encoded separators such as `%2e%2e%2f`
+/// are judged by what they decode to.
+pub fn parse_slug(segment: &str) -> Option<String> {
+    let slug = http::percent_decode(segment)?;
+    is_valid_slug(&slug).then_some(slug)
+}
+
+/// Whether `file` is the markdown source of a post: a `.md` file inside
+/// `content_dir`. Sources are only ever published rendered, so drafts and
+/// scheduled posts cannot be read through the static file path.
+pub fn is_post_source(file: &Path, content_dir: &Path) -> bool {
+    file.extension().and_then(|e| e.to_str()) == Some("md")
+        && fs::canonicalize(content_dir).ok()
+            .zip(fs::canonicalize(file).ok())
+            .is_some_and(|(dir, file)| file.starts_with(dir))
+}
+
+/// Most recent first; undated posts go last. Ties are broken by slug so the
+/// order does not depend on directory iteration order.
+pub fn sort_posts<P: AsRef<BlogPost>>(posts: &mut [P]) {
+    posts.sort_by(|a, b| {
+        let (a, b) = (a.as_ref(), b.as_ref());
+        let by_date = match (a.date, b.date) {
+            (Some(da), Some(db)) => db.cmp(&da),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        by_date.then_with(|| a.slug.cmp(&b.slug))
+    });
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Newest,
+    Oldest,
+    Title,
+}
+
+impl SortOrder {
+    fn parse(value: &str) -> Option<SortOrder> {
+        match value {
+            "newest" | "date" | "-date" => Some(SortOrder::Newest),
+            "oldest" | "+date" => Some(SortOrder::Oldest),
+            "title" => Some(SortOrder::Title),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Newest => "newest",
+            SortOrder::Oldest => "oldest",
+            SortOrder::Title => "title",
+        }
+    }
+}
+
+/// Filtering, ordering and paging options for the blog listing, taken from
+/// the `/api/blogs` query string. Absent or empty values take the defaults;
+/// `per_page` above `MAX_PER_PAGE` is capped.
+#[derive(Debug, Clone)]
+pub struct BlogQuery {
+    pub page: usize,
+    pub per_page: usize,
+    pub tag: Option<String>,
+    pub year: Option<i32>,
+    pub q: Option<String>,
+    pub sort: SortOrder,
+}
+
+impl BlogQuery {
+    /// Fails with a message naming the parameter when a value is present
+    /// but invalid, such as `page=0` or `sort=random`.
+    pub fn from_params(params: &HashMap<String, String>, default_per_page: usize) -> Result<BlogQuery, String> {
+        let non_empty = |key: &str| {
+            params.get(key).map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+        };
+        let positive = |key: &str| match non_empty(key) {
+            Some(v) => v.parse::<usize>().ok().filter(|n| *n > 0)
+                .map(Some)
+                .ok_or_else(|| format!("{} must be a positive number", key)),
+            None => Ok(None),
+        };
+        let year = match non_empty("year") {
+            Some(v) => Some(v.parse().map_err(|_| "year must be a number".to_string())?),
+            None => None,
+        };
+        let sort = match non_empty("sort") {
+            Some(v) => SortOrder::parse(&v).ok_or("sort must be one of newest, oldest or title")?,
+            None => SortOrder::Newest,
+        };
+        Ok(BlogQuery {
+            page: positive("page")?.unwrap_or(1),
+            per_page: positive("per_page")?.map(|p| p.min(MAX_PER_PAGE)).unwrap_or(default_per_page),
+            tag: non_empty("tag"),
+            year,
+            q: non_empty("q"),
+            sort,
+        })
+    }
+
+    pub fn matches(&self, post: &BlogPost) -> bool {
+        if let Some(tag) = &self.tag
+            && !tags::has_tag(post, &tags::tag_slug(tag)) {
+            return false;
+        }
+        if let Some(year) = self.year
+            && post.date.map(|d| d.year) != Some(year) {
+            return false;
+        }
+        if let Some(q) = &self.q {
+            let needle = q.to_lowercase();
+            let haystacks = [Some(&post.title), post.summary.as_ref(), Some(&post.body)];
+            if !haystacks.iter().flatten().any(|h| h.to_lowercase().contains(&needle)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn sort<P: AsRef<BlogPost>>(&self, posts: &mut [P]) {
+        match self.sort {
+            SortOrder::Newest => sort_posts(posts),
+            SortOrder::Oldest => {
+                sort_posts(posts);
+                // Keep undated posts last when reversing the date order.
+                let dated = posts.iter().filter(|p| p.as_ref().date.is_some()).count();
+                posts[..dated].reverse();
+            }
+            SortOrder::Title => posts.sort_by(|a, b| {
+                let (a, b) = (a.as_ref(), b.as_ref());
+                a.title.to_lowercase().cmp(&b.title.to_lowercase()).then_with(|| a.slug.cmp(&b.slug))
+            }),
+        }
+    }
+
+    /// Query string selecting `page` with the same filters as this query.
+    pub fn to_query_string(&self, page: usize) -> String {
+        http::build_query(&[
+            ("page", page.to_string()),
+            ("per_page", self.per_page.to_string()),
+            ("tag", self.tag.clone().unwrap_or_default()),
+            ("year", self.year.map(|y| y.to_string()).unwrap_or_default()),
+            ("q", self.q.clone().unwrap_or_default()),
+            ("sort", if self.sort == SortOrder::Newest { String::new() } else { self.sort.as_str().to_string() }),
+        ])
+    }
+}
+
+// Older posts have no front matter: the first line is the title and the date
+// follows a "**Published:**" marker somewhere in the text.
+fn heuristic_title(body: &str, slug: &str) -> String {
+    body.lines()
+        .next()
+        .unwrap_or(slug)
+        .trim_start_matches('#')
+        .trim()
+        .to_string()
+}
+
+fn heuristic_date(body: &str) -> Option<String> {
+    body.lines()
+        .find(|line| line.contains("**Published:**") || line.contains("Published:"))
+        .and_then(|line| {
+            line.split("Published:").nth(1)
+                .or_else(|| line.split("**Published:**").nth(1))
+                .map(|s| s.trim().trim_matches('*').trim().to_string())
+        })
+}
+
+/// Counts the words of prose in `body` and finds its first real paragraph,
+/// as plain text. Headings, code blocks and the "Published:" line of older
+/// posts are never taken as the opening paragraph.
+fn prose_stats(body: &str) -> (usize, Option<String>) {
+    let mut words = 0;
+    let mut first_paragraph = None;
+    let mut paragraph: Option<String> = None;
+    let mut in_code = false;
+
+    for event in Parser::new_ext(body, Options::all()) {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => in_code = true,
+            Event::End(Tag::CodeBlock(_)) => in_code = false,
+            Event::Start(Tag::Paragraph) => paragraph = Some(String::new()),
+            Event::End(Tag::Paragraph) => {
+                let text = paragraph.take().unwrap_or_default();
+                let text = text.trim();
+                words += text.split_whitespace().count();
+                if first_paragraph.is_none() && !text.is_empty() && !text.starts_with("Published:") {
+                    first_paragraph = Some(text.to_string());
+                }
+            }
+            Event::Text(text) | Event::Code(text) if !in_code => match paragraph.as_mut() {
+                Some(p) => p.push_str(&text),
+                None => words += text.split_whitespace().count(),
+            },
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some(p) = paragraph.as_mut() {
+                    p.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+    (words, first_paragraph)
+}
+
+/// Shortens `text` to at most `max_chars`, cutting at the last whole word
+/// and marking the cut with an ellipsis.
+pub fn truncate_at_word(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let cut = text.char_indices().nth(max_chars).map(|(idx, _)| idx).unwrap_or(text.len());
+    let head = &text[..cut];
+    let head = match head.rfind(char::is_whitespace) {
+        Some(space) if space > 0 => &head[..space],
+        _ => head,
+    };
+    format!("{}…", head.trim_end_matches(|c: char| c.is_ascii_punctuation() || c.is_whitespace()))
+}
+
+/// Splits a leading front matter block from the markdown body. Returns `None`
+/// when the file does not start with a `---` or `+++` fence or the block is
+/// never closed.
+fn split_front_matter(source: &str) -> Option<(FrontMatter, &str)> {
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let first_line_end = source.find('\n')?;
+    let fence = source[..first_line_end].trim_end();
+    let toml = match fence {
+        "---" => false,
+        "+++" => true,
+        _ => return None,
+    };
+
+    let rest = &source[first_line_end + 1..];
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == fence {
+            let block = &rest[..offset];
+            let body = &rest[offset + line.len()..];
+            let front = if toml { parse_toml_block(block) } else { parse_yaml_block(block) };
+            return Some((front, body.trim_start_matches(['\r', '\n'])));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+fn parse_yaml_block(block: &str) -> FrontMatter {
+    let mut front = FrontMatter::default();
+    let lines: Vec<&str> = block.lines().collect();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        if line.trim().is_empty() || line.trim_start().starts_with('#') || line.starts_with(' ') {
+            continue;
+        }
+        let Some(colon_pos) = line.find(':') else { continue };
+        let key = line[..colon_pos].trim();
+        let raw = line[colon_pos + 1..].trim();
+
+        if raw.is_empty() && matches!(key, "tags" | "categories") {
+            // Block list:
+            //   tags:
+            //     - rust
+            while i < lines.len() && lines[i].trim_start().starts_with('-') {
+                let item = unquote(lines[i].trim_start()[1..].trim());
+                if !item.is_empty() {
+                    front.tags.push(item);
+                }
+                i += 1;
+            }
+            continue;
+        }
+        apply_field(&mut front, key, raw);
+    }
+    front
+}
+
+fn parse_toml_block(block: &str) -> FrontMatter {
+    let mut front = FrontMatter::default();
+    for line in block.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+            continue;
+        }
+        if let Some(eq_pos) = line.find('=') {
+            apply_field(&mut front, line[..eq_pos].trim(), line[eq_pos + 1..].trim());
+        }
+    }
+    front
+}
+
+fn apply_field(front: &mut FrontMatter, key: &str, raw: &str) {
+    let value = unquote(raw);
+    let optional = if value.is_empty() { None } else { Some(value.clone()) };
+    match key {
+        "title" => front.title = optional,
+        "date" | "published" | "published_date" => front.date = optional,
+        "tags" | "categories" => front.tags.extend(parse_inline_list(raw)),
+        "summary" | "description" => front.summary = optional,
+        "author" => front.author = optional,
+        "draft" => front.draft = value.eq_ignore_ascii_case("true"),
+        "cover_image" | "cover" | "image" => front.cover_image = optional,
+        "canonical_url" | "canonical" => front.canonical_url = optional,
+        _ => {}
+    }
+}
+
+/// Parses `[a, "b"]` or a bare comma separated `a, b` into trimmed items.
+fn parse_inline_list(raw: &str) -> Vec<String> {
+    let inner = raw.trim();
+    let inner = inner.strip_prefix('[').and_then(|s| s.strip_suffix(']')).unwrap_or(inner);
+    inner.split(',')
+        .map(|item| unquote(item.trim()))
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn unquote(value: &str) -> String {
+    let value = value.trim();
+    if value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"'))
+            || (value.starts_with('\'') && value.ends_with('\'')))
+    {
+        value[1..value.len() - 1].to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slugs_are_validated_after_decoding() {
+        let long = "a".repeat(MAX_SLUG_LEN + 1);
+        let longest = "a".repeat(MAX_SLUG_LEN);
+        let cases: &[(&str, Option<&str>)] = &[
+            ("hello-world", Some("hello-world")),
+            ("Post_2", Some("Post_2")),
+            ("hello%2Dworld", Some("hello-world")),
+            (&longest, Some(&longest)),
+            ("", None),
+            ("..", None),
+            ("../etc/passwd", None),
+            ("%2e%2e%2f", None),
+            ("%2e%2e%2fconfig", None),
+            ("a%2Fb", None),
+            ("a%2fb", None),
+            ("%252e%252e%252f", None),
+            ("post%2500", None),
+            ("a\0b", None),
+            ("post%00", None),
+            ("a\\b", None),
+            ("a%5Cb", None),
+            ("café", None),
+            ("caf%C3%A9", None),
+            ("ｐｏｓｔ", None),
+            ("post.md", None),
+            ("-post", None),
+            ("post_", None),
+            ("a b", None),
+            ("%", None),
+            ("%zz", None),
+            ("%C3", None),
+            (&long, None),
+        ];
+        for (segment, expected) in cases {
+            assert_eq!(parse_slug(segment).as_deref(), *expected, "{:?}", segment);
+            if let Some(slug) = expected {
+                assert!(is_valid_slug(slug));
+            }
+        }
+    }
+
+    #[test]
+    fn yaml_front_matter() {
+        let source = "---\n\
+            title: \"Hello: World\"\n\
+            date: 2025-11-09\n\
+            # a comment\n\
+            tags: [rust, 'web']\n\
+            categories:\n  - cli\n  - \"async\"\n\
+            summary: Short\n\
+            author: Ada\n\
+            draft: true\n\
+            cover_image: /img/cover.png\n\
+            canonical_url: https://example.com/hello\n\
+            unknown: ignored\n\
+            ---\n\
+            \n# Body heading\nText";
+        let post = BlogPost::parse("hello", source, None);
+        assert_eq!(post.title, "Hello: World");
+        assert_eq!(post.published_date, "2025-11-09");
+        assert_eq!(post.date, Date::new(2025, 11, 9));
+        assert_eq!(post.tags, ["rust", "web", "cli", "async"]);
+        assert_eq!(post.summary.as_deref(), Some("Short"));
+        assert_eq!(post.author.as_deref(), Some("Ada"));
+        assert!(post.draft);
+        assert_eq!(post.cover_image.as_deref(), Some("/img/cover.png"));
+        assert_eq!(post.canonical_url.as_deref(), Some("https://example.com/hello"));
+        assert_eq!(post.body, "# Body heading\nText");
+    }
+
+    #[test]
+    fn yaml_block_list_of_categories() {
+        let post = BlogPost::parse("cats", "---\ntitle: Cats\ncategories:\n  - rust\n  - web\ndraft: false\n---\nBody", None);
+        assert_eq!(post.tags, ["rust", "web"]);
+        assert!(!post.draft);
+    }
+
+    #[test]
+    fn toml_front_matter() {
+        let source = "+++\ntitle = \"Toml post\"\ndate = \"Nov 9, 2025\"\ntags = [\"rust\", \"toml\"]\ndraft = false\n[extra]\nauthor = \"Nobody\"\n+++\nBody";
+        let post = BlogPost::parse("toml", source, None);
+        assert_eq!(post.title, "Toml post");
+        assert_eq!(post.date, Date::new(2025, 11, 9));
+        assert_eq!(post.tags, ["rust", "toml"]);
+        assert!(!post.draft);
+        assert_eq!(post.body, "Body");
+    }
+
+    #[test]
+    fn legacy_posts_use_heuristics() {
+        let post = BlogPost::parse("legacy", "# Legacy title\n\n**Published:** November 2025\n\nFirst paragraph.", None);
+        assert_eq!(post.title, "Legacy title");
+        assert_eq!(post.published_date, "November 2025");
+        assert_eq!(post.date, Date::new(2025, 11, 1));
+        assert!(post.tags.is_empty() && !post.draft);
+
+        let undated = BlogPost::parse("undated", "Just text", None);
+        assert_eq!((undated.title.as_str(), undated.published_date.as_str(), undated.date), ("Just text", "Unknown", None));
+
+        // An unclosed fence is not front matter.
+        let unclosed = BlogPost::parse("unclosed", "---\ntitle: Never closed\n", None);
+        assert_eq!(unclosed.body, "---\ntitle: Never closed\n");
+    }
+
+    #[test]
+    fn truncation_respects_words_and_characters() {
+        assert_eq!(truncate_at_word("short enough", 20), "short enough");
+        assert_eq!(truncate_at_word("one two three four", 12), "one two…");
+        assert_eq!(truncate_at_word("one two, three", 9), "one two…");
+        assert_eq!(truncate_at_word("unbreakableword", 5), "unbre…");
+        // Limits count characters, and cuts never split one.
+        assert_eq!(truncate_at_word("héhé ñoño ürü", 13), "héhé ñoño ürü");
+        assert_eq!(truncate_at_word("héhé ñoño ürü", 11), "héhé ñoño…");
+        assert_eq!(truncate_at_word("日本語のテキスト", 3), "日本語…");
+        assert_eq!(truncate_at_word("🦀🦀 🦀🦀🦀", 4), "🦀🦀…");
+    }
+
+    #[test]
+    fn excerpt_and_reading_time() {
+        let body = format!(
+            "# Title\n\n**Published:** 2025-11-09\n\n{}\n\n```rust\n{}\n```\n\n{}",
+            "word ".repeat(150),
+            "code ".repeat(500),
+            "more ".repeat(51),
+        );
+        let post = BlogPost::parse("long", &body, None);
+        // The heading and the prose paragraphs count; the code block does not.
+        assert_eq!(post.word_count, 1 + 2 + 150 + 51);
+        assert_eq!(post.reading_time_minutes, 2);
+        assert!(post.excerpt.starts_with("word word"));
+        assert!(post.excerpt.ends_with("word…"));
+        assert!(post.excerpt.chars().count() <= EXCERPT_CHARS + 1);
+
+        let cases = [(0, 1), (1, 1), (WORDS_PER_MINUTE, 1), (WORDS_PER_MINUTE + 1, 2), (3 * WORDS_PER_MINUTE, 3)];
+        for (words, minutes) in cases {
+            let post = BlogPost::parse("n", &format!("---\ntitle: N\n---\n{}", "w ".repeat(words)), None);
+            assert_eq!((post.word_count, post.reading_time_minutes), (words, minutes));
+        }
+
+        let summarised = BlogPost::parse("s", "---\ntitle: S\nsummary: Given\n---\nBody text", None);
+        assert_eq!(summarised.excerpt, "Given");
+    }
+}
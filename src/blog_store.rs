@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use serde::Serialize;
+
+use crate::blog::{self, BlogPost};
+use crate::date::Date;
+use crate::images::ImageContext;
+use crate::static_files::{ensure_within, ResolveError};
+
+/// Parsed and rendered blog posts kept in memory. Each lookup compares the
+/// file's modification time with the cached one, so edited, added and removed
+/// posts are picked up without a restart while unchanged posts are never
+/// re-read or re-rendered.
+#[derive(Debug)]
+pub struct BlogStore {
+    dir: PathBuf,
+    /// Passed to rendering so post images get sizes and a `srcset`.
+    images: Option<ImageContext>,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    /// Bumped whenever any entry is loaded, replaced or dropped, so derived
+    /// data (indexes, feeds) can tell when it needs rebuilding.
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    modified: SystemTime,
+    post: Arc<BlogPost>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub generation: u64,
+}
+
+impl BlogStore {
+    pub fn new<P: AsRef<Path>>(dir: P) -> BlogStore {
+        BlogStore {
+            dir: dir.as_ref().to_path_buf(),
+            images: None,
+            entries: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_images(mut self, images: ImageContext) -> BlogStore {
+        self.images = Some(images);
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// All posts in the blog directory, in no particular order.
+    pub fn posts(&self) -> Result<Vec<Arc<BlogPost>>, String> {
+        if !self.dir.exists() {
+            self.clear();
+            return Ok(Vec::new());
+        }
+
+        let dir_entries = fs::read_dir(&self.dir)
+            .map_err(|e| format!("Failed to read blogs directory: {}", e))?;
+
+        let mut on_disk: Vec<(String, PathBuf)> = Vec::new();
+        for entry in dir_entries {
+            let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+            let path = entry.path();
+            if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("md")
+                && let Some(file_name) = path.file_stem().and_then(|s| s.to_str())
+                && blog::is_valid_slug(file_name)
+                && ensure_within(&self.dir, &path).is_ok() {
+                on_disk.push((file_name.to_string(), path));
+            }
+        }
+
+        let mut entries = self.lock();
+        let before = entries.len();
+        entries.retain(|slug, _| on_disk.iter().any(|(s, _)| s == slug));
+        if entries.len() != before {
+            self.generation.fetch_add(1, Ordering::SeqCst);
+        }
+
+        let mut posts = Vec::with_capacity(on_disk.len());
+        for (slug, path) in on_disk {
+            posts.push(self.refresh(&mut entries, &slug, &path)?);
+        }
+        Ok(posts)
+    }
+
+    /// Posts readers may see on `today`: drafts and posts scheduled for a
+    /// later date are left out.
+    pub fn published(&self, today: Date) -> Result<Vec<Arc<BlogPost>>, String> {
+        let mut posts = self.posts()?;
+        posts.retain(|post| post.is_published(today));
+        Ok(posts)
+    }
+
+    /// A single post, or `None` if `slug` has no markdown file. Invalid slugs
+    /// and files resolving outside the blog directory (e.g. through a
+    /// symlink) are treated as missing.
+    pub fn get(&self, slug: &str) -> Result<Option<Arc<BlogPost>>, String> {
+        if !blog::is_valid_slug(slug) {
+            return Ok(None);
+        }
+        let candidate = self.dir.join(format!("{}.md", slug));
+        let path = match ensure_within(&self.dir, &candidate) {
+            Ok(path) if path.is_file() => Some(path),
+            Ok(_) | Err(ResolveError::NotFound) => None,
+            Err(ResolveError::Forbidden) => {
+                eprintln!("Refusing blog post outside {}: {}", self.dir.display(), candidate.display());
+                None
+            }
+        };
+        let mut entries = self.lock();
+        let Some(path) = path else {
+            if entries.remove(slug).is_some() {
+                self.generation.fetch_add(1, Ordering::SeqCst);
+            }
+            return Ok(None);
+        };
+        self.refresh(&mut entries, slug, &path).map(Some)
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.lock().len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            generation: self.generation(),
+        }
+    }
+
+    fn refresh(
+        &self,
+        entries: &mut HashMap<String, CacheEntry>,
+        slug: &str,
+        path: &Path,
+    ) -> Result<Arc<BlogPost>, String> {
+        let modified = fs::metadata(path)
+            .and_then(|m| m.modified())
+            .map_err(|e| format!("Failed to read blog file metadata: {}", e))?;
+
+        if let Some(entry) = entries.get(slug)
+            && entry.modified == modified {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(entry.post.clone());
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let post = Arc::new(BlogPost::load(slug, path, self.images.as_ref())?);
+        entries.insert(slug.to_string(), CacheEntry { modified, post: post.clone() });
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        Ok(post)
+    }
+
+    fn clear(&self) {
+        let mut entries = self.lock();
+        if !entries.is_empty() {
+            entries.clear();
+            self.generation.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    // A panic while holding the lock leaves the map itself consistent, so a
+    // poisoned mutex is simply recovered.
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, CacheEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("portfolio-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes `source`, moving the modification time forward so the change
+    /// is seen even within the file system's timestamp granularity.
+    fn write_post(path: &Path, source: &str, age: u64) {
+        fs::write(path, source).unwrap();
+        fs::File::options().write(true).open(path).unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(age)).unwrap();
+    }
+
+    #[test]
+    fn edited_posts_are_reparsed() {
+        let dir = scratch_dir("store-edit");
+        let path = dir.join("post.md");
+        write_post(&path, "---\ntitle: Before\n---\nOld body", 0);
+        let store = BlogStore::new(&dir);
+
+        assert_eq!(store.get("post").unwrap().unwrap().title, "Before");
+        let generation = store.generation();
+        assert_eq!(store.get("post").unwrap().unwrap().title, "Before");
+        assert_eq!(store.generation(), generation);
+        let stats = store.stats();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (1, 1, 1));
+
+        write_post(&path, "---\ntitle: After\n---\nNew body", 60);
+        let post = store.get("post").unwrap().unwrap();
+        assert_eq!(post.title, "After");
+        assert_eq!(post.body, "New body");
+        assert!(store.generation() > generation);
+        assert_eq!(store.stats().misses, 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn deleted_posts_are_dropped() {
+        let dir = scratch_dir("store-delete");
+        write_post(&dir.join("kept.md"), "# Kept", 0);
+        write_post(&dir.join("gone.md"), "# Gone", 0);
+        let store = BlogStore::new(&dir);
+        assert_eq!(store.published(Date::today()).unwrap().len(), 2);
+        let generation = store.generation();
+
+        fs::remove_file(dir.join("gone.md")).unwrap();
+        let slugs: Vec<String> = store.published(Date::today()).unwrap().iter().map(|p| p.slug.clone()).collect();
+        assert_eq!(slugs, ["kept"]);
+        assert!(store.generation() > generation);
+        assert_eq!(store.stats().entries, 1);
+        assert!(store.get("gone").unwrap().is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn posts_resolving_outside_the_directory_are_missing() {
+        let base = std::env::temp_dir().join(format!("portfolio-test-{}-store", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let dir = base.join("blogs");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("post.md"), "# Post").unwrap();
+        fs::write(base.join("outside.md"), "# Outside").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(base.join("outside.md"), dir.join("leak.md")).unwrap();
+
+        let store = BlogStore::new(&dir);
+        assert!(store.get("post").unwrap().is_some());
+        for slug in ["leak", "../outside", "..%2Foutside", "outside", "post.md", "post\0", ""] {
+            assert!(store.get(slug).unwrap().is_none(), "{:?}", slug);
+        }
+        assert!(store.posts().unwrap().iter().all(|post| post.slug != "leak"));
+        let _ = fs::remove_dir_all(&base);
+    }
+}
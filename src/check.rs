@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use pulldown_cmark::{Event, Options, Parser, Tag};
+
+use crate::date::Date;
+use crate::links::{extract_links, internal_route, line_at};
+use crate::server::Server;
+
+/// An internal link or asset reference that the server does not answer with
+/// a 200.
+#[derive(Debug)]
+pub struct Problem {
+    pub file: PathBuf,
+    /// 1-based line of the link in `file`.
+    pub line: usize,
+    /// The link as written.
+    pub link: String,
+    /// Site route the link resolved to.
+    pub route: String,
+    pub status: u16,
+}
+
+#[derive(Debug, Default)]
+pub struct CheckReport {
+    pub files: usize,
+    pub links: usize,
+    pub problems: Vec<Problem>,
+}
+
+/// Checks every link in the HTML files under the static root and in the
+/// markdown of every published blog post. Each internal `href`/`src` is
+/// resolved against the page it appears on and requested through
+/// `Server::dispatch`, so static files and blog routes are checked exactly as
+/// readers would be served them. Drafts and scheduled posts are skipped, like
+/// the pages they would link to: their links are checked once they go live.
+/// External links are not followed.
+pub fn check(server: &Server) -> Result<CheckReport, String> {
+    let cfg = server.config.as_ref().ok_or("Server configuration missing")?;
+    let resolver = server.resolver.as_ref().ok_or("Static file resolver not initialized")?;
+    let base_url = cfg.site.base_url.as_str();
+
+    let mut report = CheckReport::default();
+    let mut statuses: HashMap<String, u16> = HashMap::new();
+    let mut check_link = |report: &mut CheckReport, file: &PathBuf, page: &str, text: &str, offset: usize, link: String| {
+        let Some(route) = internal_route(page, &link, base_url) else { return };
+        report.links += 1;
+        let status = *statuses.entry(route.clone())
+            .or_insert_with(|| server.dispatch("GET", &route, None, None).status);
+        if status != 200 {
+            report.problems.push(Problem { file: file.clone(), line: line_at(text, offset), link, route, status });
+        }
+    };
+
+    for (url, file) in resolver.files() {
+        if file.extension().and_then(|e| e.to_str()) != Some("html") {
+            continue;
+        }
+        let html = fs::read_to_string(&file)
+            .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+        report.files += 1;
+        for (offset, link) in extract_links(&html) {
+            check_link(&mut report, &file, &url, &html, offset, link);
+        }
+    }
+
+    if cfg.blog.enabled {
+        for post in server.blog_store.published(Date::today())? {
+            let file = cfg.blog.content_dir.join(format!("{}.md", post.slug));
+            let source = fs::read_to_string(&file)
+                .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+            // The body is the tail of the file after the front matter.
+            let body_start = if source.ends_with(&post.body) { source.len() - post.body.len() } else { 0 };
+            let page = cfg.blog.post_url(&post.slug);
+            report.files += 1;
+
+            for (event, range) in Parser::new_ext(&post.body, Options::all()).into_offset_iter() {
+                match event {
+                    Event::Start(Tag::Link(_, dest, _)) | Event::Start(Tag::Image(_, dest, _)) => {
+                        check_link(&mut report, &file, &page, &source, body_start + range.start, dest.to_string());
+                    }
+                    Event::Html(html) => {
+                        for (offset, link) in extract_links(&html) {
+                            check_link(&mut report, &file, &page, &source, body_start + range.start + offset, link);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    report.problems.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::site;
+
+    #[test]
+    fn only_published_posts_are_checked() {
+        let site = site("check", &[
+            ("live.md", "---\ntitle: Live\n---\n[Other](/blogs/other-live) and [missing](/blogs/missing)"),
+            ("other-live.md", "---\ntitle: Other\n---\nText"),
+            ("draft-a.md", "---\ntitle: A\ndraft: true\n---\n[B](/blogs/draft-b)"),
+            ("draft-b.md", "---\ntitle: B\ndraft: true\n---\n[A](/blogs/draft-a)"),
+        ], "");
+        let report = check(&site.server).unwrap();
+        let routes: Vec<&str> = report.problems.iter().map(|p| p.route.as_str()).collect();
+        assert_eq!(routes, ["/blogs/missing"]);
+        assert_eq!(report.problems[0].status, 404);
+    }
+}
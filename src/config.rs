@@ -1,19 +1,143 @@
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::fmt;
 
+use crate::static_files::canonicalize_missing;
+
+/// Shorter preview tokens are too easy to guess.
+const MIN_PREVIEW_TOKEN_LEN: usize = 16;
+
+const MAX_IMAGE_WIDTH: u32 = 4096;
+const DEFAULT_IMAGE_WIDTHS: &[u32] = &[320, 640, 960, 1280, 1920];
+
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     pub server: ServerConfig,
     pub static_cfg: StaticConfig,
     pub content_types: HashMap<String, String>,
+    pub site: SiteConfig,
+    pub feed: FeedConfig,
+    pub robots: RobotsConfig,
+    pub templates: TemplatesConfig,
+    pub blog: BlogConfig,
+    pub highlight: HighlightConfig,
+    pub images: ImagesConfig,
+    /// Pages served for error statuses, by status code; paths are relative
+    /// to `static.root_dir`. Statuses without one get a built-in page.
+    pub error_pages: HashMap<u16, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImagesConfig {
+    /// Resize and convert static images requested with `?w=` or `?format=`.
+    pub enabled: bool,
+    /// The only widths `?w=` may ask for, so requests cannot fill the cache
+    /// with arbitrary sizes. Also the `srcset` candidates of post images.
+    pub widths: Vec<u32>,
+    /// Where resized images are stored; must lie outside `static.root_dir`.
+    pub cache_dir: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct HighlightConfig {
+    /// Stylesheet served at `/highlight.css`; one of `highlight::THEMES`.
+    pub theme: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlogConfig {
+    pub enabled: bool,
+    /// Directory holding the markdown posts. A relative `content_dir` in the
+    /// YAML is resolved against `static.root_dir`.
+    pub content_dir: PathBuf,
+    /// URL prefix of post pages: `/blogs` serves posts at `/blogs/<slug>`.
+    pub page_prefix: String,
+    /// URL prefix of the JSON endpoints `<api>/blogs`, `<api>/blog/<slug>`,
+    /// `<api>/search` and `<api>/blogs/stats`, the last one only answering
+    /// requests allowed to preview.
+    pub api_prefix: String,
+    /// Page size of the list API when the request does not ask for one.
+    pub per_page: usize,
+    /// Show a table of contents above posts that have section headings.
+    pub toc: bool,
+    /// Show drafts and scheduled posts to every visitor (local writing only).
+    pub preview: bool,
+    /// Secret that turns on preview mode for a single request when passed as
+    /// `?preview=<token>`. The `BLOG_PREVIEW_TOKEN` environment variable
+    /// takes precedence over the YAML value.
+    pub preview_token: Option<String>,
+}
+
+impl BlogConfig {
+    pub fn post_url(&self, slug: &str) -> String {
+        format!("{}/{}", self.page_prefix, slug)
+    }
+
+    pub fn api_path(&self, endpoint: &str) -> String {
+        format!("{}/{}", self.api_prefix, endpoint)
+    }
+
+    /// Whether a request carrying `token` (the `preview` query parameter)
+    /// may see unpublished posts.
+    pub fn preview_allowed(&self, token: Option<&str>) -> bool {
+        if self.preview {
+            return true;
+        }
+        match (&self.preview_token, token) {
+            (Some(expected), Some(given)) => constant_time_eq(expected.as_bytes(), given.as_bytes()),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SiteConfig {
+    /// Public origin used to build absolute URLs, e.g. `https://example.com`.
+    pub base_url: String,
+    pub title: String,
+    pub description: String,
+    pub author: String,
+    pub language: String,
+    /// Site name shown in social previews; defaults to `title`.
+    pub name: Option<String>,
+    /// Preview image for pages without one of their own, e.g. `/og.png`.
+    pub image: Option<String>,
+    /// Twitter/X handle credited in Twitter Cards, with or without the `@`.
+    pub twitter: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RobotsConfig {
+    pub allow: Vec<String>,
+    /// Path prefixes crawlers should skip; these are also left out of the sitemap.
+    pub disallow: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TemplatesConfig {
+    pub dir: String,
+    /// Re-read templates when their files change instead of caching them for
+    /// the lifetime of the process. Meant for development.
+    pub reload: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct FeedConfig {
+    /// Embed the full rendered post in feeds instead of just the summary.
+    pub full_content: bool,
+    pub limit: usize,
 }
 
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// Endpoints to bind, e.g. `0.0.0.0:5169`, `[::]:5169` or `unix:/run/portfolio.sock`.
+    /// When empty, the server falls back to a single `host:port` endpoint.
+    pub listen: Vec<String>,
+    /// Permission bits applied to Unix domain socket files after binding.
+    pub socket_mode: Option<u32>,
 }
 
 #[derive(Debug, Clone)]
@@ -58,6 +182,10 @@ pub fn load_config<P: AsRef<Path>>(path: P) -> Result<AppConfig, ConfigError> {
         return Err(ConfigError::Invalid("server.host cannot be empty".to_string()));
     }
 
+    if cfg.server.listen.iter().any(|l| l.trim().is_empty() || l.trim() == "unix:") {
+        return Err(ConfigError::Invalid("server.listen entries cannot be empty".to_string()));
+    }
+
     if cfg.static_cfg.root_dir.trim().is_empty() {
         return Err(ConfigError::Invalid("static.root_dir cannot be empty".to_string()));
     }
@@ -66,6 +194,55 @@ pub fn load_config<P: AsRef<Path>>(path: P) -> Result<AppConfig, ConfigError> {
         return Err(ConfigError::Invalid("static.index_file cannot be empty".to_string()));
     }
 
+    if !cfg.site.base_url.starts_with("http://") && !cfg.site.base_url.starts_with("https://") {
+        return Err(ConfigError::Invalid("site.base_url must start with http:// or https://".to_string()));
+    }
+
+    if cfg.blog.page_prefix.is_empty() {
+        return Err(ConfigError::Invalid("blog.page_prefix cannot be '/'".to_string()));
+    }
+
+    if !(1..=crate::blog::MAX_PER_PAGE).contains(&cfg.blog.per_page) {
+        return Err(ConfigError::Invalid(format!(
+            "blog.per_page must be between 1 and {}",
+            crate::blog::MAX_PER_PAGE
+        )));
+    }
+
+    if cfg.blog.preview_token.as_ref().is_some_and(|token| token.len() < MIN_PREVIEW_TOKEN_LEN) {
+        return Err(ConfigError::Invalid(format!(
+            "blog.preview_token must be at least {} characters",
+            MIN_PREVIEW_TOKEN_LEN
+        )));
+    }
+
+    if cfg.images.widths.iter().any(|&w| w == 0 || w > MAX_IMAGE_WIDTH) {
+        return Err(ConfigError::Invalid(format!(
+            "images.widths must be between 1 and {}",
+            MAX_IMAGE_WIDTH
+        )));
+    }
+
+    // Compared canonically, so an absolute path and a relative one, `..` or a
+    // symlink cannot hide the cache inside the served directory.
+    let cache_dir = canonicalize_missing(&cfg.images.cache_dir)?;
+    let root_dir = canonicalize_missing(Path::new(&cfg.static_cfg.root_dir))?;
+    if cache_dir.starts_with(&root_dir) {
+        return Err(ConfigError::Invalid("images.cache_dir must be outside static.root_dir".to_string()));
+    }
+
+    if cfg.error_pages.keys().any(|status| !(400..=599).contains(status)) {
+        return Err(ConfigError::Invalid("error_pages keys must be status codes between 400 and 599".to_string()));
+    }
+
+    if crate::highlight::theme_css(&cfg.highlight.theme).is_none() {
+        let themes: Vec<&str> = crate::highlight::THEMES.iter().map(|(name, _)| *name).collect();
+        return Err(ConfigError::Invalid(format!(
+            "highlight.theme must be one of: {}",
+            themes.join(", ")
+        )));
+    }
+
     Ok(cfg)
 }
 
@@ -76,6 +253,8 @@ fn parse_yaml_config(content: &str) -> Result<AppConfig, ConfigError> {
     let mut server_config = ServerConfig {
         host: String::new(),
         port: 8080,
+        listen: Vec::new(),
+        socket_mode: None,
     };
     
     let mut static_config = StaticConfig {
@@ -86,6 +265,54 @@ fn parse_yaml_config(content: &str) -> Result<AppConfig, ConfigError> {
     };
     
     let mut content_types = HashMap::new();
+    let mut error_pages = HashMap::new();
+
+    let mut site_config = SiteConfig {
+        base_url: "http://localhost:5169".to_string(),
+        title: String::new(),
+        description: String::new(),
+        author: String::new(),
+        language: "en".to_string(),
+        name: None,
+        image: None,
+        twitter: None,
+    };
+
+    let mut robots_config = RobotsConfig {
+        allow: Vec::new(),
+        disallow: Vec::new(),
+    };
+
+    let mut feed_config = FeedConfig {
+        full_content: true,
+        limit: 20,
+    };
+
+    let mut blog_config = BlogConfig {
+        enabled: true,
+        content_dir: PathBuf::from("blogs"),
+        page_prefix: "/blogs".to_string(),
+        api_prefix: "/api".to_string(),
+        per_page: crate::blog::DEFAULT_PER_PAGE,
+        toc: true,
+        preview: false,
+        preview_token: None,
+    };
+
+    let mut highlight_config = HighlightConfig {
+        theme: "light".to_string(),
+    };
+
+    let mut images_config = ImagesConfig {
+        enabled: true,
+        widths: Vec::new(),
+        cache_dir: PathBuf::from(".cache/images"),
+    };
+
+    let mut templates_config = TemplatesConfig {
+        dir: "templates".to_string(),
+        reload: false,
+    };
     
     while i < lines.len() {
         let line = lines[i].trim();
@@ -99,6 +326,20 @@ fn parse_yaml_config(content: &str) -> Result<AppConfig, ConfigError> {
                 } else if sub_line.starts_with("port:") {
                     server_config.port = extract_value(sub_line)?.parse()
                         .map_err(|_| ConfigError::Parse("Invalid port number".to_string()))?;
+                } else if sub_line.starts_with("socket_mode:") {
+                    let raw = extract_value(sub_line)?;
+                    let mode = u32::from_str_radix(raw.trim_start_matches("0o"), 8)
+                        .map_err(|_| ConfigError::Parse(format!("Invalid socket_mode: {}", raw)))?;
+                    server_config.socket_mode = Some(mode);
+                } else if sub_line.starts_with("listen:") {
+                    i += 1;
+                    while i < lines.len() && lines[i].starts_with("    ") {
+                        if let Some(item) = parse_list_item(lines[i].trim()) {
+                            server_config.listen.push(item);
+                        }
+                        i += 1;
+                    }
+                    i -= 1; // Adjust for the outer loop increment
                 }
                 i += 1;
             }
@@ -125,6 +366,159 @@ fn parse_yaml_config(content: &str) -> Result<AppConfig, ConfigError> {
                 }
                 i += 1;
             }
+        } else if line.starts_with("site:") {
+            i += 1;
+            while i < lines.len() && lines[i].starts_with("  ") {
+                let sub_line = lines[i].trim();
+                if sub_line.starts_with("base_url:") {
+                    site_config.base_url = extract_value(sub_line)?.trim_end_matches('/').to_string();
+                } else if sub_line.starts_with("title:") {
+                    site_config.title = extract_value(sub_line)?;
+                } else if sub_line.starts_with("description:") {
+                    site_config.description = extract_value(sub_line)?;
+                } else if sub_line.starts_with("author:") {
+                    site_config.author = extract_value(sub_line)?;
+                } else if sub_line.starts_with("language:") {
+                    site_config.language = extract_value(sub_line)?;
+                } else if sub_line.starts_with("name:") {
+                    let name = extract_value(sub_line)?;
+                    site_config.name = (!name.is_empty()).then_some(name);
+                } else if sub_line.starts_with("image:") {
+                    let image = extract_value(sub_line)?;
+                    site_config.image = (!image.is_empty()).then_some(image);
+                } else if sub_line.starts_with("twitter:") {
+                    let handle = extract_value(sub_line)?;
+                    site_config.twitter = (!handle.is_empty()).then_some(handle);
+                }
+                i += 1;
+            }
+            i -= 1; // Adjust for the outer loop increment
+        } else if line.starts_with("feed:") {
+            i += 1;
+            while i < lines.len() && lines[i].starts_with("  ") {
+                let sub_line = lines[i].trim();
+                if sub_line.starts_with("content:") {
+                    let value = extract_value(sub_line)?;
+                    feed_config.full_content = match value.as_str() {
+                        "full" => true,
+                        "summary" => false,
+                        _ => return Err(ConfigError::Parse(format!("feed.content must be 'full' or 'summary', got '{}'", value))),
+                    };
+                } else if sub_line.starts_with("limit:") {
+                    feed_config.limit = extract_value(sub_line)?.parse()
+                        .map_err(|_| ConfigError::Parse("Invalid feed limit".to_string()))?;
+                }
+                i += 1;
+            }
+            i -= 1; // Adjust for the outer loop increment
+        } else if line.starts_with("blog:") {
+            i += 1;
+            while i < lines.len() && lines[i].starts_with("  ") {
+                let sub_line = lines[i].trim();
+                if sub_line.starts_with("enabled:") {
+                    blog_config.enabled = extract_value(sub_line)?.to_lowercase() == "true";
+                } else if sub_line.starts_with("content_dir:") {
+                    blog_config.content_dir = PathBuf::from(extract_value(sub_line)?);
+                } else if sub_line.starts_with("page_prefix:") {
+                    blog_config.page_prefix = normalize_prefix(&extract_value(sub_line)?);
+                } else if sub_line.starts_with("api_prefix:") {
+                    blog_config.api_prefix = normalize_prefix(&extract_value(sub_line)?);
+                } else if sub_line.starts_with("toc:") {
+                    blog_config.toc = extract_value(sub_line)?.to_lowercase() == "true";
+                } else if sub_line.starts_with("preview:") {
+                    blog_config.preview = extract_value(sub_line)?.to_lowercase() == "true";
+                } else if sub_line.starts_with("preview_token:") {
+                    let token = extract_value(sub_line)?;
+                    blog_config.preview_token = (!token.is_empty()).then_some(token);
+                } else if sub_line.starts_with("per_page:") {
+                    blog_config.per_page = extract_value(sub_line)?.parse()
+                        .map_err(|_| ConfigError::Parse("Invalid blog per_page".to_string()))?;
+                }
+                i += 1;
+            }
+            i -= 1; // Adjust for the outer loop increment
+        } else if line.starts_with("highlight:") {
+            i += 1;
+            while i < lines.len() && lines[i].starts_with("  ") {
+                let sub_line = lines[i].trim();
+                if sub_line.starts_with("theme:") {
+                    highlight_config.theme = extract_value(sub_line)?;
+                }
+                i += 1;
+            }
+            i -= 1; // Adjust for the outer loop increment
+        } else if line.starts_with("images:") {
+            i += 1;
+            while i < lines.len() && lines[i].starts_with("  ") {
+                let sub_line = lines[i].trim();
+                if sub_line.starts_with("enabled:") {
+                    images_config.enabled = extract_value(sub_line)?.to_lowercase() == "true";
+                } else if sub_line.starts_with("cache_dir:") {
+                    images_config.cache_dir = PathBuf::from(extract_value(sub_line)?);
+                } else if sub_line.starts_with("widths:") {
+                    i += 1;
+                    while i < lines.len() && lines[i].starts_with("    ") {
+                        if let Some(item) = parse_list_item(lines[i].trim()) {
+                            let width = item.parse()
+                                .map_err(|_| ConfigError::Parse(format!("Invalid image width: {}", item)))?;
+                            images_config.widths.push(width);
+                        }
+                        i += 1;
+                    }
+                    i -= 1; // Adjust for the outer loop increment
+                }
+                i += 1;
+            }
+            i -= 1; // Adjust for the outer loop increment
+        } else if line.starts_with("templates:") {
+            i += 1;
+            while i < lines.len() && lines[i].starts_with("  ") {
+                let sub_line = lines[i].trim();
+                if sub_line.starts_with("dir:") {
+                    templates_config.dir = extract_value(sub_line)?;
+                } else if sub_line.starts_with("reload:") {
+                    templates_config.reload = extract_value(sub_line)?.to_lowercase() == "true";
+                }
+                i += 1;
+            }
+            i -= 1; // Adjust for the outer loop increment
+        } else if line.starts_with("robots:") {
+            i += 1;
+            while i < lines.len() && lines[i].starts_with("  ") {
+                let sub_line = lines[i].trim();
+                let target = if sub_line.starts_with("allow:") {
+                    Some(&mut robots_config.allow)
+                } else if sub_line.starts_with("disallow:") {
+                    Some(&mut robots_config.disallow)
+                } else {
+                    None
+                };
+                if let Some(list) = target {
+                    i += 1;
+                    while i < lines.len() && lines[i].starts_with("    ") {
+                        if let Some(item) = parse_list_item(lines[i].trim()) {
+                            list.push(item);
+                        }
+                        i += 1;
+                    }
+                    i -= 1; // Adjust for the outer loop increment
+                }
+                i += 1;
+            }
+            i -= 1; // Adjust for the outer loop increment
+        } else if line.starts_with("error_pages:") {
+            i += 1;
+            while i < lines.len() && lines[i].starts_with("  ") {
+                let page_line = lines[i].trim();
+                if !page_line.starts_with('#')
+                    && let Some((status, file)) = parse_key_value(page_line) {
+                    let status: u16 = status.parse()
+                        .map_err(|_| ConfigError::Parse(format!("Invalid error page status: {}", status)))?;
+                    error_pages.insert(status, file);
+                }
+                i += 1;
+            }
+            i -= 1; // Adjust for the outer loop increment
         } else if line.starts_with("content_types:") {
             i += 1;
             while i < lines.len() && lines[i].starts_with("  ") {
@@ -138,13 +532,55 @@ fn parse_yaml_config(content: &str) -> Result<AppConfig, ConfigError> {
         i += 1;
     }
     
+    if let Ok(token) = std::env::var("BLOG_PREVIEW_TOKEN")
+        && !token.trim().is_empty() {
+        blog_config.preview_token = Some(token.trim().to_string());
+    }
+
+    if images_config.widths.is_empty() {
+        images_config.widths = DEFAULT_IMAGE_WIDTHS.to_vec();
+    }
+    images_config.widths.sort_unstable();
+    images_config.widths.dedup();
+
+    if blog_config.content_dir.is_relative() {
+        blog_config.content_dir = Path::new(&static_config.root_dir).join(&blog_config.content_dir);
+    }
+
     Ok(AppConfig {
         server: server_config,
         static_cfg: static_config,
         content_types,
+        site: site_config,
+        feed: feed_config,
+        robots: robots_config,
+        templates: templates_config,
+        blog: blog_config,
+        highlight: highlight_config,
+        images: images_config,
+        error_pages,
     })
 }
 
+/// Compares without stopping at the first difference, so response timing
+/// does not reveal how much of a guessed preview token was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// `blogs/` and `/blogs` both become `/blogs`; `/` becomes the empty string.
+fn normalize_prefix(value: &str) -> String {
+    let trimmed = value.trim().trim_matches('/');
+    if trimmed.is_empty() {
+        String::new()
+    } else {
+        format!("/{}", trimmed)
+    }
+}
+
 fn extract_value(line: &str) -> Result<String, ConfigError> {
     if let Some(colon_pos) = line.find(':') {
         let value = line[colon_pos + 1..].trim();
@@ -158,9 +594,27 @@ fn extract_value(line: &str) -> Result<String, ConfigError> {
     }
 }
 
+fn parse_list_item(line: &str) -> Option<String> {
+    let value = line.strip_prefix('-')?.trim();
+    if value.is_empty() {
+        return None;
+    }
+    if value.starts_with('"') && value.ends_with('"') && value.len() >= 2 {
+        Some(value[1..value.len()-1].to_string())
+    } else {
+        Some(value.to_string())
+    }
+}
+
 fn parse_key_value(line: &str) -> Option<(String, String)> {
     if let Some(colon_pos) = line.find(':') {
         let key = line[..colon_pos].trim();
+        // Keys such as "/blogs" are quoted in the YAML just like values.
+        let key = if key.len() >= 2 && key.starts_with('"') && key.ends_with('"') {
+            &key[1..key.len()-1]
+        } else {
+            key
+        };
         let value = line[colon_pos + 1..].trim();
         let clean_value = if value.starts_with('"') && value.ends_with('"') {
             value[1..value.len()-1].to_string()
@@ -173,3 +627,61 @@ fn parse_key_value(line: &str) -> Option<(String, String)> {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_dir_inside_root_is_rejected_however_spelled() {
+        let dir = std::env::temp_dir().join(format!("portfolio-test-{}-config", std::process::id()));
+        fs::create_dir_all(dir.join("public")).unwrap();
+        std::os::unix::fs::symlink(dir.join("public"), dir.join("link")).unwrap();
+        let load = |cache_dir: &str| {
+            let yaml = format!(
+                "server:\n  host: 127.0.0.1\n  port: 0\n\nstatic:\n  root_dir: {}/public\n  index_file: index.html\n\nimages:\n  cache_dir: {}\n",
+                dir.display(),
+                cache_dir.replace("DIR", &dir.display().to_string())
+            );
+            fs::write(dir.join("config.yaml"), yaml).unwrap();
+            load_config(dir.join("config.yaml"))
+        };
+
+        for inside in ["DIR/public/cache", "DIR/other/../public/cache", "DIR/link/cache", "DIR/public/./new/../cache"] {
+            assert!(matches!(load(inside), Err(ConfigError::Invalid(_))), "{}", inside);
+        }
+        assert!(load("DIR/cache").is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn robots_lists_end_at_the_next_key() {
+        let site = crate::test_support::site(
+            "config-robots",
+            &[],
+            "\nrobots:\n  allow:\n    - /\n  disallow:\n    - /drafts\n    - /private\n\nhighlight:\n  theme: dark\n",
+        );
+        let cfg = site.server.config.as_ref().unwrap();
+        assert_eq!(cfg.robots.allow, ["/"]);
+        assert_eq!(cfg.robots.disallow, ["/drafts", "/private"]);
+        assert_eq!(cfg.highlight.theme, "dark");
+    }
+
+    #[test]
+    fn image_widths_are_sorted_and_deduplicated() {
+        let dir = std::env::temp_dir().join(format!("portfolio-test-{}-config-widths", std::process::id()));
+        fs::create_dir_all(dir.join("public")).unwrap();
+        let yaml = format!(
+            "server:\n  host: 127.0.0.1\n  port: 0\n\nstatic:\n  root_dir: {dir}/public\n  index_file: index.html\n\nimages:\n  widths:\n    - 800\n    - 320\n    - 800\n  cache_dir: {dir}/cache\n",
+            dir = dir.display()
+        );
+        fs::write(dir.join("config.yaml"), yaml).unwrap();
+        let cfg = load_config(dir.join("config.yaml")).unwrap();
+        assert_eq!(cfg.images.widths, [320, 800]);
+        assert_eq!(cfg.images.cache_dir, dir.join("cache"));
+
+        fs::write(dir.join("config.yaml"), "images:\n  widths:\n    - wide\n").unwrap();
+        assert!(matches!(load_config(dir.join("config.yaml")), Err(ConfigError::Parse(_))));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,255 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Serializer};
+
+const MONTHS: [&str; 12] = [
+    "january", "february", "march", "april", "may", "june",
+    "july", "august", "september", "october", "november", "december",
+];
+
+/// A calendar date. Dates given with month precision only ("November 2025")
+/// are pinned to the first of the month so they still sort sensibly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn new(year: i32, month: u32, day: u32) -> Option<Date> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Date { year, month, day })
+    }
+
+    /// Parses the formats used in post metadata:
+    /// `2025-11-09`, `2025/11/09`, `2025-11-09T10:00:00Z`, `November 2025`,
+    /// `Nov 2025`, `Nov 9, 2025`, `November 9 2025` and `9 November 2025`.
+    pub fn parse(input: &str) -> Option<Date> {
+        let input = input.trim();
+        if input.is_empty() {
+            return None;
+        }
+        parse_numeric(input).or_else(|| parse_named(input))
+    }
+
+    pub fn today() -> Date {
+        Date::from_system_time(SystemTime::now())
+    }
+
+    pub fn from_system_time(time: SystemTime) -> Date {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        Date::from_days(secs.div_euclid(86_400))
+    }
+
+    /// Converts days since 1970-01-01 into a civil date.
+    pub fn from_days(days: i64) -> Date {
+        // Howard Hinnant's civil_from_days algorithm.
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + if month <= 2 { 1 } else { 0 }) as i32;
+        Date { year, month, day }
+    }
+
+    /// Days since 1970-01-01.
+    pub fn to_days(&self) -> i64 {
+        let year = if self.month <= 2 { self.year as i64 - 1 } else { self.year as i64 };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let month = self.month as i64;
+        let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    pub fn to_iso(&self) -> String {
+        format!("{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+
+    /// RFC 822 timestamp at midnight UTC, as used by RSS `<pubDate>`.
+    pub fn to_rfc2822(&self) -> String {
+        const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+        let weekday = WEEKDAYS[self.to_days().rem_euclid(7) as usize];
+        format!("{}, {:02} {} {:04} 00:00:00 +0000", weekday, self.day, &self.month_name()[..3], self.year)
+    }
+
+    /// RFC 3339 timestamp at midnight UTC, as used by Atom and JSON Feed.
+    pub fn to_rfc3339(&self) -> String {
+        format!("{}T00:00:00Z", self.to_iso())
+    }
+
+    pub fn month_name(&self) -> &'static str {
+        const NAMES: [&str; 12] = [
+            "January", "February", "March", "April", "May", "June",
+            "July", "August", "September", "October", "November", "December",
+        ];
+        NAMES[(self.month - 1) as usize]
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_iso())
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_iso())
+    }
+}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+fn parse_numeric(input: &str) -> Option<Date> {
+    // Drop any time component from RFC 3339 timestamps.
+    let date_part = input.split(['T', ' ']).next()?;
+    let parts: Vec<&str> = date_part.split(['-', '/']).collect();
+    if parts.len() != 3 || parts[0].len() != 4 {
+        return None;
+    }
+    let year = parts[0].parse().ok()?;
+    let month = parts[1].parse().ok()?;
+    let day = parts[2].parse().ok()?;
+    Date::new(year, month, day)
+}
+
+fn parse_named(input: &str) -> Option<Date> {
+    let words: Vec<&str> = input
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    let mut year = None;
+    let mut month = None;
+    let mut day = None;
+    for word in words {
+        if let Some(m) = month_from_name(word) {
+            month = Some(m);
+        } else if let Ok(n) = word.trim_end_matches(|c: char| c.is_ascii_alphabetic()).parse::<u32>() {
+            // Four digit numbers are years, anything else a day ("9th" included).
+            if word.len() == 4 {
+                year = Some(n as i32);
+            } else {
+                day = Some(n);
+            }
+        } else {
+            return None;
+        }
+    }
+    Date::new(year?, month?, day.unwrap_or(1))
+}
+
+fn month_from_name(word: &str) -> Option<u32> {
+    let word = word.trim_end_matches('.').to_lowercase();
+    if word.len() < 3 {
+        return None;
+    }
+    MONTHS.iter()
+        .position(|name| name.starts_with(&word))
+        .map(|idx| idx as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blog::{sort_posts, BlogPost};
+
+    #[test]
+    fn parses_every_metadata_format() {
+        let cases: &[(&str, Option<&str>)] = &[
+            ("2025-11-09", Some("2025-11-09")),
+            ("2025/11/09", Some("2025-11-09")),
+            ("2025-11-09T10:00:00Z", Some("2025-11-09")),
+            ("  2025-11-09  ", Some("2025-11-09")),
+            ("November 2025", Some("2025-11-01")),
+            ("Nov 2025", Some("2025-11-01")),
+            ("Nov. 9, 2025", Some("2025-11-09")),
+            ("Nov 9, 2025", Some("2025-11-09")),
+            ("November 9 2025", Some("2025-11-09")),
+            ("9 November 2025", Some("2025-11-09")),
+            ("November 9th, 2025", Some("2025-11-09")),
+            ("2024-02-29", Some("2024-02-29")),
+            ("", None),
+            ("Unknown", None),
+            ("2025-13-01", None),
+            ("2025-02-29", None),
+            ("2025-11-31", None),
+            ("25-11-09", None),
+            ("November", None),
+            ("No 2025", None),
+            ("Nov 32, 2025", None),
+            ("Someday in November 2025", None),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(Date::parse(input).map(|d| d.to_iso()).as_deref(), *expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn month_precision_orders_by_month() {
+        let november = Date::parse("November 2025").unwrap();
+        let october = Date::parse("October 2025").unwrap();
+        assert!(november > october);
+        assert!(Date::parse("Oct 31, 2025").unwrap() < november);
+        assert!(Date::parse("2024-12-31").unwrap() < october);
+    }
+
+    #[test]
+    fn feed_timestamps() {
+        let date = Date::new(2025, 11, 9).unwrap();
+        assert_eq!(date.to_iso(), "2025-11-09");
+        assert_eq!(date.to_rfc2822(), "Sun, 09 Nov 2025 00:00:00 +0000");
+        assert_eq!(date.to_rfc3339(), "2025-11-09T00:00:00Z");
+        assert_eq!(Date::new(1970, 1, 1).unwrap().to_rfc2822(), "Thu, 01 Jan 1970 00:00:00 +0000");
+        assert_eq!(Date::new(2024, 2, 29).unwrap().to_rfc2822(), "Thu, 29 Feb 2024 00:00:00 +0000");
+        for days in [-1, 0, 19_999, 20_401] {
+            assert_eq!(Date::from_days(days).to_days(), days);
+        }
+    }
+
+    #[test]
+    fn undated_posts_sort_last_by_slug() {
+        let post = |slug: &str, date: &str| {
+            BlogPost::parse(slug, &format!("---\ntitle: {}\ndate: {}\n---\nBody", slug, date), None)
+        };
+        let mut posts = vec![
+            post("zeta", "Unknown"),
+            post("october", "October 2025"),
+            post("alpha", "Unknown"),
+            post("same-b", "2025-11-01"),
+            post("november", "November 2025"),
+            post("same-a", "2025-11-01"),
+            post("older", "2024-01-05"),
+        ];
+        sort_posts(&mut posts);
+        let slugs: Vec<&str> = posts.iter().map(|p| p.slug.as_str()).collect();
+        assert_eq!(slugs, ["november", "same-a", "same-b", "october", "older", "alpha", "zeta"]);
+        assert_eq!(posts[5].published_date, "Unknown");
+        assert_eq!(posts[5].date, None);
+    }
+}
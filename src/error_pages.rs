@@ -0,0 +1,114 @@
+use std::fs;
+use std::path::Path;
+
+use crate::escape::escape_html;
+use crate::http::{self, Response};
+
+/// Whether a request's `Accept` header asks for JSON rather than HTML.
+/// Browsers list `text/html`, so they keep getting pages even though they
+/// also accept `*/*`; ranges with `q=0` are refused and ignored.
+pub fn wants_json(accept: Option<&str>) -> bool {
+    let Some(accept) = accept else { return false };
+    let mut json = false;
+    for range in accept.split(',') {
+        let mut parts = range.split(';');
+        let media = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        let refused = parts.any(|param| {
+            param.trim().strip_prefix("q=").and_then(|q| q.trim().parse::<f32>().ok()) == Some(0.0)
+        });
+        if refused {
+            continue;
+        }
+        if media == "text/html" || media == "application/xhtml+xml" {
+            return false;
+        }
+        if media == "application/json" || media.ends_with("+json") {
+            json = true;
+        }
+    }
+    json
+}
+
+/// Message shown to clients. Server errors may carry paths or upstream
+/// details, so only their reason phrase leaves the server.
+fn public_message(status: u16, message: &str) -> String {
+    if status >= 500 || message.trim().is_empty() {
+        http::reason_phrase(status).to_string()
+    } else {
+        message.to_string()
+    }
+}
+
+/// Turns an error from `Response::error` into a JSON body of the form
+/// `{"error": "...", "status": 404}`. Headers set by the handler are kept.
+pub fn json(mut response: Response) -> Response {
+    let message = response.error.take().unwrap_or_default();
+    let body = serde_json::json!({
+        "error": public_message(response.status, &message),
+        "status": response.status,
+    });
+    response.content_type = "application/json".to_string();
+    response.body = body.to_string().into_bytes();
+    response
+}
+
+/// Turns an error from `Response::error` into an HTML page: the file at
+/// `page` when one is configured and readable, the built-in page otherwise.
+pub fn html(mut response: Response, page: Option<&Path>) -> Response {
+    let message = response.error.take().unwrap_or_default();
+    let custom = page.and_then(|path| match fs::read(path) {
+        Ok(bytes) => Some(bytes),
+        Err(e) => {
+            eprintln!("Failed to read error page {}: {}", path.display(), e);
+            None
+        }
+    });
+    response.content_type = "text/html; charset=utf-8".to_string();
+    response.body = match custom {
+        Some(bytes) => bytes,
+        None => builtin_page(response.status, &public_message(response.status, &message)).into_bytes(),
+    };
+    response
+}
+
+/// A self-contained page in the site's look, so it renders even when the
+/// stylesheet or the templates are what failed.
+fn builtin_page(status: u16, message: &str) -> String {
+    let reason = escape_html(http::reason_phrase(status));
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="utf-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+    <meta name="robots" content="noindex" />
+    <title>{status} {reason}</title>
+    <style>
+        html, body {{ margin: 0; padding: 0; background: #f0f9ff; color: #0f172a; line-height: 1.6;
+            font-family: ui-sans-serif, system-ui, -apple-system, Segoe UI, Roboto, Helvetica, Arial, sans-serif; }}
+        main {{ width: min(560px, 92%); margin: 12vh auto; padding: 2rem; background: #ffffff;
+            border: 3px solid #0f172a; box-shadow: 8px 8px 0 #0f172a; }}
+        .status {{ display: inline-block; margin: 0; padding: 0.1rem 0.6rem; background: #fbbf24;
+            border: 3px solid #0f172a; font-weight: 800; }}
+        h1 {{ margin: 1rem 0 0.5rem; font-family: ui-serif, Georgia, Cambria, "Times New Roman", Times, serif; }}
+        p {{ color: #475569; }}
+        a {{ display: inline-block; margin-top: 1rem; padding: 0.5rem 1rem; background: #0ea5e9; color: #0f172a;
+            border: 3px solid #0f172a; box-shadow: 4px 4px 0 #0f172a; font-weight: 700; text-decoration: none; }}
+        a:hover {{ box-shadow: 2px 2px 0 #0f172a; transform: translate(2px, 2px); }}
+    </style>
+</head>
+<body>
+    <main>
+        <p class="status">{status}</p>
+        <h1>{reason}</h1>
+        <p>{message}</p>
+        <a href="/">Back to the home page</a>
+    </main>
+</body>
+</html>
+"#,
+        status = status,
+        reason = reason,
+        message = escape_html(message),
+    )
+}
@@ -0,0 +1,25 @@
+/// Escapes text for use in HTML or XML element content and quoted
+/// attribute values.
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// XML needs the same five characters escaped; control characters other
+/// than tab and newlines are not allowed at all in XML 1.0 and are dropped.
+pub fn escape_xml(text: &str) -> String {
+    let cleaned: String = text.chars()
+        .filter(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    escape_html(&cleaned).replace("&#39;", "&apos;")
+}
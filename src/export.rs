@@ -0,0 +1,224 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::blog;
+use crate::date::Date;
+use crate::http;
+use crate::links::{extract_links, internal_route};
+use crate::server::Server;
+use crate::static_files::canonicalize_missing;
+use crate::tags;
+
+/// An internal link that does not lead to a page the server serves.
+#[derive(Debug)]
+pub struct BrokenLink {
+    /// Route of the page containing the link.
+    pub page: String,
+    /// The link as written in the page.
+    pub link: String,
+    pub status: u16,
+}
+
+#[derive(Debug, Default)]
+pub struct ExportReport {
+    pub files: usize,
+    pub broken: Vec<BrokenLink>,
+}
+
+/// Pre-renders every route the server can answer into `out_dir`, so the site
+/// can be mirrored on static hosting:
+///
+/// * static files keep their path; blog post sources (`.md` files in the blog
+///   directory) are skipped as drafts must not leak,
+/// * HTML pages without an extension become `<route>/index.html`,
+/// * JSON endpoints become `<route>.json` (e.g. `api/blogs.json`),
+/// * feeds, `sitemap.xml`, `robots.txt` and `highlight.css` keep their names.
+///
+/// Links in every exported HTML page are followed, so pages only reachable
+/// through links are exported too, and links the server answers with
+/// anything but 200 are reported. Query strings and fragments are ignored as
+/// a static host cannot serve them.
+pub fn export(server: &Server, out_dir: &Path) -> Result<ExportReport, String> {
+    let cfg = server.config.as_ref().ok_or("Server configuration missing")?;
+    let resolver = server.resolver.as_ref().ok_or("Static file resolver not initialized")?;
+
+    let out_canon = canonicalize_missing(out_dir)
+        .map_err(|e| format!("Failed to resolve {}: {}", out_dir.display(), e))?;
+    let root_canon = fs::canonicalize(resolver.root_dir())
+        .map_err(|e| format!("Failed to resolve static root: {}", e))?;
+    if out_canon.starts_with(&root_canon) || root_canon.starts_with(&out_canon) {
+        return Err(format!(
+            "Output directory {} overlaps the static root {}",
+            out_dir.display(),
+            resolver.root_dir().display()
+        ));
+    }
+    fs::create_dir_all(out_dir)
+        .map_err(|e| format!("Failed to create {}: {}", out_dir.display(), e))?;
+
+    let mut seeds: Vec<String> = Vec::new();
+    for (url, file) in resolver.files() {
+        if !blog::is_post_source(&file, &cfg.blog.content_dir) {
+            seeds.push(url);
+        }
+    }
+    seeds.extend(resolver.html_pages().into_iter().map(|(url, _)| url));
+    seeds.extend(["/sitemap.xml", "/robots.txt", "/highlight.css"].map(String::from));
+
+    let blog = &cfg.blog;
+    if blog.enabled {
+        let posts = server.blog_store.published(Date::today())?;
+        seeds.extend(["/feed.xml", "/atom.xml", "/feed.json"].map(String::from));
+        seeds.push(blog.api_path("blogs"));
+        seeds.push(blog.api_path("tags"));
+        for post in &posts {
+            seeds.push(blog.post_url(&post.slug));
+            seeds.push(blog.api_path(&format!("blog/{}", post.slug)));
+        }
+        for tag in tags::collect_tags(&posts, blog) {
+            seeds.push(blog.api_path(&format!("tags/{}", tag.slug)));
+            seeds.push(tag.url);
+        }
+    }
+
+    let mut report = ExportReport::default();
+    let mut queue: VecDeque<String> = VecDeque::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    for seed in seeds {
+        if seen.insert(seed.clone()) {
+            queue.push_back(seed);
+        }
+    }
+
+    let mut written: HashSet<PathBuf> = HashSet::new();
+    let mut statuses: HashMap<String, u16> = HashMap::new();
+    // (page, link as written, resolved route)
+    let mut references: Vec<(String, String, String)> = Vec::new();
+
+    while let Some(route) = queue.pop_front() {
+        let response = server.dispatch("GET", &route, None, None);
+        statuses.insert(route.clone(), response.status);
+        if response.status != 200 {
+            continue;
+        }
+
+        let Some(relative) = output_path(&route, &response.content_type) else {
+            eprintln!("Skipping route that cannot be stored as a file: {}", route);
+            continue;
+        };
+        let target = out_dir.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        fs::write(&target, &response.body)
+            .map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+        written.insert(target);
+
+        if !response.content_type.starts_with("text/html") {
+            continue;
+        }
+        let html = String::from_utf8_lossy(&response.body);
+        for (_, link) in extract_links(&html) {
+            let Some(linked) = internal_route(&route, &link, &cfg.site.base_url) else { continue };
+            if seen.insert(linked.clone()) {
+                queue.push_back(linked.clone());
+            }
+            references.push((route.clone(), link, linked));
+        }
+    }
+
+    report.files = written.len();
+    for (page, link, linked) in references {
+        let status = statuses.get(&linked).copied().unwrap_or(404);
+        if status != 200 {
+            report.broken.push(BrokenLink { page, link, status });
+        }
+    }
+    report.broken.sort_by(|a, b| a.page.cmp(&b.page).then_with(|| a.link.cmp(&b.link)));
+    report.broken.dedup_by(|a, b| a.page == b.page && a.link == b.link);
+    Ok(report)
+}
+
+/// File (relative to the output directory) that stores `route`. `None` for
+/// routes whose decoded segments would escape the output directory.
+fn output_path(route: &str, content_type: &str) -> Option<PathBuf> {
+    let decoded = http::percent_decode(route).unwrap_or_else(|| route.to_string());
+    let mut path = PathBuf::new();
+    for segment in decoded.split('/').filter(|s| !s.is_empty()) {
+        if segment == "." || segment == ".." || segment.contains('\\') || segment.contains('\0') {
+            return None;
+        }
+        path.push(segment);
+    }
+    if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return None;
+    }
+
+    let has_extension = path.extension().is_some();
+    if decoded.ends_with('/') || path.as_os_str().is_empty()
+        || (!has_extension && content_type.starts_with("text/html")) {
+        path.push("index.html");
+    } else if !has_extension && content_type.starts_with("application/json") {
+        path.set_extension("json");
+    }
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::site;
+
+    #[test]
+    fn routes_that_escape_are_rejected() {
+        for route in [
+            "/../etc/passwd",
+            "/blogs/%2e%2e/%2e%2e/secret",
+            "/a/./b",
+            "/a\\..\\b",
+            "/a%5C..%5Cb",
+            "/a%00b",
+        ] {
+            assert_eq!(output_path(route, "text/html"), None, "{:?}", route);
+        }
+    }
+
+    #[test]
+    fn html_and_json_naming() {
+        for (route, content_type, expected) in [
+            ("/", "text/html; charset=utf-8", "index.html"),
+            ("", "text/html", "index.html"),
+            ("/blogs", "text/html; charset=utf-8", "blogs/index.html"),
+            ("/blogs/", "application/json", "blogs/index.html"),
+            ("/blogs/hello%20world", "text/html", "blogs/hello world/index.html"),
+            ("/about.html", "text/html", "about.html"),
+            ("/api/blogs", "application/json", "api/blogs.json"),
+            ("/feed.json", "application/feed+json", "feed.json"),
+            ("/feed.xml", "application/rss+xml", "feed.xml"),
+            ("/LICENSE", "text/plain", "LICENSE"),
+        ] {
+            assert_eq!(output_path(route, content_type), Some(PathBuf::from(expected)), "{:?}", route);
+        }
+    }
+
+    #[test]
+    fn output_cannot_overlap_the_static_root() {
+        let site = site("export", &[("hello.md", "---\ntitle: Hello\ndate: 2020-01-01\n---\nHi")], "");
+        let public = site.dir.join("public");
+        for out_dir in [public.clone(), public.join("dist"), public.join("blogs/new/dist"), site.dir.clone()] {
+            let err = export(&site.server, &out_dir).unwrap_err();
+            assert!(err.contains("overlaps the static root"), "{}: {}", out_dir.display(), err);
+        }
+        assert!(!public.join("dist").exists());
+
+        let out_dir = site.dir.join("dist");
+        let report = export(&site.server, &out_dir).unwrap();
+        assert!(report.files >= 5);
+        for file in ["index.html", "blogs/hello/index.html", "api/blogs.json", "feed.xml", "sitemap.xml"] {
+            assert!(out_dir.join(file).is_file(), "{}", file);
+        }
+        assert!(!out_dir.join("blogs/hello.md").exists());
+    }
+}
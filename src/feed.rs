@@ -0,0 +1,400 @@
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::blog::{self, BlogPost};
+use crate::config::{BlogConfig, FeedConfig, SiteConfig};
+use crate::date::Date;
+use crate::escape::escape_xml;
+
+/// Posts selected for syndication, newest first, with their absolute URLs and
+/// the content to publish already resolved.
+pub struct Feed<'a> {
+    site: &'a SiteConfig,
+    blog: &'a BlogConfig,
+    entries: Vec<FeedEntry>,
+}
+
+struct FeedEntry {
+    url: String,
+    post: Arc<BlogPost>,
+    content_html: Option<String>,
+}
+
+impl<'a> Feed<'a> {
+    pub fn new(mut posts: Vec<Arc<BlogPost>>, site: &'a SiteConfig, blog: &'a BlogConfig, cfg: &FeedConfig) -> Feed<'a> {
+        blog::sort_posts(&mut posts);
+        posts.truncate(cfg.limit);
+        let entries = posts.into_iter()
+            .map(|post| FeedEntry {
+                url: format!("{}{}", site.base_url, blog.post_url(&post.slug)),
+                content_html: cfg.full_content.then(|| absolutize_links(&post.html, &site.base_url)),
+                post,
+            })
+            .collect();
+        Feed { site, blog, entries }
+    }
+
+    fn updated(&self) -> Date {
+        self.entries.iter().filter_map(|e| e.post.date).max().unwrap_or_else(Date::today)
+    }
+
+    fn home_page_url(&self) -> String {
+        format!("{}{}", self.site.base_url, self.blog.page_prefix)
+    }
+
+    fn title(&self) -> &str {
+        if self.site.title.is_empty() { "Blog" } else { &self.site.title }
+    }
+
+    /// RSS 2.0 document served at `/feed.xml`.
+    pub fn to_rss(&self) -> String {
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n<channel>\n");
+        push_element(&mut xml, 1, "title", self.title());
+        push_element(&mut xml, 1, "link", &self.home_page_url());
+        push_element(&mut xml, 1, "description", &self.site.description);
+        push_element(&mut xml, 1, "language", &self.site.language);
+        push_element(&mut xml, 1, "lastBuildDate", &self.updated().to_rfc2822());
+        xml.push_str(&format!(
+            "  <atom:link href=\"{}/feed.xml\" rel=\"self\" type=\"application/rss+xml\" />\n",
+            escape_xml(&self.site.base_url)
+        ));
+
+        for entry in &self.entries {
+            let post = &entry.post;
+            xml.push_str("  <item>\n");
+            push_element(&mut xml, 2, "title", &post.title);
+            push_element(&mut xml, 2, "link", &entry.url);
+            xml.push_str(&format!("    <guid isPermaLink=\"true\">{}</guid>\n", escape_xml(&entry.url)));
+            if let Some(date) = post.date {
+                push_element(&mut xml, 2, "pubDate", &date.to_rfc2822());
+            }
+            for tag in &post.tags {
+                push_element(&mut xml, 2, "category", tag);
+            }
+            if let Some(content) = &entry.content_html {
+                push_element(&mut xml, 2, "description", content);
+            } else if let Some(summary) = summary(post) {
+                push_element(&mut xml, 2, "description", summary);
+            }
+            xml.push_str("  </item>\n");
+        }
+
+        xml.push_str("</channel>\n</rss>\n");
+        xml
+    }
+
+    /// Atom 1.0 document served at `/atom.xml`.
+    pub fn to_atom(&self) -> String {
+        let base = &self.site.base_url;
+        let mut xml = String::new();
+        xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        xml.push_str(&format!(
+            "<feed xmlns=\"http://www.w3.org/2005/Atom\" xml:lang=\"{}\">\n",
+            escape_xml(&self.site.language)
+        ));
+        push_element(&mut xml, 1, "title", self.title());
+        if !self.site.description.is_empty() {
+            push_element(&mut xml, 1, "subtitle", &self.site.description);
+        }
+        push_element(&mut xml, 1, "id", &self.home_page_url());
+        xml.push_str(&format!("  <link href=\"{}\" />\n", escape_xml(&self.home_page_url())));
+        xml.push_str(&format!("  <link href=\"{}/atom.xml\" rel=\"self\" />\n", escape_xml(base)));
+        push_element(&mut xml, 1, "updated", &self.updated().to_rfc3339());
+        if !self.site.author.is_empty() {
+            xml.push_str(&format!("  <author><name>{}</name></author>\n", escape_xml(&self.site.author)));
+        }
+
+        for entry in &self.entries {
+            let post = &entry.post;
+            xml.push_str("  <entry>\n");
+            push_element(&mut xml, 2, "title", &post.title);
+            push_element(&mut xml, 2, "id", &entry.url);
+            xml.push_str(&format!("    <link href=\"{}\" />\n", escape_xml(&entry.url)));
+            let updated = post.date.unwrap_or_else(|| self.updated()).to_rfc3339();
+            if post.date.is_some() {
+                push_element(&mut xml, 2, "published", &updated);
+            }
+            push_element(&mut xml, 2, "updated", &updated);
+            if let Some(author) = &post.author {
+                xml.push_str(&format!("    <author><name>{}</name></author>\n", escape_xml(author)));
+            }
+            for tag in &post.tags {
+                xml.push_str(&format!("    <category term=\"{}\" />\n", escape_xml(tag)));
+            }
+            if let Some(summary) = summary(post) {
+                push_element(&mut xml, 2, "summary", summary);
+            }
+            if let Some(content) = &entry.content_html {
+                xml.push_str(&format!("    <content type=\"html\">{}</content>\n", escape_xml(content)));
+            }
+            xml.push_str("  </entry>\n");
+        }
+
+        xml.push_str("</feed>\n");
+        xml
+    }
+
+    /// JSON Feed 1.1 document served at `/feed.json`.
+    pub fn to_json(&self) -> Result<String, String> {
+        #[derive(Serialize)]
+        struct JsonFeed<'a> {
+            version: &'static str,
+            title: &'a str,
+            home_page_url: String,
+            feed_url: String,
+            #[serde(skip_serializing_if = "str::is_empty")]
+            description: &'a str,
+            language: &'a str,
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            authors: Vec<JsonAuthor<'a>>,
+            items: Vec<JsonItem<'a>>,
+        }
+
+        #[derive(Serialize)]
+        struct JsonAuthor<'a> {
+            name: &'a str,
+        }
+
+        #[derive(Serialize)]
+        struct JsonItem<'a> {
+            id: &'a str,
+            url: &'a str,
+            title: &'a str,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            content_html: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            summary: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            date_published: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            image: Option<String>,
+            #[serde(skip_serializing_if = "<[String]>::is_empty")]
+            tags: &'a [String],
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            authors: Vec<JsonAuthor<'a>>,
+        }
+
+        let base = &self.site.base_url;
+        let feed = JsonFeed {
+            version: "https://jsonfeed.org/version/1.1",
+            title: self.title(),
+            home_page_url: self.home_page_url(),
+            feed_url: format!("{}/feed.json", base),
+            description: &self.site.description,
+            language: &self.site.language,
+            authors: if self.site.author.is_empty() { Vec::new() } else { vec![JsonAuthor { name: &self.site.author }] },
+            items: self.entries.iter()
+                .map(|entry| {
+                    let post = &entry.post;
+                    JsonItem {
+                        id: &entry.url,
+                        url: &entry.url,
+                        title: &post.title,
+                        // JSON Feed requires either content or a summary.
+                        content_html: entry.content_html.as_deref()
+                            .or(if summary(post).is_none() { Some("") } else { None }),
+                        summary: summary(post),
+                        date_published: post.date.map(|d| d.to_rfc3339()),
+                        image: post.cover_image.as_ref().map(|img| absolute_url(img, base)),
+                        tags: &post.tags,
+                        authors: post.author.iter().map(|name| JsonAuthor { name }).collect(),
+                    }
+                })
+                .collect(),
+        };
+
+        serde_json::to_string(&feed).map_err(|e| format!("Failed to serialize JSON feed: {}", e))
+    }
+}
+
+/// The post's explicit summary, or its generated excerpt.
+fn summary(post: &BlogPost) -> Option<&str> {
+    Some(post.excerpt.as_str()).filter(|excerpt| !excerpt.is_empty())
+}
+
+fn push_element(xml: &mut String, depth: usize, name: &str, text: &str) {
+    xml.push_str(&"  ".repeat(depth));
+    xml.push_str(&format!("<{}>{}</{}>\n", name, escape_xml(text), name));
+}
+
+/// Site-relative URLs resolve against the page in a browser but have nothing
+/// to resolve against inside a feed reader, so they are made absolute.
+fn absolutize_links(html: &str, base_url: &str) -> String {
+    let html = html.replace("href=\"/", &format!("href=\"{}/", base_url))
+        .replace("src=\"/", &format!("src=\"{}/", base_url))
+        .replace(&format!("=\"{}//", base_url), "=\"//");
+    absolutize_srcsets(&html, base_url)
+}
+
+/// A `srcset` lists several `<url> <descriptor>` candidates separated by
+/// commas; each URL is made absolute on its own.
+fn absolutize_srcsets(html: &str, base_url: &str) -> String {
+    const ATTRIBUTE: &str = "srcset=\"";
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(pos) = rest.find(ATTRIBUTE) {
+        let start = pos + ATTRIBUTE.len();
+        let Some(len) = rest[start..].find('"') else { break };
+        out.push_str(&rest[..start]);
+        let candidates: Vec<String> = rest[start..start + len].split(',')
+            .map(|candidate| match candidate.trim().split_once(char::is_whitespace) {
+                Some((url, descriptor)) => format!("{} {}", absolute_url(url, base_url), descriptor.trim()),
+                None => absolute_url(candidate.trim(), base_url),
+            })
+            .collect();
+        out.push_str(&candidates.join(", "));
+        rest = &rest[start + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+pub fn absolute_url(url: &str, base_url: &str) -> String {
+    if url.starts_with('/') && !url.starts_with("//") {
+        format!("{}{}", base_url, url)
+    } else {
+        url.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::images::ImageContext;
+    use crate::test_support::site;
+
+    use super::*;
+
+    #[test]
+    fn full_content_images_get_absolute_srcsets() {
+        let site = site("feed-images", &[], "");
+        image::RgbImage::new(1200, 600).save(site.dir.join("public/blogs/photo.png")).unwrap();
+        let images = ImageContext {
+            root_dir: site.dir.join("public"),
+            page_prefix: "/blogs".to_string(),
+            widths: vec![480, 960],
+        };
+        let post = BlogPost::parse("pics", "---\ntitle: Pics\ndate: 2025-11-09\n---\n![A photo](/blogs/photo.png)", Some(&images));
+        assert!(post.html.contains("srcset=\"/blogs/photo.png?w=480 480w, "), "{}", post.html);
+
+        let cfg = site.server.config.as_ref().unwrap();
+        let feed = Feed::new(vec![Arc::new(post)], &cfg.site, &cfg.blog, &FeedConfig { full_content: true, limit: 10 });
+        let base = &cfg.site.base_url;
+        let srcset = format!(
+            "srcset=\"{base}/blogs/photo.png?w=480 480w, {base}/blogs/photo.png?w=960 960w, {base}/blogs/photo.png 1200w\"",
+            base = base
+        );
+        let json: serde_json::Value = serde_json::from_str(&feed.to_json().unwrap()).unwrap();
+        let content = json["items"][0]["content_html"].as_str().unwrap();
+        assert!(content.contains(&srcset), "{}", content);
+        assert!(content.contains(&format!("src=\"{}/blogs/photo.png\"", base)), "{}", content);
+        assert!(feed.to_rss().contains(&escape_xml(&srcset)));
+        assert!(feed.to_atom().contains(&escape_xml(&srcset)));
+    }
+
+    /// Checks that every element is closed in order and no raw `<` or `&`
+    /// appears in text, which is what a feed reader's XML parser requires.
+    fn assert_well_formed(xml: &str) {
+        let mut open: Vec<&str> = Vec::new();
+        let mut rest = xml.strip_prefix("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n").expect("XML declaration");
+        while let Some(lt) = rest.find('<') {
+            let text = &rest[..lt];
+            for (idx, _) in text.match_indices('&') {
+                let entity = &text[idx..text[idx..].find(';').map(|end| idx + end + 1).unwrap_or(text.len())];
+                assert!(["&amp;", "&lt;", "&gt;", "&quot;", "&#39;"].contains(&entity), "bad entity {:?}", entity);
+            }
+            let gt = rest[lt..].find('>').expect("unterminated tag") + lt;
+            let tag = &rest[lt + 1..gt];
+            assert!(!tag.contains('<'), "{:?}", tag);
+            if let Some(name) = tag.strip_prefix('/') {
+                assert_eq!(open.pop(), Some(name));
+            } else if !tag.ends_with('/') {
+                open.push(tag.split_whitespace().next().unwrap());
+            }
+            rest = &rest[gt + 1..];
+        }
+        assert!(open.is_empty(), "unclosed {:?}", open);
+    }
+
+    fn feed_site(name: &str) -> (crate::test_support::Site, Vec<Arc<BlogPost>>) {
+        let site = site(name, &[], "");
+        let posts = vec![
+            Arc::new(BlogPost::parse(
+                "tricky",
+                "---\ntitle: \"Fish & <Chips>\"\ndate: 2025-11-09\ntags: [r&d]\nauthor: A <B>\ncover_image: /img/cover.png\n---\n\
+                 Read [this](/blogs/other) & see ![x](/img/x.png) or [that](https://example.com/).",
+                None,
+            )),
+            Arc::new(BlogPost::parse("older", "---\ntitle: Older\ndate: 2024-02-29\nsummary: Old news\n---\nBody", None)),
+        ];
+        (site, posts)
+    }
+
+    #[test]
+    fn rss_is_well_formed_with_absolute_links() {
+        let (site, posts) = feed_site("feed-rss");
+        let cfg = site.server.config.as_ref().unwrap();
+        let base = &cfg.site.base_url;
+        let rss = Feed::new(posts, &cfg.site, &cfg.blog, &FeedConfig { full_content: true, limit: 10 }).to_rss();
+        assert_well_formed(&rss);
+        assert!(rss.contains("<title>Fish &amp; &lt;Chips&gt;</title>"), "{}", rss);
+        assert!(rss.contains(&format!("<link>{}/blogs/tricky</link>", base)));
+        assert!(rss.contains("<pubDate>Sun, 09 Nov 2025 00:00:00 +0000</pubDate>"));
+        assert!(rss.contains("<lastBuildDate>Sun, 09 Nov 2025 00:00:00 +0000</lastBuildDate>"));
+        assert!(rss.contains("<category>r&amp;d</category>"));
+        assert!(rss.contains(&format!("href=&quot;{}/blogs/other&quot;", base)), "{}", rss);
+        assert!(rss.contains(&format!("src=&quot;{}/img/x.png&quot;", base)));
+        assert!(rss.contains("href=&quot;https://example.com/&quot;"));
+        assert!(rss.find("/blogs/tricky").unwrap() < rss.find("/blogs/older").unwrap());
+    }
+
+    #[test]
+    fn atom_is_well_formed_with_absolute_links() {
+        let (site, posts) = feed_site("feed-atom");
+        let cfg = site.server.config.as_ref().unwrap();
+        let base = &cfg.site.base_url;
+        let atom = Feed::new(posts, &cfg.site, &cfg.blog, &FeedConfig { full_content: false, limit: 1 }).to_atom();
+        assert_well_formed(&atom);
+        assert!(atom.contains("<title>Fish &amp; &lt;Chips&gt;</title>"));
+        assert!(atom.contains(&format!("<link href=\"{}/blogs/tricky\" />", base)));
+        assert!(atom.contains(&format!("<link href=\"{}/atom.xml\" rel=\"self\" />", base)));
+        assert!(atom.contains("<published>2025-11-09T00:00:00Z</published>"));
+        assert!(atom.contains("<author><name>A &lt;B&gt;</name></author>"));
+        assert!(atom.contains("<category term=\"r&amp;d\" />"));
+        assert!(!atom.contains("<content"), "summary only without full content");
+        assert!(!atom.contains("/blogs/older"), "limited to one entry");
+    }
+
+    #[test]
+    fn json_feed_has_absolute_urls() {
+        let (site, posts) = feed_site("feed-json");
+        let cfg = site.server.config.as_ref().unwrap();
+        let base = &cfg.site.base_url;
+        let feed = Feed::new(posts, &cfg.site, &cfg.blog, &FeedConfig { full_content: true, limit: 10 });
+        let json: serde_json::Value = serde_json::from_str(&feed.to_json().unwrap()).unwrap();
+        assert_eq!(json["version"], "https://jsonfeed.org/version/1.1");
+        assert_eq!(json["feed_url"], format!("{}/feed.json", base));
+        let item = &json["items"][0];
+        assert_eq!(item["title"], "Fish & <Chips>");
+        assert_eq!(item["url"], format!("{}/blogs/tricky", base));
+        assert_eq!(item["image"], format!("{}/img/cover.png", base));
+        assert_eq!(item["date_published"], "2025-11-09T00:00:00Z");
+        assert!(item["content_html"].as_str().unwrap().contains(&format!("href=\"{}/blogs/other\"", base)));
+        assert_eq!(json["items"][1]["summary"], "Old news");
+    }
+
+    #[test]
+    fn absolute_urls() {
+        let base = "https://example.com";
+        assert_eq!(absolute_url("/img/a.png", base), "https://example.com/img/a.png");
+        assert_eq!(absolute_url("//cdn.example.org/a.png", base), "//cdn.example.org/a.png");
+        assert_eq!(absolute_url("https://other.org/a.png", base), "https://other.org/a.png");
+        assert_eq!(absolute_url("relative.png", base), "relative.png");
+        assert_eq!(
+            absolutize_links("<a href=\"//cdn.example.org/x\"><img src=\"/a.png\" srcset=\"/a.png?w=10 10w, https://x.org/b.png 20w\"></a>", base),
+            "<a href=\"//cdn.example.org/x\"><img src=\"https://example.com/a.png\" srcset=\"https://example.com/a.png?w=10 10w, https://x.org/b.png 20w\"></a>"
+        );
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::{HashMap, HashSet};
+
+use pulldown_cmark::{Event, Tag};
+
+use crate::escape::escape_html;
+
+/// Markdown pass rendering footnotes as a numbered list at the end of the
+/// post instead of wherever their definitions were written. Footnotes are
+/// numbered in order of first reference; each reference links to its note
+/// and each note links back to its first reference. Definitions that are
+/// never referenced are listed after the rest, and references to missing
+/// definitions are kept as plain text.
+pub fn collect_footnotes<'a, I: Iterator<Item = Event<'a>>>(events: I) -> Vec<Event<'a>> {
+    let events: Vec<Event<'a>> = events.collect();
+
+    // Definitions first, so references to missing notes can be detected.
+    let mut definitions: HashMap<String, Vec<Event<'a>>> = HashMap::new();
+    let mut definition_order: Vec<String> = Vec::new();
+    let mut body = Vec::with_capacity(events.len());
+    let mut current: Option<(String, Vec<Event<'a>>)> = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::FootnoteDefinition(name)) => {
+                current = Some((name.to_string(), Vec::new()));
+            }
+            Event::End(Tag::FootnoteDefinition(_)) => {
+                if let Some((name, inner)) = current.take()
+                    && !definitions.contains_key(&name) {
+                    definition_order.push(name.clone());
+                    definitions.insert(name, inner);
+                }
+            }
+            event => match current.as_mut() {
+                Some((_, inner)) => inner.push(event),
+                None => body.push(event),
+            },
+        }
+    }
+    if definitions.is_empty() {
+        return body;
+    }
+    let ids = footnote_ids(&definition_order);
+
+    let mut numbers: HashMap<String, usize> = HashMap::new();
+    let mut order: Vec<String> = Vec::new();
+    let mut out = Vec::with_capacity(body.len());
+    for event in body {
+        let Event::FootnoteReference(name) = &event else {
+            out.push(event);
+            continue;
+        };
+        if !definitions.contains_key(name.as_ref()) {
+            out.push(Event::Text(format!("[^{}]", name).into()));
+            continue;
+        }
+        let first = !numbers.contains_key(name.as_ref());
+        let number = *numbers.entry(name.to_string()).or_insert_with(|| {
+            order.push(name.to_string());
+            order.len()
+        });
+        let id = &ids[name.as_ref()];
+        // Only the first reference gets an id for the back link to target.
+        let ref_id = if first { format!(" id=\"fnref-{}\"", id) } else { String::new() };
+        out.push(Event::Html(format!(
+            "<sup class=\"footnote-reference\"{}><a href=\"#fn-{}\">{}</a></sup>",
+            ref_id, id, number
+        ).into()));
+    }
+    for name in definition_order {
+        if !numbers.contains_key(&name) {
+            order.push(name);
+        }
+    }
+
+    out.push(Event::Html("<div class=\"footnotes\">\n<hr />\n<ol>\n".into()));
+    for name in order {
+        let id = &ids[&name];
+        out.push(Event::Html(format!("<li id=\"fn-{}\">\n", id).into()));
+        out.extend(definitions.remove(&name).unwrap_or_default());
+        if numbers.contains_key(&name) {
+            out.push(Event::Html(format!(
+                "<a class=\"footnote-backref\" href=\"#fnref-{}\" title=\"Back to text\">↩</a>\n",
+                id
+            ).into()));
+        }
+        out.push(Event::Html("</li>\n".into()));
+    }
+    out.push(Event::Html("</ol>\n</div>\n".into()));
+    out
+}
+
+/// Footnote labels may contain spaces and punctuation; ids keep only
+/// characters that need no escaping in a fragment. Labels that differ only
+/// in those characters, such as `a b` and `a-b`, get numbered suffixes in
+/// definition order so every id stays unique.
+fn footnote_ids(names: &[String]) -> HashMap<String, String> {
+    let mut used = HashSet::new();
+    let mut ids = HashMap::new();
+    for name in names {
+        let base: String = name.chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c.to_ascii_lowercase() } else { '-' })
+            .collect();
+        let mut id = base.clone();
+        let mut n = 2;
+        while !used.insert(id.clone()) {
+            id = format!("{}-{}", base, n);
+            n += 1;
+        }
+        ids.insert(name.clone(), escape_html(&id));
+    }
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::{html, Options, Parser};
+
+    use super::*;
+
+    fn render(markdown: &str) -> String {
+        let mut out = String::new();
+        html::push_html(&mut out, collect_footnotes(Parser::new_ext(markdown, Options::all())).into_iter());
+        out
+    }
+
+    #[test]
+    fn numbered_by_first_reference() {
+        let html = render("B[^b] then A[^a] and B again[^b].\n\n[^a]: Note A.\n[^b]: Note B.\n");
+        assert!(html.contains("<sup class=\"footnote-reference\" id=\"fnref-b\"><a href=\"#fn-b\">1</a></sup>"));
+        assert!(html.contains("<sup class=\"footnote-reference\" id=\"fnref-a\"><a href=\"#fn-a\">2</a></sup>"));
+        assert!(html.contains("<sup class=\"footnote-reference\"><a href=\"#fn-b\">1</a></sup>"));
+        assert!(html.find("<li id=\"fn-b\">").unwrap() < html.find("<li id=\"fn-a\">").unwrap());
+    }
+
+    #[test]
+    fn ids_stay_unique() {
+        let html = render("One[^a b] two[^a-b].\n\n[^a b]: Spaced.\n[^a-b]: Dashed.\n");
+        assert!(html.contains("<li id=\"fn-a-b\">"), "{}", html);
+        assert!(html.contains("<li id=\"fn-a-b-2\">"), "{}", html);
+        assert!(html.contains("href=\"#fn-a-b\""));
+        assert!(html.contains("href=\"#fn-a-b-2\""));
+    }
+
+    #[test]
+    fn missing_definitions_stay_text() {
+        let html = render("Known[^a] unknown[^zzz].\n\n[^a]: Note.\n");
+        assert!(html.contains("unknown[^zzz]."));
+        assert!(!html.contains("fn-zzz"));
+    }
+
+    #[test]
+    fn no_section_without_definitions() {
+        assert_eq!(render("Plain text."), "<p>Plain text.</p>\n");
+    }
+}
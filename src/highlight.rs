@@ -0,0 +1,519 @@
+use pulldown_cmark::{CodeBlockKind, Event, Tag};
+
+use crate::escape::escape_html;
+
+/// Themes served at `/highlight.css`. They only style the `hl-*` classes
+/// emitted by `highlight`, so switching theme never requires re-rendering.
+pub const THEMES: &[(&str, &str)] = &[
+    ("light", LIGHT_THEME),
+    ("dark", DARK_THEME),
+];
+
+const LIGHT_THEME: &str = "\
+pre code.hl { display: block; background: #f6f8fa; color: #24292f; }
+.hl-comment { color: #6e7781; font-style: italic; }
+.hl-keyword { color: #cf222e; }
+.hl-type { color: #953800; }
+.hl-string { color: #0a3069; }
+.hl-number, .hl-literal { color: #0550ae; }
+.hl-function { color: #8250df; }
+.hl-macro, .hl-attribute { color: #116329; }
+.hl-key { color: #0550ae; }
+.hl-variable { color: #953800; }
+";
+
+const DARK_THEME: &str = "\
+pre code.hl { display: block; background: #0d1117; color: #c9d1d9; }
+.hl-comment { color: #8b949e; font-style: italic; }
+.hl-keyword { color: #ff7b72; }
+.hl-type { color: #ffa657; }
+.hl-string { color: #a5d6ff; }
+.hl-number, .hl-literal { color: #79c0ff; }
+.hl-function { color: #d2a8ff; }
+.hl-macro, .hl-attribute { color: #7ee787; }
+.hl-key { color: #79c0ff; }
+.hl-variable { color: #ffa657; }
+";
+
+pub fn theme_css(name: &str) -> Option<&'static str> {
+    THEMES.iter().find(|(theme, _)| *theme == name).map(|(_, css)| *css)
+}
+
+/// How a language's source is tokenized. Everything not recognized is
+/// emitted as escaped plain text.
+struct Language {
+    names: &'static [&'static str],
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    literals: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+    /// Python-style `"""` strings.
+    triple_quotes: bool,
+    /// Rust: `name!` macros, `#[attr]`, and `'a'` versus `'a` lifetimes.
+    rust_syntax: bool,
+    /// Python decorators and TS/JS annotations starting with `@`.
+    decorators: bool,
+    /// Shell `$VAR`, `${VAR}` and `$1`.
+    dollar_variables: bool,
+    /// Character that ends a key at the start of a line (`:` in YAML, `=` in
+    /// TOML), or a quoted key in JSON.
+    key_separator: Option<char>,
+    /// TOML `[table]` headers.
+    table_headers: bool,
+    /// Identifiers starting with a capital letter are types.
+    capitalized_types: bool,
+}
+
+const DEFAULT: Language = Language {
+    names: &[],
+    keywords: &[],
+    types: &[],
+    literals: &[],
+    line_comments: &[],
+    block_comment: None,
+    quotes: &[],
+    triple_quotes: false,
+    rust_syntax: false,
+    decorators: false,
+    dollar_variables: false,
+    key_separator: None,
+    table_headers: false,
+    capitalized_types: false,
+};
+
+const JS_KEYWORDS: &[&str] = &[
+    "async", "await", "break", "case", "catch", "class", "const", "continue", "debugger", "default",
+    "delete", "do", "else", "export", "extends", "finally", "for", "from", "function", "if", "import",
+    "in", "instanceof", "let", "new", "of", "return", "static", "super", "switch", "this", "throw",
+    "try", "typeof", "var", "void", "while", "with", "yield",
+];
+
+const LANGUAGES: &[Language] = &[
+    Language {
+        names: &["rust", "rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+            "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type",
+            "unsafe", "use", "where", "while",
+        ],
+        types: &[
+            "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+            "i64", "i128", "isize", "f32", "f64",
+        ],
+        literals: &["true", "false", "None", "Some", "Ok", "Err"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+        rust_syntax: true,
+        capitalized_types: true,
+        ..DEFAULT
+    },
+    Language {
+        names: &["javascript", "js", "jsx", "mjs", "cjs"],
+        keywords: JS_KEYWORDS,
+        literals: &["true", "false", "null", "undefined", "NaN", "Infinity"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        capitalized_types: true,
+        ..DEFAULT
+    },
+    Language {
+        names: &["typescript", "ts", "tsx"],
+        keywords: &[
+            "abstract", "as", "async", "await", "break", "case", "catch", "class", "const",
+            "continue", "declare", "default", "delete", "do", "else", "enum", "export", "extends",
+            "finally", "for", "from", "function", "if", "implements", "import", "in", "instanceof",
+            "interface", "keyof", "let", "namespace", "new", "of", "private", "protected", "public",
+            "readonly", "return", "static", "super", "switch", "this", "throw", "try", "type",
+            "typeof", "var", "void", "while", "yield",
+        ],
+        types: &["any", "boolean", "never", "number", "object", "string", "symbol", "unknown", "bigint"],
+        literals: &["true", "false", "null", "undefined", "NaN", "Infinity"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+        decorators: true,
+        capitalized_types: true,
+        ..DEFAULT
+    },
+    Language {
+        names: &["python", "py"],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+            "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with",
+            "yield",
+        ],
+        types: &["int", "float", "str", "bool", "list", "dict", "set", "tuple", "bytes", "object"],
+        literals: &["True", "False", "None"],
+        line_comments: &["#"],
+        quotes: &['"', '\''],
+        triple_quotes: true,
+        decorators: true,
+        capitalized_types: true,
+        ..DEFAULT
+    },
+    Language {
+        names: &["yaml", "yml"],
+        literals: &["true", "false", "null", "yes", "no", "on", "off", "~"],
+        line_comments: &["#"],
+        quotes: &['"', '\''],
+        key_separator: Some(':'),
+        ..DEFAULT
+    },
+    Language {
+        names: &["json", "jsonc", "json5"],
+        literals: &["true", "false", "null"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"'],
+        key_separator: Some(':'),
+        ..DEFAULT
+    },
+    Language {
+        names: &["toml"],
+        literals: &["true", "false"],
+        line_comments: &["#"],
+        quotes: &['"', '\''],
+        triple_quotes: true,
+        key_separator: Some('='),
+        table_headers: true,
+        ..DEFAULT
+    },
+    Language {
+        names: &["shell", "sh", "bash", "zsh", "console", "shell-session"],
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if",
+            "in", "local", "return", "select", "then", "until", "while",
+        ],
+        types: &[
+            "cd", "echo", "eval", "exec", "exit", "printf", "read", "set", "shift", "source", "test",
+            "trap", "unset",
+        ],
+        literals: &["true", "false"],
+        line_comments: &["#"],
+        quotes: &['"', '\''],
+        dollar_variables: true,
+        ..DEFAULT
+    },
+];
+
+/// Highlights `code` written in the fenced-block language `lang`, returning
+/// escaped HTML with tokens wrapped in `<span class="hl-…">`. Returns `None`
+/// for languages without a definition so callers can fall back to plain text.
+pub fn highlight(code: &str, lang: &str) -> Option<String> {
+    let lang = lang.to_ascii_lowercase();
+    let language = LANGUAGES.iter().find(|l| l.names.contains(&lang.as_str()))?;
+    Some(Highlighter { lang: language, src: code, pos: 0, out: String::with_capacity(code.len() * 2) }.run())
+}
+
+/// The language named by a fence info string such as `rust,ignore` or
+/// `python title="x.py"`.
+pub fn fence_language(info: &str) -> &str {
+    info.split([',', ' ', '{']).next().unwrap_or("").trim()
+}
+
+/// Markdown pass replacing fenced code blocks in known languages with
+/// highlighted HTML. Other code blocks pass through untouched and render as
+/// plain text.
+pub fn highlight_code_blocks<'a, I: Iterator<Item = Event<'a>>>(events: I) -> Vec<Event<'a>> {
+    let mut out = Vec::new();
+    let mut pending: Option<(Vec<Event<'a>>, String, String)> = None;
+
+    for event in events {
+        match (event, pending.as_mut()) {
+            (Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))), None) => {
+                let lang = fence_language(&info).to_string();
+                let start = Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)));
+                pending = Some((vec![start], lang, String::new()));
+            }
+            (Event::End(Tag::CodeBlock(kind)), Some(_)) => {
+                let (mut raw, lang, code) = pending.take().unwrap_or_default();
+                match highlight(&code, &lang) {
+                    Some(html) => out.push(Event::Html(format!(
+                        "<pre><code class=\"language-{} hl\">{}</code></pre>\n",
+                        escape_html(&lang),
+                        html
+                    ).into())),
+                    None => {
+                        raw.push(Event::End(Tag::CodeBlock(kind)));
+                        out.append(&mut raw);
+                    }
+                }
+            }
+            (event, Some((raw, _, code))) => {
+                if let Event::Text(text) = &event {
+                    code.push_str(text);
+                }
+                raw.push(event);
+            }
+            (event, None) => out.push(event),
+        }
+    }
+    out
+}
+
+struct Highlighter<'a> {
+    lang: &'a Language,
+    src: &'a str,
+    pos: usize,
+    out: String,
+}
+
+impl Highlighter<'_> {
+    fn run(mut self) -> String {
+        let mut line_start = true;
+        while self.pos < self.src.len() {
+            let rest = &self.src[self.pos..];
+            let ch = rest.chars().next().unwrap_or('\0');
+
+            if ch == '\n' {
+                self.plain(1);
+                line_start = true;
+                continue;
+            }
+            if line_start && ch.is_whitespace() {
+                self.plain(ch.len_utf8());
+                continue;
+            }
+            // YAML list items can hold mappings, so a key may follow the marker.
+            if line_start && self.lang.key_separator == Some(':') && rest.starts_with("- ") {
+                self.plain(2);
+                continue;
+            }
+            let at_line_start = std::mem::replace(&mut line_start, false);
+
+            if let Some(len) = self.comment_len(rest) {
+                self.span("comment", len);
+            } else if at_line_start && self.lang.table_headers && ch == '[' {
+                let len = rest.find('\n').unwrap_or(rest.len());
+                self.span("type", len);
+            } else if at_line_start && let Some(len) = self.bare_key_len(rest) {
+                self.span("key", len);
+            } else if let Some(len) = self.string_len(rest) {
+                let class = if self.is_quoted_key(rest, len) { "key" } else { "string" };
+                self.span(class, len);
+            } else if self.lang.rust_syntax && (rest.starts_with("#[") || rest.starts_with("#![")) {
+                let len = rest.find(']').map(|p| p + 1).unwrap_or(rest.len());
+                self.span("attribute", len);
+            } else if self.lang.decorators && ch == '@' && rest[1..].starts_with(is_ident_start) {
+                let len = 1 + ident_len(&rest[1..]);
+                self.span("attribute", len);
+            } else if self.lang.dollar_variables && ch == '$' && let Some(len) = variable_len(rest) {
+                self.span("variable", len);
+            } else if ch.is_ascii_digit() && !self.follows_ident() {
+                let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '.')).unwrap_or(rest.len());
+                self.span("number", len);
+            } else if is_ident_start(ch) {
+                let len = ident_len(rest);
+                self.word(&rest[..len]);
+            } else {
+                self.plain(ch.len_utf8());
+            }
+        }
+        self.out
+    }
+
+    fn word(&mut self, word: &str) {
+        let after = &self.src[self.pos + word.len()..];
+        let class = if self.lang.keywords.contains(&word) {
+            Some("keyword")
+        } else if self.lang.literals.contains(&word) {
+            Some("literal")
+        } else if self.lang.types.contains(&word) {
+            Some("type")
+        } else if self.lang.rust_syntax && after.starts_with('!') && !after.starts_with("!=") {
+            self.span("macro", word.len() + 1);
+            return;
+        } else if self.lang.key_separator.is_none() && after.trim_start_matches(' ').starts_with('(') {
+            Some("function")
+        } else if self.lang.capitalized_types && word.starts_with(|c: char| c.is_ascii_uppercase()) {
+            Some("type")
+        } else {
+            None
+        };
+        match class {
+            Some(class) => self.span(class, word.len()),
+            None => self.plain(word.len()),
+        }
+    }
+
+    fn comment_len(&self, rest: &str) -> Option<usize> {
+        if let Some((open, close)) = self.lang.block_comment
+            && rest.starts_with(open) {
+            let end = rest[open.len()..].find(close).map(|p| p + open.len() + close.len()).unwrap_or(rest.len());
+            return Some(end);
+        }
+        for marker in self.lang.line_comments {
+            // `#` only starts a comment at a word boundary (`a#b` in shell, `#fff` inside YAML values are not comments).
+            if rest.starts_with(marker) && (*marker != "#" || self.at_word_boundary()) {
+                return Some(rest.find('\n').unwrap_or(rest.len()));
+            }
+        }
+        None
+    }
+
+    fn string_len(&self, rest: &str) -> Option<usize> {
+        let quote = rest.chars().next()?;
+        if !self.lang.quotes.contains(&quote) {
+            return None;
+        }
+        if self.lang.rust_syntax && quote == '\'' {
+            return rust_char_len(rest);
+        }
+        if self.lang.triple_quotes {
+            let triple: String = std::iter::repeat_n(quote, 3).collect();
+            if rest.starts_with(&triple) {
+                let end = rest[3..].find(&triple).map(|p| p + 6).unwrap_or(rest.len());
+                return Some(end);
+            }
+        }
+        // Single-quoted shell and YAML strings have no escapes.
+        let escapes = !(quote == '\'' && (self.lang.dollar_variables || self.lang.key_separator == Some(':')));
+        let mut chars = rest.char_indices().skip(1);
+        while let Some((idx, c)) = chars.next() {
+            if escapes && c == '\\' {
+                chars.next();
+            } else if c == quote {
+                return Some(idx + c.len_utf8());
+            } else if c == '\n' && quote != '`' && !self.lang.dollar_variables {
+                // Unterminated: stop at the end of the line.
+                return Some(idx);
+            }
+        }
+        Some(rest.len())
+    }
+
+    /// A JSON (or quoted YAML/TOML) key: a string followed by the separator.
+    fn is_quoted_key(&self, rest: &str, len: usize) -> bool {
+        match self.lang.key_separator {
+            Some(sep) => rest[len..].trim_start_matches([' ', '\t']).starts_with(sep),
+            None => false,
+        }
+    }
+
+    /// An unquoted key at the start of a YAML or TOML line.
+    fn bare_key_len(&self, rest: &str) -> Option<usize> {
+        let sep = self.lang.key_separator?;
+        let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+        let key_len = line.find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')))?;
+        let after = line[key_len..].trim_start_matches([' ', '\t']);
+        // `key: value` in YAML needs the space; `http://x` is not a key.
+        let is_key = key_len > 0
+            && after.starts_with(sep)
+            && (sep != ':' || after.len() == 1 || after[1..].starts_with([' ', '\t']));
+        is_key.then_some(key_len)
+    }
+
+    fn follows_ident(&self) -> bool {
+        self.src[..self.pos].chars().next_back().is_some_and(|c| c.is_alphanumeric() || c == '_')
+    }
+
+    fn at_word_boundary(&self) -> bool {
+        self.src[..self.pos].chars().next_back().is_none_or(char::is_whitespace)
+    }
+
+    fn span(&mut self, class: &str, len: usize) {
+        let text = &self.src[self.pos..self.pos + len];
+        self.out.push_str(&format!("<span class=\"hl-{}\">{}</span>", class, escape_html(text)));
+        self.pos += len;
+    }
+
+    fn plain(&mut self, len: usize) {
+        self.out.push_str(&escape_html(&self.src[self.pos..self.pos + len]));
+        self.pos += len;
+    }
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn ident_len(s: &str) -> usize {
+    s.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(s.len())
+}
+
+/// `'a'` and `'\n'` are character literals; `'a` on its own is a lifetime.
+fn rust_char_len(rest: &str) -> Option<usize> {
+    let mut chars = rest.char_indices().skip(1);
+    let (_, first) = chars.next()?;
+    if first == '\\' {
+        // Skip the escaped character so `'\''` is read as one literal.
+        return rest.get(3..)?.find('\'').map(|p| p + 4);
+    }
+    match chars.next() {
+        Some((idx, '\'')) => Some(idx + 1),
+        _ => None,
+    }
+}
+
+fn variable_len(rest: &str) -> Option<usize> {
+    let after = &rest[1..];
+    if after.starts_with('{') {
+        return after.find('}').map(|p| p + 2);
+    }
+    let first = after.chars().next()?;
+    if first.is_ascii_digit() || matches!(first, '?' | '@' | '#' | '*' | '$' | '!') {
+        return Some(2);
+    }
+    let len = ident_len(after);
+    (len > 0).then_some(len + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::{html, Options, Parser};
+
+    use super::*;
+    use crate::test_support::{get, site};
+
+    fn render(markdown: &str) -> String {
+        let mut out = String::new();
+        html::push_html(&mut out, highlight_code_blocks(Parser::new_ext(markdown, Options::all())).into_iter());
+        out
+    }
+
+    #[test]
+    fn highlighted_code_is_escaped() {
+        let html = render("```rust\nfn main() { let s = \"<script>alert(1)</script>\"; x < y && y > z; }\n```\n");
+        assert!(html.starts_with("<pre><code class=\"language-rust hl\">"), "{}", html);
+        assert!(html.contains("<span class=\"hl-keyword\">fn</span>"), "{}", html);
+        assert!(!html.contains("<script>"), "{}", html);
+        assert!(html.contains("&lt;script&gt;"), "{}", html);
+        assert!(html.contains("x &lt; y &amp;&amp; y &gt; z"), "{}", html);
+
+        let html = render("```RS title=\"a<b>.rs\"\nlet a = 1;\n```\n");
+        assert!(html.contains("class=\"language-RS hl\""), "{}", html);
+        assert!(highlight("<b>", "rust").unwrap().contains("&lt;b&gt;"));
+    }
+
+    #[test]
+    fn unknown_languages_stay_plain() {
+        assert_eq!(highlight("code", "brainfuck"), None);
+        for markdown in ["```brainfuck\n<b>+</b>\n```\n", "```\n<b>+</b>\n```\n", "    <b>+</b>\n"] {
+            let html = render(markdown);
+            assert!(html.starts_with("<pre><code"), "{}", html);
+            assert!(html.contains("&lt;b&gt;+&lt;/b&gt;"), "{}", html);
+            assert!(!html.contains("hl-"), "{}", html);
+        }
+    }
+
+    #[test]
+    fn theme_stylesheet_is_served() {
+        let site = site("highlight-css", &[], "");
+        let response = get(&site, "/highlight.css");
+        assert_eq!(response.status, 200);
+        assert!(response.content_type.starts_with("text/css"));
+        assert!(String::from_utf8_lossy(&response.body).contains(".hl-keyword"));
+        for (name, _) in THEMES {
+            let response = get(&site, &format!("/highlight.css?theme={}", name));
+            assert_eq!(response.status, 200, "{}", name);
+            assert_eq!(response.body, theme_css(name).unwrap().as_bytes());
+        }
+        assert_eq!(get(&site, "/highlight.css?theme=nope").status, 404);
+    }
+}
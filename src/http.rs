@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+
+/// Splits a request target such as `/api/blogs?page=2` into its path and
+/// (possibly empty) query string.
+pub fn split_target(target: &str) -> (&str, &str) {
+    let target = target.split('#').next().unwrap_or(target);
+    match target.find('?') {
+        Some(pos) => (&target[..pos], &target[pos + 1..]),
+        None => (target, ""),
+    }
+}
+
+/// Parses an `application/x-www-form-urlencoded` query string. Later
+/// occurrences of a key overwrite earlier ones; undecodable pairs are skipped.
+pub fn parse_query(query: &str) -> HashMap<String, String> {
+    let mut params = HashMap::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = match pair.find('=') {
+            Some(pos) => (&pair[..pos], &pair[pos + 1..]),
+            None => (pair, ""),
+        };
+        let key = percent_decode(&key.replace('+', " "));
+        let value = percent_decode(&value.replace('+', " "));
+        if let (Some(key), Some(value)) = (key, value) {
+            params.insert(key, value);
+        }
+    }
+    params
+}
+
+/// Decodes `%XX` escapes. Returns `None` for malformed escapes or when the
+/// decoded bytes are not valid UTF-8.
+pub fn percent_decode(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = input.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Encodes a value for use inside a query string.
+pub fn percent_encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for byte in input.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(byte as char),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+/// Builds a query string from ordered key/value pairs, skipping empty values.
+pub fn build_query(pairs: &[(&str, String)]) -> String {
+    pairs.iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// A complete response, produced by `Server::dispatch` and either written to
+/// a connection or saved to disk by the static exporter.
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    /// Sent in order after `Content-Type` and `Content-Length`.
+    pub headers: Vec<(String, String)>,
+    pub content_type: String,
+    pub body: Vec<u8>,
+    /// Set by `Response::error`: the message of an error whose page is
+    /// still to be rendered, as JSON or HTML depending on the request.
+    pub error: Option<String>,
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Response {
+        Response {
+            status,
+            headers: Vec::new(),
+            content_type: content_type.to_string(),
+            body: body.into(),
+            error: None,
+        }
+    }
+
+    pub fn json(status: u16, body: impl Into<Vec<u8>>) -> Response {
+        Response::new(status, "application/json", body)
+    }
+
+    /// An error `Server::dispatch` turns into a JSON body or an error page.
+    /// Until then the body is the plain message.
+    pub fn error(status: u16, message: impl Into<String>) -> Response {
+        let message = message.into();
+        let mut response = Response::new(status, "text/plain; charset=utf-8", message.clone());
+        response.error = Some(message);
+        response
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Status line, headers and body as sent over the wire.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n",
+            self.status,
+            reason_phrase(self.status),
+            self.content_type,
+            self.body.len()
+        );
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str("\r\n");
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
+
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        502 => "Bad Gateway",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
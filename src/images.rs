@@ -0,0 +1,316 @@
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::UNIX_EPOCH;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat, ImageReader, Limits};
+
+use crate::config::ImagesConfig;
+use crate::http;
+use crate::links::internal_route;
+use crate::static_files::ensure_within;
+
+/// Images larger than this in either dimension are not decoded, so a huge
+/// upload cannot exhaust memory on the first resize request.
+const MAX_SOURCE_DIMENSION: u32 = 12_000;
+const MAX_DECODE_BYTES: u64 = 256 * 1024 * 1024;
+const JPEG_QUALITY: u8 = 82;
+
+/// Extensions of the raster images that can be resized.
+const RASTER_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp"];
+
+/// Formats `?format=` may convert to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputFormat {
+    Webp,
+    Png,
+    Jpeg,
+}
+
+impl OutputFormat {
+    pub fn parse(name: &str) -> Option<OutputFormat> {
+        match name {
+            "webp" => Some(OutputFormat::Webp),
+            "png" => Some(OutputFormat::Png),
+            "jpeg" | "jpg" => Some(OutputFormat::Jpeg),
+            _ => None,
+        }
+    }
+
+    fn of_path(path: &Path) -> Option<OutputFormat> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "webp" => Some(OutputFormat::Webp),
+            "png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            // GIFs are re-encoded as PNG; animation is not kept.
+            "gif" => Some(OutputFormat::Png),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            OutputFormat::Webp => "image/webp",
+            OutputFormat::Png => "image/png",
+            OutputFormat::Jpeg => "image/jpeg",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Webp => "webp",
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+/// A resize or conversion asked for through the query string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Transform {
+    pub width: Option<u32>,
+    pub format: Option<OutputFormat>,
+}
+
+impl Transform {
+    /// Reads `w` and `format` from the query. `Ok(None)` when neither is
+    /// present, so the file is served as-is; an error for widths outside the
+    /// configured allowlist and unknown formats.
+    pub fn from_query(params: &HashMap<String, String>, cfg: &ImagesConfig) -> Result<Option<Transform>, String> {
+        let width = match params.get("w") {
+            None => None,
+            Some(raw) => {
+                let width: u32 = raw.parse().map_err(|_| format!("Invalid width: {}", raw))?;
+                if !cfg.widths.contains(&width) {
+                    let allowed: Vec<String> = cfg.widths.iter().map(u32::to_string).collect();
+                    return Err(format!("Width must be one of: {}", allowed.join(", ")));
+                }
+                Some(width)
+            }
+        };
+        let format = match params.get("format") {
+            None => None,
+            Some(raw) => Some(OutputFormat::parse(raw).ok_or_else(|| format!("Unsupported format: {}", raw))?),
+        };
+        Ok((width.is_some() || format.is_some()).then_some(Transform { width, format }))
+    }
+}
+
+pub fn is_raster(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| RASTER_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+/// Resizes and/or converts the image at `source`, returning the encoded
+/// bytes and their content type. Results are cached in `cfg.cache_dir`
+/// under a name derived from the source path and transform, followed by the
+/// source's size and modification time, so an edited image is never served
+/// stale; the entries it replaces are removed when it is stored. Images are
+/// never enlarged: a width above the original just converts.
+pub fn transform(source: &Path, transform: Transform, cfg: &ImagesConfig) -> Result<(Vec<u8>, &'static str), String> {
+    let format = transform.format
+        .or_else(|| OutputFormat::of_path(source))
+        .ok_or("Not a raster image")?;
+    let metadata = fs::metadata(source).map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    transform.hash(&mut hasher);
+    format.hash(&mut hasher);
+    let key = format!("{:016x}-", hasher.finish());
+    let mut hasher = DefaultHasher::new();
+    metadata.len().hash(&mut hasher);
+    metadata.modified().ok()
+        .and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .hash(&mut hasher);
+    let name = format!("{}{:016x}.{}", key, hasher.finish(), format.extension());
+    let cached = cfg.cache_dir.join(&name);
+
+    if let Ok(bytes) = fs::read(&cached) {
+        return Ok((bytes, format.content_type()));
+    }
+
+    let image = decode(source)?;
+    let image = match transform.width {
+        Some(width) if width < image.width() => image.resize(width, u32::MAX, FilterType::Lanczos3),
+        _ => image,
+    };
+    let bytes = encode(&image, format)?;
+
+    // Written under a temporary name first, so concurrent requests never read
+    // a half-written file.
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let temp = cfg.cache_dir.join(format!(
+        ".{}-{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let stored = fs::create_dir_all(&cfg.cache_dir)
+        .and_then(|_| fs::write(&temp, &bytes))
+        .and_then(|_| fs::rename(&temp, &cached));
+    match stored {
+        Ok(()) => prune_stale(&cfg.cache_dir, &key, &name),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            eprintln!("Failed to cache {}: {}", cached.display(), e);
+        }
+    }
+    Ok((bytes, format.content_type()))
+}
+
+/// Removes the entries cached for earlier versions of the same source and
+/// transform: every file named `key` plus another version than `current`.
+fn prune_stale(cache_dir: &Path, key: &str, current: &str) {
+    let Ok(entries) = fs::read_dir(cache_dir) else { return };
+    for entry in entries.flatten() {
+        let file_name = entry.file_name();
+        let Some(file_name) = file_name.to_str() else { continue };
+        if file_name.starts_with(key) && file_name != current
+            && let Err(e) = fs::remove_file(entry.path()) {
+            eprintln!("Failed to remove stale cache entry {}: {}", entry.path().display(), e);
+        }
+    }
+}
+
+fn decode(source: &Path) -> Result<DynamicImage, String> {
+    let mut reader = ImageReader::open(source)
+        .and_then(|r| r.with_guessed_format())
+        .map_err(|e| format!("Failed to open {}: {}", source.display(), e))?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_BYTES);
+    reader.limits(limits);
+    reader.decode().map_err(|e| format!("Failed to decode {}: {}", source.display(), e))
+}
+
+fn encode(image: &DynamicImage, format: OutputFormat) -> Result<Vec<u8>, String> {
+    let mut out = Cursor::new(Vec::new());
+    let result = match format {
+        // JPEG has no alpha channel.
+        OutputFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY)),
+        OutputFormat::Png => image.write_to(&mut out, ImageFormat::Png),
+        OutputFormat::Webp => DynamicImage::ImageRgba8(image.to_rgba8()).write_to(&mut out, ImageFormat::WebP),
+    };
+    result.map_err(|e| format!("Failed to encode image: {}", e))?;
+    Ok(out.into_inner())
+}
+
+/// What markdown rendering needs to describe local images: where they live
+/// and which widths the server resizes to.
+#[derive(Debug, Clone)]
+pub struct ImageContext {
+    pub root_dir: PathBuf,
+    pub page_prefix: String,
+    pub widths: Vec<u32>,
+}
+
+/// Intrinsic size of an image and the `srcset` candidates smaller than it,
+/// if its URL can be listed in one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    pub srcset: Option<String>,
+}
+
+impl ImageContext {
+    /// Describes the image `src` in the post `slug`, if it is a raster image
+    /// under the static root. Only the header is read.
+    pub fn describe(&self, slug: &str, src: &str) -> Option<ImageInfo> {
+        if src.contains(['?', '#']) {
+            return None;
+        }
+        let page = format!("{}/{}", self.page_prefix, slug);
+        let route = internal_route(&page, src, "")?;
+        let route = http::percent_decode(&route)?;
+        let path = ensure_within(&self.root_dir, &self.root_dir.join(route.trim_start_matches('/'))).ok()?;
+        if !is_raster(&path) {
+            return None;
+        }
+        let (width, height) = image::image_dimensions(&path).ok()?;
+
+        // Commas and whitespace separate srcset candidates, so such a URL
+        // cannot be listed; the image keeps its size attributes.
+        if src.contains(|c: char| c == ',' || c.is_whitespace()) {
+            return Some(ImageInfo { width, height, srcset: None });
+        }
+        let mut candidates: Vec<String> = self.widths.iter()
+            .filter(|&&w| w < width)
+            .map(|w| format!("{}?w={} {}w", src, w, w))
+            .collect();
+        let srcset = (!candidates.is_empty()).then(|| {
+            candidates.push(format!("{} {}w", src, width));
+            candidates.join(", ")
+        });
+        Some(ImageInfo { width, height, srcset })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::*;
+    use crate::test_support::site;
+
+    #[test]
+    fn srcset_skipped_for_unlistable_urls() {
+        let site = site("images-srcset", &[], "");
+        let root = site.dir.join("public");
+        for name in ["plain.png", "with space.png", "a,b.png"] {
+            image::RgbImage::new(400, 200).save(root.join(name)).unwrap();
+        }
+        let context = ImageContext { root_dir: root, page_prefix: "/blogs".to_string(), widths: vec![100, 800] };
+
+        let plain = context.describe("post", "/plain.png").unwrap();
+        assert_eq!((plain.width, plain.height), (400, 200));
+        assert_eq!(plain.srcset.as_deref(), Some("/plain.png?w=100 100w, /plain.png 400w"));
+        let encoded = context.describe("post", "/with%20space.png").unwrap();
+        assert!(encoded.srcset.is_some());
+        for src in ["/with space.png", "/a,b.png"] {
+            let info = context.describe("post", src).unwrap();
+            assert_eq!((info.width, info.height, info.srcset), (400, 200, None), "{}", src);
+        }
+    }
+
+    #[test]
+    fn stale_cache_entries_are_pruned() {
+        let site = site("images-cache", &[], "");
+        let source = site.dir.join("public/photo.png");
+        let cfg = ImagesConfig { enabled: true, widths: vec![10], cache_dir: site.dir.join("cache") };
+        let entries = || {
+            let mut names: Vec<String> = fs::read_dir(&cfg.cache_dir).unwrap()
+                .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+                .collect();
+            names.sort();
+            names
+        };
+        let small = Transform { width: Some(10), format: None };
+        let webp = Transform { width: None, format: Some(OutputFormat::Webp) };
+
+        image::RgbImage::new(40, 20).save(&source).unwrap();
+        transform(&source, small, &cfg).unwrap();
+        transform(&source, webp, &cfg).unwrap();
+        let before = entries();
+        assert_eq!(before.len(), 2);
+
+        image::RgbImage::new(60, 30).save(&source).unwrap();
+        fs::File::options().write(true).open(&source).unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+        transform(&source, small, &cfg).unwrap();
+        let after = entries();
+        assert_eq!(after.len(), 2, "{:?}", after);
+        assert!(after.iter().any(|name| !before.contains(name)));
+        assert!(after.iter().any(|name| name.ends_with(".webp") && before.contains(name)));
+    }
+}
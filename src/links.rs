@@ -0,0 +1,164 @@
+use std::ops::Range;
+
+/// The route a link in the page at `page` points to, if it stays on this
+/// site. Query strings and fragments are dropped; links assembled by scripts
+/// or templates (`${...}`, `{{ ... }}`) are ignored.
+pub fn internal_route(page: &str, link: &str, base_url: &str) -> Option<String> {
+    let link = link.trim();
+    if link.is_empty() || link.starts_with('#') || link.contains("${") || link.contains("{{") {
+        return None;
+    }
+    let base = base_url.trim_end_matches('/');
+    let link = match link.strip_prefix(base) {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '?', '#']) => rest,
+        _ => link,
+    };
+    if link.starts_with("//") {
+        return None;
+    }
+    // Another host, or a scheme such as mailto:, tel: or javascript:.
+    if let Some(colon) = link.find(':')
+        && colon < link.find(['/', '?', '#']).unwrap_or(link.len()) {
+        return None;
+    }
+
+    let path = link.split(['?', '#']).next().unwrap_or("");
+    let joined = if path.starts_with('/') {
+        path.to_string()
+    } else if path.is_empty() {
+        page.to_string()
+    } else {
+        let dir = &page[..page.rfind('/').map(|i| i + 1).unwrap_or(0)];
+        format!("{}{}", dir, path)
+    };
+
+    // Resolve `.` and `..` segments the way a browser would.
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in joined.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    let mut route = format!("/{}", segments.join("/"));
+    if joined.ends_with('/') && route != "/" {
+        route.push('/');
+    }
+    Some(route)
+}
+
+/// `href` and `src` attribute values in `html` with the byte offset where
+/// each value starts, entity-decoded. Script and style bodies and comments
+/// are skipped.
+pub fn extract_links(html: &str) -> Vec<(usize, String)> {
+    let lower = html.to_ascii_lowercase();
+    let mut links = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = lower[pos..].find('<') {
+        let start = pos + offset + 1;
+        if lower[start..].starts_with("!--") {
+            match lower[start..].find("-->") {
+                Some(end) => {
+                    pos = start + end + 3;
+                    continue;
+                }
+                None => break,
+            }
+        }
+        let Some(len) = tag_end(&html[start..]) else { break };
+        let tag = &html[start..start + len];
+        pos = start + len + 1;
+
+        let name: String = tag.chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        for (attr, range) in attributes(tag) {
+            if attr == "href" || attr == "src" {
+                let value = &tag[range.clone()];
+                let value = value.replace("&amp;", "&").replace("&#39;", "'").replace("&quot;", "\"");
+                links.push((start + range.start, value));
+            }
+        }
+        if name == "script" || name == "style" {
+            match lower[pos..].find(&format!("</{}", name)) {
+                Some(end) => pos += end,
+                None => break,
+            }
+        }
+    }
+    links
+}
+
+/// Length of a tag's contents up to its closing `>`, ignoring any `>` inside
+/// quoted attribute values.
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, ch) in tag.char_indices() {
+        match (quote, ch) {
+            (None, '"' | '\'') => quote = Some(ch),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Attribute names (lowercased) of a tag's contents with the byte range of
+/// each value, quotes excluded.
+fn attributes(tag: &str) -> Vec<(String, Range<usize>)> {
+    let mut attrs = Vec::new();
+    let bytes = tag.as_bytes();
+    // Skip the tag name.
+    let mut i = tag.find(|c: char| c.is_whitespace()).unwrap_or(tag.len());
+    while i < bytes.len() {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            i += 1;
+        }
+        let name_start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'=' && bytes[i] != b'/' {
+            i += 1;
+        }
+        let name = tag[name_start..i].to_ascii_lowercase();
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        if i >= bytes.len() || bytes[i] != b'=' {
+            if name.is_empty() {
+                i += 1;
+            }
+            continue;
+        }
+        i += 1;
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        let value = match bytes.get(i) {
+            Some(&q) if q == b'"' || q == b'\'' => {
+                let value_start = i + 1;
+                let len = tag[value_start..].find(q as char).unwrap_or(tag.len() - value_start);
+                i = value_start + len + 1;
+                value_start..value_start + len
+            }
+            _ => {
+                let value_start = i;
+                while i < bytes.len() && !bytes[i].is_ascii_whitespace() {
+                    i += 1;
+                }
+                value_start..i
+            }
+        };
+        attrs.push((name, value));
+    }
+    attrs
+}
+
+/// 1-based line number of the byte `offset` in `text`.
+pub fn line_at(text: &str, offset: usize) -> usize {
+    let end = offset.min(text.len());
+    text.as_bytes()[..end].iter().filter(|&&b| b == b'\n').count() + 1
+}
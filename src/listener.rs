@@ -0,0 +1,316 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, SocketAddrV6, TcpListener, TcpStream, ToSocketAddrs};
+use std::env;
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// A single address the server should accept connections on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Endpoint {
+    Tcp(String),
+    Unix(PathBuf),
+}
+
+impl Endpoint {
+    /// Parses `host:port`, `[v6addr]:port` or `unix:/path/to.sock`.
+    pub fn parse(spec: &str) -> Endpoint {
+        let spec = spec.trim();
+        match spec.strip_prefix("unix:") {
+            Some(path) => Endpoint::Unix(PathBuf::from(path)),
+            None => Endpoint::Tcp(spec.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Endpoint::Tcp(addr) => write!(f, "{}", addr),
+            Endpoint::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    pub fn bind(endpoint: &Endpoint, socket_mode: Option<u32>) -> io::Result<Listener> {
+        match endpoint {
+            Endpoint::Tcp(addr) => bind_tcp(addr).map(Listener::Tcp),
+            Endpoint::Unix(path) => {
+                remove_stale_socket(path)?;
+                let listener = UnixListener::bind(path)?;
+                if let Some(mode) = socket_mode {
+                    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+                }
+                Ok(Listener::Unix(listener, path.clone()))
+            }
+        }
+    }
+
+    /// Connections are always blocking, even when the listener is not (some
+    /// platforms pass the flag on to accepted sockets).
+    pub fn accept(&self) -> io::Result<Connection> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                Ok(Connection::Tcp(stream))
+            }
+            Listener::Unix(listener, _) => {
+                let (stream, _) = listener.accept()?;
+                stream.set_nonblocking(false)?;
+                Ok(Connection::Unix(stream))
+            }
+        }
+    }
+
+    /// Non-blocking listeners let an accept loop give up when another
+    /// process sharing the socket took the connection first.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        match self {
+            Listener::Tcp(listener) => listener.set_nonblocking(nonblocking),
+            Listener::Unix(listener, _) => listener.set_nonblocking(nonblocking),
+        }
+    }
+
+    /// Waits up to `timeout` for a pending connection. `Ok(false)` on
+    /// timeout or when interrupted by a signal.
+    pub fn wait_readable(&self, timeout: Duration) -> io::Result<bool> {
+        let mut poll_fd = libc::pollfd { fd: self.raw_fd(), events: libc::POLLIN, revents: 0 };
+        let millis = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+        let rc = unsafe { libc::poll(&mut poll_fd, 1, millis) };
+        if rc < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                return Ok(false);
+            }
+            return Err(err);
+        }
+        Ok(rc > 0)
+    }
+
+    /// Wraps an already-listening socket handed over by a supervisor or a
+    /// previous server process, detecting its address family.
+    pub fn from_inherited_fd(fd: RawFd) -> io::Result<Listener> {
+        let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+        let mut len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+        let rc = unsafe {
+            libc::getsockname(fd, &mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr, &mut len)
+        };
+        if rc < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Inherited descriptors arrive without close-on-exec; restore it so they
+        // only reach a successor process when handed over explicitly.
+        if unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        match storage.ss_family as libc::c_int {
+            libc::AF_INET | libc::AF_INET6 => Ok(Listener::Tcp(unsafe { TcpListener::from_raw_fd(fd) })),
+            libc::AF_UNIX => {
+                let listener = unsafe { UnixListener::from_raw_fd(fd) };
+                let path = listener
+                    .local_addr()?
+                    .as_pathname()
+                    .map(|p| p.to_path_buf())
+                    .unwrap_or_default();
+                Ok(Listener::Unix(listener, path))
+            }
+            family => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("fd {} has unsupported address family {}", fd, family),
+            )),
+        }
+    }
+
+    pub fn raw_fd(&self) -> RawFd {
+        match self {
+            Listener::Tcp(listener) => listener.as_raw_fd(),
+            Listener::Unix(listener, _) => listener.as_raw_fd(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Listener::Tcp(listener) => match listener.local_addr() {
+                Ok(addr) => addr.to_string(),
+                Err(_) => "tcp:<unknown>".to_string(),
+            },
+            Listener::Unix(_, path) => format!("unix:{}", path.display()),
+        }
+    }
+}
+
+/// An accepted client connection, independent of the socket family it arrived on.
+#[derive(Debug)]
+pub enum Connection {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Tcp(s) => s.read(buf),
+            Connection::Unix(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Tcp(s) => s.write(buf),
+            Connection::Unix(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Tcp(s) => s.flush(),
+            Connection::Unix(s) => s.flush(),
+        }
+    }
+}
+
+fn bind_tcp(addr: &str) -> io::Result<TcpListener> {
+    let mut last_err = io::Error::new(io::ErrorKind::InvalidInput, format!("no addresses resolved for {}", addr));
+    for sock_addr in addr.to_socket_addrs()? {
+        let result = match sock_addr {
+            SocketAddr::V4(_) => TcpListener::bind(sock_addr),
+            SocketAddr::V6(v6) => bind_v6_only(v6),
+        };
+        match result {
+            Ok(listener) => return Ok(listener),
+            Err(err) => last_err = err,
+        }
+    }
+    Err(last_err)
+}
+
+// IPv6 sockets are dual-stack by default on Linux, which would collide with an
+// explicit IPv4 endpoint on the same port, so they are bound with IPV6_V6ONLY.
+fn bind_v6_only(addr: SocketAddrV6) -> io::Result<TcpListener> {
+    let fd = unsafe { libc::socket(libc::AF_INET6, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // Take ownership right away so the descriptor is closed on every error path.
+    let listener = unsafe { TcpListener::from_raw_fd(fd) };
+
+    set_int_option(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, 1)?;
+    set_int_option(fd, libc::IPPROTO_IPV6, libc::IPV6_V6ONLY, 1)?;
+
+    let sin6 = libc::sockaddr_in6 {
+        sin6_family: libc::AF_INET6 as libc::sa_family_t,
+        sin6_port: addr.port().to_be(),
+        sin6_flowinfo: addr.flowinfo(),
+        sin6_addr: libc::in6_addr { s6_addr: addr.ip().octets() },
+        sin6_scope_id: addr.scope_id(),
+    };
+    let rc = unsafe {
+        libc::bind(
+            listener.as_raw_fd(),
+            &sin6 as *const libc::sockaddr_in6 as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+        )
+    };
+    if rc < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if unsafe { libc::listen(listener.as_raw_fd(), 128) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(listener)
+}
+
+fn set_int_option(fd: libc::c_int, level: libc::c_int, name: libc::c_int, value: libc::c_int) -> io::Result<()> {
+    let rc = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if rc < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Removes a socket file left behind by a previous run. A socket that still
+/// accepts connections belongs to a live process and is left alone.
+fn remove_stale_socket(path: &PathBuf) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => {
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is in use by another process", path.display()),
+                ));
+            }
+            println!("Removing stale socket: {}", path.display());
+            fs::remove_file(path)
+        }
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a socket", path.display()),
+        )),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// First descriptor used for passed sockets, as defined by sd_listen_fds(3).
+pub const LISTEN_FDS_START: RawFd = 3;
+
+/// Environment marker set by a running server when it execs its successor
+/// during an upgrade. Its value is the pid of the old process.
+pub const UPGRADE_FROM_ENV: &str = "PORTFOLIO_UPGRADE_FROM";
+
+/// Collects listening sockets passed in via `LISTEN_FDS`/`LISTEN_PID`, either by
+/// systemd socket activation or by a previous server process handing over its
+/// listeners. Returns an empty list when nothing was passed to this process.
+pub fn inherited_listeners() -> io::Result<Vec<Listener>> {
+    let count = match env::var("LISTEN_FDS") {
+        Ok(raw) => raw.trim().parse::<RawFd>().map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("invalid LISTEN_FDS: {}", raw))
+        })?,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    // LISTEN_PID guards against the variables leaking into unrelated children.
+    // A successor spawned during an upgrade cannot know its pid up front, so it
+    // is recognised by the upgrade marker instead.
+    let for_us = match env::var("LISTEN_PID") {
+        Ok(pid) => pid.trim() == std::process::id().to_string(),
+        Err(_) => env::var(UPGRADE_FROM_ENV).is_ok(),
+    };
+
+    // The variables must not be seen by anything this process spawns later.
+    unsafe {
+        env::remove_var("LISTEN_FDS");
+        env::remove_var("LISTEN_PID");
+        env::remove_var("LISTEN_FDNAMES");
+    }
+
+    if !for_us {
+        return Ok(Vec::new());
+    }
+
+    (LISTEN_FDS_START..LISTEN_FDS_START + count)
+        .map(Listener::from_inherited_fd)
+        .collect()
+}
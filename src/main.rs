@@ -1,15 +1,71 @@
 extern crate dotenv;
 use dotenv::dotenv;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use crate::blog_store::BlogStore;
+use crate::images::ImageContext;
+use crate::related::TermCache;
+use crate::search::Searcher;
 use crate::server::Server;
+use crate::template::Templates;
 pub mod server;
 pub mod config;
 pub mod static_files;
+pub mod listener;
+pub mod upgrade;
+pub mod blog;
+pub mod blog_store;
+pub mod date;
+pub mod http;
+pub mod search;
+pub mod escape;
+pub mod feed;
+pub mod sitemap;
+pub mod template;
+pub mod sanitize;
+pub mod highlight;
+pub mod toc;
+pub mod tags;
+pub mod related;
+pub mod export;
+pub mod links;
+pub mod check;
+pub mod math;
+pub mod admonition;
+pub mod footnotes;
+pub mod media;
+pub mod images;
+pub mod meta;
+pub mod error_pages;
+#[cfg(test)]
+pub mod test_support;
+
+/// What to do once the config is loaded.
+enum Command {
+    Serve,
+    /// Write a static copy of the site into the directory.
+    Export(String),
+    /// Report broken internal links and missing assets.
+    Check,
+}
 
 fn main() {
     dotenv().ok();
     let config_path = env::var("CONFIG_PATH").unwrap_or_else(|_| "config.yaml".to_string());
-    let config = match config::load_config(&config_path) {
+    let args: Vec<String> = env::args().collect();
+    let command = match args.get(1).map(String::as_str) {
+        None | Some("serve") => Command::Serve,
+        Some("export") => Command::Export(args.get(2).cloned().unwrap_or_else(|| "dist".to_string())),
+        Some("check") => Command::Check,
+        Some(other) => {
+            eprintln!("Unknown command: {}", other);
+            eprintln!("Usage: portfolio_website [serve | export [output_dir] | check]");
+            std::process::exit(2);
+        }
+    };
+
+    let mut config = match config::load_config(&config_path) {
         Ok(c) => c,
         Err(err) => {
             eprintln!("Failed to load config: {:?}", err);
@@ -17,14 +73,97 @@ fn main() {
         }
     };
 
+    if !matches!(command, Command::Serve) {
+        // Exports are public and checks must see the site as readers do:
+        // never include drafts or scheduled posts.
+        config.blog.preview = false;
+        config.blog.preview_token = None;
+    }
+
+    let templates = Templates::new(&config.templates.dir, config.templates.reload);
+    let mut blog_store = BlogStore::new(&config.blog.content_dir);
+    if config.images.enabled {
+        blog_store = blog_store.with_images(ImageContext {
+            root_dir: PathBuf::from(&config.static_cfg.root_dir),
+            page_prefix: config.blog.page_prefix.clone(),
+            widths: config.images.widths.clone(),
+        });
+    }
+
     let mut server:Server = Server {
         port: config.server.port.to_string(),
         host: config.server.host.clone(),
-        address: None,
-        listener: None,
+        endpoints: Vec::new(),
+        listeners: Vec::new(),
         config: Some(config),
         resolver: None,
+        draining: AtomicBool::new(false),
+        blog_store,
+        searcher: Searcher::default(),
+        term_cache: TermCache::default(),
+        templates,
     };
 
-   server.setup_server();
+    match command {
+        Command::Serve => server.setup_server(),
+        Command::Export(dir) => {
+            server.init_resolver();
+            run_export(&server, &dir);
+        }
+        Command::Check => {
+            server.init_resolver();
+            run_check(&server);
+        }
+    }
+}
+
+fn run_export(server: &Server, dir: &str) {
+    match export::export(server, Path::new(dir)) {
+        Ok(report) => {
+            println!("Exported {} files to {}", report.files, dir);
+            for link in &report.broken {
+                eprintln!("Broken link on {}: {} ({})", link.page, link.link, link.status);
+            }
+            if !report.broken.is_empty() {
+                eprintln!("{} broken internal link(s)", report.broken.len());
+                std::process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("Export failed: {}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Prints problems as `file:line: link (status)` and exits non-zero if there
+/// are any, so CI can fail the build.
+fn run_check(server: &Server) {
+    match check::check(server) {
+        Ok(report) => {
+            for problem in &report.problems {
+                println!(
+                    "{}:{}: broken link {} -> {} ({})",
+                    problem.file.display(),
+                    problem.line,
+                    problem.link,
+                    problem.route,
+                    problem.status
+                );
+            }
+            println!(
+                "Checked {} links in {} files: {} broken",
+                report.links,
+                report.files,
+                report.problems.len()
+            );
+            if !report.problems.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("Check failed: {}", err);
+            std::process::exit(1);
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,988 @@
+use std::ops::Range;
+
+use pulldown_cmark::{CodeBlockKind, Event, Tag};
+
+use crate::escape::escape_html;
+
+/// Groups, environments and command arguments nested deeper than this are
+/// rejected rather than risking the stack on hostile input.
+const MAX_DEPTH: usize = 64;
+
+const GREEK: &[(&str, &str)] = &[
+    ("alpha", "α"), ("beta", "β"), ("gamma", "γ"), ("delta", "δ"), ("epsilon", "ϵ"),
+    ("varepsilon", "ε"), ("zeta", "ζ"), ("eta", "η"), ("theta", "θ"), ("vartheta", "ϑ"),
+    ("iota", "ι"), ("kappa", "κ"), ("lambda", "λ"), ("mu", "μ"), ("nu", "ν"), ("xi", "ξ"),
+    ("pi", "π"), ("varpi", "ϖ"), ("rho", "ρ"), ("varrho", "ϱ"), ("sigma", "σ"), ("varsigma", "ς"),
+    ("tau", "τ"), ("upsilon", "υ"), ("phi", "ϕ"), ("varphi", "φ"), ("chi", "χ"), ("psi", "ψ"),
+    ("omega", "ω"),
+];
+
+/// Capital Greek letters are upright in TeX.
+const GREEK_UPRIGHT: &[(&str, &str)] = &[
+    ("Gamma", "Γ"), ("Delta", "Δ"), ("Theta", "Θ"), ("Lambda", "Λ"), ("Xi", "Ξ"), ("Pi", "Π"),
+    ("Sigma", "Σ"), ("Upsilon", "Υ"), ("Phi", "Φ"), ("Psi", "Ψ"), ("Omega", "Ω"),
+];
+
+const IDENTIFIERS: &[(&str, &str)] = &[
+    ("infty", "∞"), ("partial", "∂"), ("nabla", "∇"), ("emptyset", "∅"), ("varnothing", "∅"),
+    ("hbar", "ℏ"), ("ell", "ℓ"), ("Re", "ℜ"), ("Im", "ℑ"), ("aleph", "ℵ"),
+];
+
+const OPERATORS: &[(&str, &str)] = &[
+    ("pm", "±"), ("mp", "∓"), ("times", "×"), ("div", "÷"), ("cdot", "⋅"), ("ast", "∗"),
+    ("star", "⋆"), ("circ", "∘"), ("bullet", "∙"), ("leq", "≤"), ("le", "≤"), ("geq", "≥"),
+    ("ge", "≥"), ("neq", "≠"), ("ne", "≠"), ("approx", "≈"), ("equiv", "≡"), ("sim", "∼"),
+    ("simeq", "≃"), ("cong", "≅"), ("propto", "∝"), ("ll", "≪"), ("gg", "≫"), ("in", "∈"),
+    ("notin", "∉"), ("ni", "∋"), ("subset", "⊂"), ("subseteq", "⊆"), ("supset", "⊃"),
+    ("supseteq", "⊇"), ("cup", "∪"), ("cap", "∩"), ("setminus", "∖"), ("wedge", "∧"),
+    ("land", "∧"), ("vee", "∨"), ("lor", "∨"), ("neg", "¬"), ("lnot", "¬"), ("forall", "∀"),
+    ("exists", "∃"), ("to", "→"), ("rightarrow", "→"), ("leftarrow", "←"), ("gets", "←"),
+    ("leftrightarrow", "↔"), ("Rightarrow", "⇒"), ("Leftarrow", "⇐"), ("Leftrightarrow", "⇔"),
+    ("implies", "⟹"), ("iff", "⟺"), ("mapsto", "↦"), ("uparrow", "↑"), ("downarrow", "↓"),
+    ("ldots", "…"), ("dots", "…"), ("cdots", "⋯"), ("vdots", "⋮"), ("ddots", "⋱"),
+    ("prime", "′"), ("mid", "∣"), ("parallel", "∥"), ("perp", "⊥"), ("angle", "∠"),
+    ("triangle", "△"), ("oplus", "⊕"), ("otimes", "⊗"), ("langle", "⟨"), ("rangle", "⟩"),
+    ("lfloor", "⌊"), ("rfloor", "⌋"), ("lceil", "⌈"), ("rceil", "⌉"), ("vert", "|"),
+    ("lvert", "|"), ("rvert", "|"), ("Vert", "‖"), ("lVert", "‖"), ("rVert", "‖"),
+    ("colon", ":"), ("backslash", "\\"),
+];
+
+/// Operators whose scripts go above and below in display math.
+const BIG_OPERATORS: &[(&str, &str)] = &[
+    ("sum", "∑"), ("prod", "∏"), ("coprod", "∐"), ("bigcup", "⋃"), ("bigcap", "⋂"),
+    ("bigoplus", "⨁"), ("bigotimes", "⨂"),
+];
+
+/// Integrals keep their limits to the side even in display math.
+const INTEGRALS: &[(&str, &str)] = &[("int", "∫"), ("iint", "∬"), ("iiint", "∭"), ("oint", "∮")];
+
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh",
+    "tanh", "log", "ln", "lg", "exp", "det", "dim", "ker", "deg", "arg", "gcd", "hom",
+];
+
+/// Functions whose subscript goes below in display math (`\lim_{x \to 0}`).
+const LIMIT_FUNCTIONS: &[&str] = &["lim", "liminf", "limsup", "max", "min", "sup", "inf", "Pr"];
+
+const SPACES: &[(&str, &str)] = &[
+    (",", "0.1667em"), (":", "0.2222em"), (">", "0.2222em"), (";", "0.2778em"),
+    ("!", "-0.1667em"), (" ", "0.25em"), ("quad", "1em"), ("qquad", "2em"),
+];
+
+/// Accents placed over (or, for `underline`/`underbrace`, under) their argument.
+const ACCENTS: &[(&str, &str, bool)] = &[
+    ("hat", "^", false), ("widehat", "^", true), ("bar", "¯", false), ("overline", "¯", true),
+    ("vec", "→", false), ("dot", "˙", false), ("ddot", "¨", false), ("tilde", "~", false),
+    ("widetilde", "~", true), ("overbrace", "⏞", true),
+];
+
+const UNDER_ACCENTS: &[(&str, &str)] = &[("underline", "_"), ("underbrace", "⏟")];
+
+/// Matrix-like environments: opening and closing delimiters.
+const MATRICES: &[(&str, &str, &str)] = &[
+    ("matrix", "", ""), ("pmatrix", "(", ")"), ("bmatrix", "[", "]"), ("Bmatrix", "{", "}"),
+    ("vmatrix", "|", "|"), ("Vmatrix", "‖", "‖"),
+];
+
+/// Markdown pass rendering TeX math to MathML:
+///
+/// * `$...$` inline, when the opening `$` is followed and the closing `$`
+///   preceded by a non-space character and the closing one is not followed
+///   by a digit (so "$5 and $10" stays text),
+/// * `` $`...`$ `` inline, for TeX that markdown would otherwise mangle,
+/// * a paragraph holding only `$$...$$`, and fenced ```` ```math ```` blocks,
+///   as display math.
+///
+/// TeX is taken from `source` through the event offsets, so markdown escapes
+/// such as `\{` or `\\` reach the converter untouched. `\$` is a literal
+/// dollar sign. Math that fails to convert is shown as code.
+pub fn render_math<'a, I>(source: &'a str, events: I) -> Vec<Event<'a>>
+where
+    I: Iterator<Item = (Event<'a>, Range<usize>)>,
+{
+    let events: Vec<(Event<'a>, Range<usize>)> = events.collect();
+    let mut out = Vec::with_capacity(events.len());
+    let mut i = 0;
+    while i < events.len() {
+        match &events[i].0 {
+            Event::Start(Tag::Paragraph) => {
+                if let Some((tex, end)) = display_paragraph(source, &events, i) {
+                    out.push(Event::Html(math_html(&tex, true).into()));
+                    i = end + 1;
+                    continue;
+                }
+                out.push(events[i].0.clone());
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+                if info.split_whitespace().next() == Some("math") => {
+                let mut tex = String::new();
+                i += 1;
+                while i < events.len() && !matches!(events[i].0, Event::End(Tag::CodeBlock(_))) {
+                    if let Event::Text(text) = &events[i].0 {
+                        tex.push_str(text);
+                    }
+                    i += 1;
+                }
+                out.push(Event::Html(math_html(&tex, true).into()));
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                // No inline math inside code.
+                while i < events.len() {
+                    out.push(events[i].0.clone());
+                    if matches!(events[i].0, Event::End(Tag::CodeBlock(_))) {
+                        break;
+                    }
+                    i += 1;
+                }
+            }
+            Event::Code(code) => {
+                // $`...`$: the previous text ends and the next one starts with '$'.
+                let before = matches!(out.last(), Some(Event::Text(text)) if text.ends_with('$'));
+                let after = matches!(events.get(i + 1), Some((Event::Text(text), _)) if text.starts_with('$'));
+                if before && after {
+                    if let Some(Event::Text(text)) = out.pop() {
+                        let kept = &text[..text.len() - 1];
+                        if !kept.is_empty() {
+                            out.push(Event::Text(kept.to_string().into()));
+                        }
+                    }
+                    out.push(Event::Html(math_html(code, false).into()));
+                    if let (Event::Text(next), range) = &events[i + 1] {
+                        let rest = next[1..].to_string();
+                        i += 2;
+                        if !rest.is_empty() {
+                            let start = range.start + 1;
+                            i = inline_math(source, &events, i, (Event::Text(rest.into()), start..range.end), &mut out);
+                        }
+                        continue;
+                    }
+                }
+                out.push(events[i].0.clone());
+            }
+            Event::Text(_) => {
+                let current = events[i].clone();
+                i = inline_math(source, &events, i + 1, current, &mut out);
+                continue;
+            }
+            _ => out.push(events[i].0.clone()),
+        }
+        i += 1;
+    }
+    out
+}
+
+/// Finds `$...$` spans starting in `current`, whose closing `$` may lie in a
+/// later text event (`next` is the index after `current`). Emits the result
+/// and returns the index of the first event not consumed.
+fn inline_math<'a>(
+    source: &'a str,
+    events: &[(Event<'a>, Range<usize>)],
+    mut next: usize,
+    mut current: (Event<'a>, Range<usize>),
+    out: &mut Vec<Event<'a>>,
+) -> usize {
+    loop {
+        let (Event::Text(text), range) = &current else {
+            out.push(current.0);
+            return next;
+        };
+        // Only text that is a verbatim slice of the source maps 1:1 onto it;
+        // entities and smart quotes never open math.
+        let verbatim = source.get(range.clone()) == Some(text.as_ref());
+        let open = verbatim.then(|| opening_dollar(source, range.start, text)).flatten();
+        let Some(open) = open else {
+            out.push(current.0);
+            return next;
+        };
+        let Some((close_event, close)) = closing_dollar(source, events, next, range.start + open + 1, range.end) else {
+            out.push(current.0);
+            return next;
+        };
+
+        if open > 0 {
+            out.push(Event::Text(text[..open].to_string().into()));
+        }
+        let tex = &source[range.start + open + 1..close];
+        out.push(Event::Html(math_html(tex, false).into()));
+
+        // Continue after the closing '$', which is either in this event or
+        // in a later one.
+        let (rest_event, rest_range) = match close_event {
+            None => (text.to_string(), range.clone()),
+            Some(idx) => {
+                next = idx + 1;
+                let Event::Text(t) = &events[idx].0 else { unreachable!() };
+                (t.to_string(), events[idx].1.clone())
+            }
+        };
+        let rest = &rest_event[close + 1 - rest_range.start..];
+        if rest.is_empty() {
+            return next;
+        }
+        current = (Event::Text(rest.to_string().into()), close + 1..rest_range.end);
+    }
+}
+
+/// Byte offset within `text` of a `$` that can open inline math.
+fn opening_dollar(source: &str, start: usize, text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    (0..bytes.len()).find(|&i| {
+        bytes[i] == b'$'
+            && !is_escaped(source, start + i)
+            && bytes.get(i + 1).is_some_and(|b| !b.is_ascii_whitespace() && *b != b'$')
+            && (i == 0 || bytes[i - 1] != b'$')
+    })
+}
+
+/// Source offset of the `$` closing math opened before `from`, together with
+/// the index of the event holding it (`None` if in the current event, which
+/// ends at `current_end`). Only text and inline formatting may lie between
+/// the two; anything else (code, links, HTML, the end of the block) means
+/// there is no math.
+fn closing_dollar(
+    source: &str,
+    events: &[(Event, Range<usize>)],
+    next: usize,
+    from: usize,
+    current_end: usize,
+) -> Option<(Option<usize>, usize)> {
+    if let Some(pos) = find_closing(source, from, from, current_end) {
+        return Some((None, pos));
+    }
+    let mut depth = 0i32;
+    for (idx, (event, range)) in events.iter().enumerate().skip(next) {
+        match event {
+            Event::Text(text) if source.get(range.clone()) == Some(text.as_ref()) => {
+                if depth == 0
+                    && let Some(pos) = find_closing(source, from, range.start, range.end) {
+                    return Some((Some(idx), pos));
+                }
+            }
+            Event::Text(_) | Event::SoftBreak => {}
+            Event::Start(Tag::Emphasis | Tag::Strong | Tag::Strikethrough) => depth += 1,
+            Event::End(Tag::Emphasis | Tag::Strong | Tag::Strikethrough) => {
+                depth -= 1;
+                if depth < 0 {
+                    return None;
+                }
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// A closing `$` in `from..to`, leaving non-empty math after `content_start`.
+fn find_closing(source: &str, content_start: usize, from: usize, to: usize) -> Option<usize> {
+    let bytes = source.as_bytes();
+    (from..to).find(|&i| {
+        bytes[i] == b'$'
+            && i > content_start
+            && !bytes[i - 1].is_ascii_whitespace()
+            && !is_escaped(source, i)
+            && !bytes.get(i + 1).is_some_and(|b| b.is_ascii_digit())
+    })
+}
+
+/// Whether the byte at `pos` is preceded by an odd number of backslashes.
+fn is_escaped(source: &str, pos: usize) -> bool {
+    source.as_bytes()[..pos].iter().rev().take_while(|&&b| b == b'\\').count() % 2 == 1
+}
+
+/// TeX of a paragraph made only of `$$...$$`, with the index of its end
+/// event. The raw source of each text run is used, so backslash escapes are
+/// kept, while blockquote markers between lines are not.
+fn display_paragraph(source: &str, events: &[(Event, Range<usize>)], start: usize) -> Option<(String, usize)> {
+    let mut raw = String::new();
+    let mut prev_end = None;
+    for (idx, (event, range)) in events.iter().enumerate().skip(start + 1) {
+        match event {
+            Event::End(Tag::Paragraph) => {
+                let tex = raw.trim().strip_prefix("$$")?.strip_suffix("$$")?;
+                return (!tex.trim().is_empty()).then(|| (tex.to_string(), idx));
+            }
+            Event::Text(_) => {
+                // A single backslash between two text events is an escape
+                // that pulldown-cmark consumed; put it back.
+                let from = match prev_end {
+                    Some(end) if range.start == end + 1 && source.as_bytes()[end] == b'\\' => end,
+                    _ => range.start,
+                };
+                raw.push_str(&source[from..range.end]);
+                prev_end = Some(range.end);
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                raw.push('\n');
+                prev_end = None;
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+fn math_html(tex: &str, display: bool) -> String {
+    match tex_to_mathml(tex, display) {
+        Ok(mathml) => mathml,
+        Err(_) if display => format!("<pre class=\"math-error\"><code>{}</code></pre>\n", escape_html(tex.trim())),
+        Err(_) => format!("<code class=\"math-error\">{}</code>", escape_html(tex)),
+    }
+}
+
+/// Converts a TeX math expression to a MathML `<math>` element. Covers the
+/// common subset used in posts: scripts, fractions, roots, Greek letters and
+/// symbols, `\left`/`\right`, accents, font commands, `\text` and
+/// matrix-like environments. The TeX source is kept as an annotation.
+pub fn tex_to_mathml(tex: &str, display: bool) -> Result<String, String> {
+    let mut parser = TexParser { chars: tex.chars().collect(), pos: 0, display, depth: 0 };
+    // A top-level `\\` breaks the expression into lines.
+    let mut lines = vec![row(parser.sequence()?)];
+    while let Some(token) = parser.next_token() {
+        if token != Token::Command("\\".to_string()) {
+            return Err(format!("Unexpected {:?}", token));
+        }
+        lines.push(row(parser.sequence()?));
+    }
+    let body = if lines.len() == 1 {
+        lines.remove(0)
+    } else {
+        let rows: String = lines.iter().map(|line| format!("<mtr><mtd>{}</mtd></mtr>", line)).collect();
+        format!("<mtable>{}</mtable>", rows)
+    };
+    Ok(format!(
+        "<math display=\"{}\"><semantics>{}<annotation encoding=\"application/x-tex\">{}</annotation></semantics></math>{}",
+        if display { "block" } else { "inline" },
+        body,
+        escape_html(tex.trim()),
+        if display { "\n" } else { "" }
+    ))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Command(String),
+    Open,
+    Close,
+    Sup,
+    Sub,
+    Align,
+    Char(char),
+}
+
+struct TexParser {
+    chars: Vec<char>,
+    pos: usize,
+    display: bool,
+    depth: usize,
+}
+
+impl TexParser {
+    fn skip_spaces(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek_token(&mut self) -> Option<Token> {
+        let saved = self.pos;
+        let token = self.next_token();
+        self.pos = saved;
+        token
+    }
+
+    fn next_token(&mut self) -> Option<Token> {
+        self.skip_spaces();
+        let c = *self.chars.get(self.pos)?;
+        self.pos += 1;
+        Some(match c {
+            '{' => Token::Open,
+            '}' => Token::Close,
+            '^' => Token::Sup,
+            '_' => Token::Sub,
+            '&' => Token::Align,
+            '\\' => {
+                let start = self.pos;
+                while self.chars.get(self.pos).is_some_and(|c| c.is_ascii_alphabetic()) {
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    // Control symbol such as `\,`, `\{` or `\\`.
+                    match self.chars.get(self.pos) {
+                        Some(&c) => {
+                            self.pos += 1;
+                            Token::Command(c.to_string())
+                        }
+                        None => Token::Char('\\'),
+                    }
+                } else {
+                    Token::Command(self.chars[start..self.pos].iter().collect())
+                }
+            }
+            c => Token::Char(c),
+        })
+    }
+
+    fn enter(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err("Math nested too deeply".to_string());
+        }
+        Ok(())
+    }
+
+    /// Parses atoms until the end of input or a token that ends a group,
+    /// which is left unconsumed.
+    fn sequence(&mut self) -> Result<Vec<String>, String> {
+        let mut items = Vec::new();
+        loop {
+            match self.peek_token() {
+                None | Some(Token::Close) | Some(Token::Align) => return Ok(items),
+                Some(Token::Command(name)) if matches!(name.as_str(), "right" | "end" | "\\") => {
+                    return Ok(items);
+                }
+                _ => items.push(self.scripted()?),
+            }
+        }
+    }
+
+    /// An atom with any sub- and superscripts attached.
+    fn scripted(&mut self) -> Result<String, String> {
+        let (base, limits) = match self.peek_token() {
+            Some(Token::Sup) | Some(Token::Sub) => ("<mrow></mrow>".to_string(), false),
+            _ => self.atom()?,
+        };
+
+        let mut sub = None;
+        let mut sup = None;
+        loop {
+            self.skip_spaces();
+            match self.chars.get(self.pos) {
+                Some('\'') => {
+                    let mut primes = String::new();
+                    while self.chars.get(self.pos) == Some(&'\'') {
+                        primes.push('′');
+                        self.pos += 1;
+                    }
+                    sup = Some(format!("<mo>{}</mo>", primes));
+                }
+                Some('^') if sup.is_none() => {
+                    self.pos += 1;
+                    sup = Some(self.argument()?);
+                }
+                Some('_') if sub.is_none() => {
+                    self.pos += 1;
+                    sub = Some(self.argument()?);
+                }
+                _ => break,
+            }
+        }
+
+        let limits = limits && self.display;
+        Ok(match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) if limits => format!("<munder>{}{}</munder>", base, sub),
+            (None, Some(sup)) if limits => format!("<mover>{}{}</mover>", base, sup),
+            (Some(sub), Some(sup)) if limits => format!("<munderover>{}{}{}</munderover>", base, sub, sup),
+            (Some(sub), None) => format!("<msub>{}{}</msub>", base, sub),
+            (None, Some(sup)) => format!("<msup>{}{}</msup>", base, sup),
+            (Some(sub), Some(sup)) => format!("<msubsup>{}{}{}</msubsup>", base, sub, sup),
+        })
+    }
+
+    /// A command argument or script: a braced group or a single token.
+    /// Unbraced arguments count towards `MAX_DEPTH` too, as `\hat\hat\hat…`
+    /// nests just as deeply as braces do.
+    fn argument(&mut self) -> Result<String, String> {
+        self.skip_spaces();
+        match self.chars.get(self.pos) {
+            None => Err("Missing argument".to_string()),
+            // The group itself counts towards the depth.
+            Some('{') => self.atom().map(|(a, _)| a),
+            // `x^12` is x squared followed by 2, as in TeX.
+            Some(c) if c.is_ascii_digit() => {
+                let c = *c;
+                self.pos += 1;
+                Ok(format!("<mn>{}</mn>", c))
+            }
+            Some(_) => {
+                self.enter()?;
+                let (atom, _) = self.atom()?;
+                self.depth -= 1;
+                Ok(atom)
+            }
+        }
+    }
+
+    /// Contents of a braced argument, unparsed.
+    fn raw_group(&mut self) -> Result<String, String> {
+        self.skip_spaces();
+        if self.chars.get(self.pos) != Some(&'{') {
+            return Err("Expected '{'".to_string());
+        }
+        self.pos += 1;
+        let start = self.pos;
+        let mut depth = 1;
+        while let Some(&c) = self.chars.get(self.pos) {
+            self.pos += 1;
+            match c {
+                '\\' => self.pos += 1,
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(self.chars[start..self.pos - 1].iter().collect());
+                    }
+                }
+                _ => {}
+            }
+        }
+        Err("Unclosed '{'".to_string())
+    }
+
+    /// One atom and whether its scripts become limits in display math.
+    fn atom(&mut self) -> Result<(String, bool), String> {
+        let token = self.next_token().ok_or("Unexpected end of math")?;
+        let atom = match token {
+            Token::Open => {
+                self.enter()?;
+                let items = self.sequence()?;
+                if self.next_token() != Some(Token::Close) {
+                    return Err("Unclosed '{'".to_string());
+                }
+                self.depth -= 1;
+                row(items)
+            }
+            Token::Close => return Err("Unexpected '}'".to_string()),
+            Token::Sup | Token::Sub | Token::Align => return Err("Unexpected script or '&'".to_string()),
+            Token::Char(c) if c.is_ascii_digit() || c == '.' && self.chars.get(self.pos).is_some_and(|d| d.is_ascii_digit()) => {
+                let mut number = c.to_string();
+                while let Some(&d) = self.chars.get(self.pos) {
+                    let decimal_point = d == '.' && self.chars.get(self.pos + 1).is_some_and(|n| n.is_ascii_digit());
+                    if !d.is_ascii_digit() && !decimal_point {
+                        break;
+                    }
+                    number.push(d);
+                    self.pos += 1;
+                }
+                format!("<mn>{}</mn>", number)
+            }
+            Token::Char(c) if c.is_alphabetic() => format!("<mi>{}</mi>", escape_html(&c.to_string())),
+            Token::Char('~') => "<mspace width=\"0.25em\"></mspace>".to_string(),
+            Token::Char(c) => operator(&c.to_string()),
+            Token::Command(name) => return self.command(&name),
+        };
+        Ok((atom, false))
+    }
+
+    fn command(&mut self, name: &str) -> Result<(String, bool), String> {
+        let lookup = |table: &[(&str, &'static str)]| table.iter().find(|(n, _)| *n == name).map(|(_, s)| *s);
+
+        if let Some(symbol) = lookup(GREEK) {
+            return Ok((format!("<mi>{}</mi>", symbol), false));
+        }
+        if let Some(symbol) = lookup(GREEK_UPRIGHT) {
+            return Ok((format!("<mi mathvariant=\"normal\">{}</mi>", symbol), false));
+        }
+        if let Some(symbol) = lookup(IDENTIFIERS) {
+            return Ok((format!("<mi>{}</mi>", symbol), false));
+        }
+        if let Some(symbol) = lookup(OPERATORS) {
+            return Ok((operator(symbol), false));
+        }
+        if let Some(symbol) = lookup(BIG_OPERATORS) {
+            return Ok((format!("<mo largeop=\"true\" movablelimits=\"true\">{}</mo>", symbol), true));
+        }
+        if let Some(symbol) = lookup(INTEGRALS) {
+            return Ok((format!("<mo largeop=\"true\">{}</mo>", symbol), false));
+        }
+        if FUNCTIONS.contains(&name) {
+            return Ok((format!("<mi>{}</mi>", name), false));
+        }
+        if LIMIT_FUNCTIONS.contains(&name) {
+            return Ok((format!("<mi>{}</mi>", name), true));
+        }
+        if let Some(width) = lookup(SPACES) {
+            return Ok((format!("<mspace width=\"{}\"></mspace>", width), false));
+        }
+        if let Some((_, mark, stretchy)) = ACCENTS.iter().find(|(n, _, _)| *n == name) {
+            let base = self.argument()?;
+            return Ok((format!(
+                "<mover accent=\"true\">{}<mo stretchy=\"{}\">{}</mo></mover>",
+                base, stretchy, escape_html(mark)
+            ), false));
+        }
+        if let Some((_, mark)) = UNDER_ACCENTS.iter().find(|(n, _)| *n == name) {
+            let base = self.argument()?;
+            return Ok((format!(
+                "<munder accentunder=\"true\">{}<mo stretchy=\"true\">{}</mo></munder>",
+                base, escape_html(mark)
+            ), false));
+        }
+
+        let atom = match name {
+            "{" | "}" | "|" | "#" | "%" | "&" | "$" | "_" => {
+                let symbol = if name == "|" { "‖" } else { name };
+                operator(symbol)
+            }
+            "frac" | "dfrac" | "tfrac" => {
+                let num = self.argument()?;
+                let den = self.argument()?;
+                format!("<mfrac>{}{}</mfrac>", num, den)
+            }
+            "binom" => {
+                let n = self.argument()?;
+                let k = self.argument()?;
+                format!("<mrow><mo>(</mo><mfrac linethickness=\"0\">{}{}</mfrac><mo>)</mo></mrow>", n, k)
+            }
+            "sqrt" => {
+                self.skip_spaces();
+                if self.chars.get(self.pos) == Some(&'[') {
+                    self.pos += 1;
+                    let start = self.pos;
+                    while self.chars.get(self.pos).is_some_and(|c| *c != ']') {
+                        self.pos += 1;
+                    }
+                    if self.pos >= self.chars.len() {
+                        return Err("Unclosed '['".to_string());
+                    }
+                    let index: String = self.chars[start..self.pos].iter().collect();
+                    self.pos += 1;
+                    let index = tex_to_row(&index, self.display, self.depth)?;
+                    let base = self.argument()?;
+                    format!("<mroot>{}{}</mroot>", base, index)
+                } else {
+                    format!("<msqrt>{}</msqrt>", self.argument()?)
+                }
+            }
+            "text" | "textrm" | "textit" | "textbf" | "mbox" | "textnormal" => {
+                let text = self.raw_group()?;
+                let text: String = unescape_text(&text);
+                format!("<mtext>{}</mtext>", escape_html(&text))
+            }
+            "mathrm" | "operatorname" | "mathit" | "mathbf" | "boldsymbol" | "mathbb" | "mathcal"
+            | "mathscr" | "mathfrak" => {
+                let raw = self.raw_group()?;
+                if raw.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ') && !raw.trim().is_empty() {
+                    styled(&raw, name)
+                } else {
+                    tex_to_row(&raw, self.display, self.depth)?
+                }
+            }
+            "left" => {
+                self.enter()?;
+                let open = self.delimiter()?;
+                let items = self.sequence()?;
+                if self.next_token() != Some(Token::Command("right".to_string())) {
+                    return Err("\\left without \\right".to_string());
+                }
+                let close = self.delimiter()?;
+                self.depth -= 1;
+                format!("<mrow>{}{}{}</mrow>", fence(&open), items.concat(), fence(&close))
+            }
+            "middle" => fence(&self.delimiter()?),
+            "begin" => {
+                self.enter()?;
+                let env = self.raw_group()?;
+                let table = self.environment(env.trim())?;
+                self.depth -= 1;
+                table
+            }
+            "displaystyle" | "textstyle" | "limits" | "nolimits" | "big" | "Big" | "bigg" | "Bigg" => {
+                "<mrow></mrow>".to_string()
+            }
+            _ => return Err(format!("Unknown command \\{}", name)),
+        };
+        Ok((atom, false))
+    }
+
+    /// The delimiter after `\left`, `\middle` or `\right`; `.` is none.
+    fn delimiter(&mut self) -> Result<String, String> {
+        match self.next_token() {
+            Some(Token::Char('.')) => Ok(String::new()),
+            Some(Token::Char(c)) => Ok(c.to_string()),
+            Some(Token::Command(name)) => match name.as_str() {
+                "{" | "}" => Ok(name),
+                "|" => Ok("‖".to_string()),
+                _ => OPERATORS.iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, s)| s.to_string())
+                    .ok_or_else(|| format!("Bad delimiter \\{}", name)),
+            },
+            _ => Err("Missing delimiter".to_string()),
+        }
+    }
+
+    /// Parses the body of `\begin{env}` up to its `\end{env}` into a table.
+    fn environment(&mut self, env: &str) -> Result<String, String> {
+        let (open, close, align) = if let Some((_, open, close)) = MATRICES.iter().find(|(n, _, _)| *n == env) {
+            (*open, *close, None)
+        } else {
+            match env {
+                "cases" => ("{", "", Some("left left")),
+                "aligned" | "align" | "align*" | "split" => ("", "", Some("right left")),
+                "gathered" | "gather" | "gather*" => ("", "", None),
+                "array" => {
+                    // The column spec is ignored.
+                    self.raw_group()?;
+                    ("", "", None)
+                }
+                _ => return Err(format!("Unknown environment {}", env)),
+            }
+        };
+
+        let mut rows = Vec::new();
+        let mut cells = Vec::new();
+        loop {
+            cells.push(row(self.sequence()?));
+            match self.next_token() {
+                Some(Token::Align) => {}
+                Some(Token::Command(name)) if name == "\\" => {
+                    rows.push(std::mem::take(&mut cells));
+                }
+                Some(Token::Command(name)) if name == "end" => {
+                    let end = self.raw_group()?;
+                    if end.trim() != env {
+                        return Err(format!("\\begin{{{}}} ended by \\end{{{}}}", env, end.trim()));
+                    }
+                    // A trailing `\\` leaves an empty last row.
+                    if !(cells.len() == 1 && cells[0] == "<mrow></mrow>") {
+                        rows.push(cells);
+                    }
+                    break;
+                }
+                _ => return Err(format!("Unclosed environment {}", env)),
+            }
+        }
+
+        let mut table = match align {
+            Some(align) => format!("<mtable columnalign=\"{}\">", align),
+            None => "<mtable>".to_string(),
+        };
+        for cells in rows {
+            table.push_str("<mtr>");
+            for cell in cells {
+                table.push_str(&format!("<mtd>{}</mtd>", cell));
+            }
+            table.push_str("</mtr>");
+        }
+        table.push_str("</mtable>");
+        if open.is_empty() && close.is_empty() {
+            return Ok(table);
+        }
+        Ok(format!("<mrow>{}{}{}</mrow>", fence(open), table, fence(close)))
+    }
+}
+
+/// Parses a nested piece of TeX (an optional argument or styled group).
+fn tex_to_row(tex: &str, display: bool, depth: usize) -> Result<String, String> {
+    let mut parser = TexParser { chars: tex.chars().collect(), pos: 0, display, depth: depth + 1 };
+    parser.enter()?;
+    let items = parser.sequence()?;
+    if let Some(token) = parser.peek_token() {
+        return Err(format!("Unexpected {:?}", token));
+    }
+    Ok(row(items))
+}
+
+fn row(items: Vec<String>) -> String {
+    if items.len() == 1 {
+        items.into_iter().next().unwrap_or_default()
+    } else {
+        format!("<mrow>{}</mrow>", items.concat())
+    }
+}
+
+fn operator(symbol: &str) -> String {
+    let symbol = match symbol {
+        "-" => "−",
+        "*" => "∗",
+        s => s,
+    };
+    // Plain brackets keep their size, as in TeX; use \left/\right to stretch.
+    if matches!(symbol, "(" | ")" | "[" | "]" | "{" | "}" | "|" | "‖" | "⟨" | "⟩" | "⌊" | "⌋" | "⌈" | "⌉") {
+        format!("<mo stretchy=\"false\">{}</mo>", escape_html(symbol))
+    } else {
+        format!("<mo>{}</mo>", escape_html(symbol))
+    }
+}
+
+fn fence(symbol: &str) -> String {
+    if symbol.is_empty() {
+        String::new()
+    } else {
+        format!("<mo fence=\"true\" stretchy=\"true\">{}</mo>", escape_html(symbol))
+    }
+}
+
+/// Drops the backslash from escaped characters inside `\text{...}`.
+fn unescape_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(next @ ('{' | '}' | '$' | '%' | '&' | '#' | '_' | ' ')) => out.push(next),
+                Some(next) => {
+                    out.push('\\');
+                    out.push(next);
+                }
+                None => out.push('\\'),
+            },
+            '~' => out.push('\u{a0}'),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Letters and digits in a font command. Upright text is one `<mi>` (which
+/// MathML renders upright when longer than one character); the other styles
+/// map to the Unicode mathematical alphanumeric symbols, since MathML Core
+/// does not support `mathvariant` beyond `normal`.
+fn styled(raw: &str, command: &str) -> String {
+    let text: String = raw.split_whitespace().collect();
+    match command {
+        "mathrm" | "operatorname" => {
+            if text.chars().count() == 1 {
+                format!("<mi mathvariant=\"normal\">{}</mi>", text)
+            } else {
+                format!("<mi>{}</mi>", text)
+            }
+        }
+        "mathit" => row(text.chars().map(|c| format!("<mi>{}</mi>", c)).collect()),
+        _ => row(text.chars()
+            .map(|c| {
+                let mapped = math_alphanumeric(c, command).unwrap_or(c);
+                if c.is_ascii_digit() {
+                    format!("<mn>{}</mn>", mapped)
+                } else {
+                    format!("<mi>{}</mi>", mapped)
+                }
+            })
+            .collect()),
+    }
+}
+
+fn math_alphanumeric(c: char, command: &str) -> Option<char> {
+    // Letters already encoded in the Letterlike Symbols block are holes in
+    // the Mathematical Alphanumeric Symbols block.
+    let exception = |table: &[(char, char)]| table.iter().find(|(from, _)| *from == c).map(|(_, to)| *to);
+    let (upper, lower, digit) = match command {
+        "mathbf" | "boldsymbol" => (0x1D400, 0x1D41A, Some(0x1D7CE)),
+        "mathbb" => {
+            if let Some(ch) = exception(&[('C', 'ℂ'), ('H', 'ℍ'), ('N', 'ℕ'), ('P', 'ℙ'), ('Q', 'ℚ'), ('R', 'ℝ'), ('Z', 'ℤ')]) {
+                return Some(ch);
+            }
+            (0x1D538, 0x1D552, Some(0x1D7D8))
+        }
+        "mathcal" | "mathscr" => {
+            if let Some(ch) = exception(&[
+                ('B', 'ℬ'), ('E', 'ℰ'), ('F', 'ℱ'), ('H', 'ℋ'), ('I', 'ℐ'), ('L', 'ℒ'), ('M', 'ℳ'),
+                ('R', 'ℛ'), ('e', 'ℯ'), ('g', 'ℊ'), ('o', 'ℴ'),
+            ]) {
+                return Some(ch);
+            }
+            (0x1D49C, 0x1D4B6, None)
+        }
+        "mathfrak" => {
+            if let Some(ch) = exception(&[('C', 'ℭ'), ('H', 'ℌ'), ('I', 'ℑ'), ('R', 'ℜ'), ('Z', 'ℨ')]) {
+                return Some(ch);
+            }
+            (0x1D504, 0x1D51E, None)
+        }
+        _ => return None,
+    };
+    let code = match c {
+        'A'..='Z' => upper + (c as u32 - 'A' as u32),
+        'a'..='z' => lower + (c as u32 - 'a' as u32),
+        '0'..='9' => digit? + (c as u32 - '0' as u32),
+        _ => return None,
+    };
+    char::from_u32(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::{html, Options, Parser};
+
+    use super::*;
+
+    fn render(markdown: &str) -> String {
+        let events = render_math(markdown, Parser::new_ext(markdown, Options::all()).into_offset_iter());
+        let mut out = String::new();
+        html::push_html(&mut out, events.into_iter());
+        out
+    }
+
+    fn inner(tex: &str, display: bool) -> String {
+        let mathml = tex_to_mathml(tex, display).unwrap();
+        let start = mathml.find("<semantics>").unwrap() + "<semantics>".len();
+        let end = mathml.find("<annotation").unwrap();
+        mathml[start..end].to_string()
+    }
+
+    #[test]
+    fn fractions_and_roots() {
+        assert_eq!(inner("\\frac{a}{b}", false), "<mfrac><mi>a</mi><mi>b</mi></mfrac>");
+        assert_eq!(inner("\\frac12", false), "<mfrac><mn>1</mn><mn>2</mn></mfrac>");
+        assert_eq!(inner("\\sqrt{x}", false), "<msqrt><mi>x</mi></msqrt>");
+        assert_eq!(inner("\\sqrt[3]{x}", false), "<mroot><mi>x</mi><mn>3</mn></mroot>");
+    }
+
+    #[test]
+    fn scripts_and_limits() {
+        assert_eq!(inner("x^2_i", false), "<msubsup><mi>x</mi><mi>i</mi><mn>2</mn></msubsup>");
+        // Only the first digit is the exponent, as in TeX.
+        assert_eq!(inner("x^12", false), "<mrow><msup><mi>x</mi><mn>1</mn></msup><mn>2</mn></mrow>");
+        assert!(inner("\\sum_{i=1}^n i", true).starts_with("<mrow><munderover>"));
+        assert!(inner("\\sum_{i=1}^n i", false).starts_with("<mrow><msubsup>"));
+    }
+
+    #[test]
+    fn environments() {
+        let matrix = inner("\\begin{pmatrix}a & b \\\\ c & d\\end{pmatrix}", true);
+        assert!(matrix.starts_with("<mrow><mo"), "{}", matrix);
+        assert_eq!(matrix.matches("<mtr>").count(), 2);
+        assert_eq!(matrix.matches("<mtd>").count(), 4);
+        let cases = inner("f(x) = \\begin{cases} 1 & x > 0 \\\\ 0 & \\text{otherwise} \\end{cases}", true);
+        assert!(cases.contains("<mtable columnalign=\"left left\">"));
+        assert!(cases.contains("<mtext>otherwise</mtext>"));
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        for tex in ["\\frac{a}", "\\nope", "{a", "a}", "\\begin{foo}x\\end{foo}", "\\begin{matrix}a\\end{cases}", "\\left( x"] {
+            assert!(tex_to_mathml(tex, false).is_err(), "{}", tex);
+        }
+        assert!(render("$\\nope$").contains("<code class=\"math-error\">\\nope</code>"));
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |n: usize| format!("{}x{}", "{".repeat(n), "}".repeat(n));
+        assert!(tex_to_mathml(&nested(10), false).is_ok());
+        assert!(tex_to_mathml(&nested(MAX_DEPTH + 1), false).is_err());
+    }
+
+    #[test]
+    fn dollar_amounts_are_not_math() {
+        let html = render("It costs $5 and $10.");
+        assert!(!html.contains("<math"), "{}", html);
+        assert!(html.contains("$5 and $10"));
+        assert!(!render("Escaped \\$x$ stays text").contains("<math"));
+        assert!(render("Inline $x^2$ math").contains("<math display=\"inline\">"));
+        assert!(render("$$\na + b\n$$").contains("<math display=\"block\">"));
+    }
+
+    #[test]
+    fn chained_commands_hit_the_depth_limit() {
+        for command in ["\\hat", "\\frac", "\\sqrt", "\\overline"] {
+            let tex = format!("{} x", command.repeat(20_000));
+            match tex_to_mathml(&tex, false) {
+                Err(error) => assert!(error.contains("nested too deeply"), "{}: {}", command, error),
+                Ok(_) => panic!("{} nested 20000 times was accepted", command),
+            }
+        }
+        assert!(tex_to_mathml(&format!("{} x", "\\hat".repeat(10)), false).is_ok());
+    }
+}
@@ -0,0 +1,325 @@
+use std::ops::Range;
+
+use pulldown_cmark::{Event, Tag};
+
+use crate::escape::escape_html;
+use crate::images::ImageContext;
+
+/// Markdown pass wrapping an image that sits alone in its paragraph and has
+/// a title in a figure, with the title as its caption:
+///
+/// ```text
+/// ![A lighthouse at dusk](lighthouse.jpg "Pointe du Raz, 2023")
+/// ```
+///
+/// Images without a title, or sharing their paragraph with text, stay inline.
+pub fn figures<'a, I: Iterator<Item = Event<'a>>>(events: I) -> Vec<Event<'a>> {
+    let events: Vec<Event<'a>> = events.collect();
+    let mut out = Vec::with_capacity(events.len());
+    let mut i = 0;
+    while i < events.len() {
+        if let Some((end, caption)) = lone_image(&events, i) {
+            out.push(Event::Html("<figure>\n".into()));
+            out.extend(events[i + 1..end].iter().cloned());
+            out.push(Event::Html(format!("\n<figcaption>{}</figcaption>\n</figure>\n", escape_html(&caption)).into()));
+            i = end + 1;
+            continue;
+        }
+        out.push(events[i].clone());
+        i += 1;
+    }
+    out
+}
+
+/// For a paragraph at `start` holding only a titled image, the index of the
+/// paragraph's end and the title.
+fn lone_image(events: &[Event], start: usize) -> Option<(usize, String)> {
+    if !matches!(events.get(start), Some(Event::Start(Tag::Paragraph))) {
+        return None;
+    }
+    let Some(Event::Start(Tag::Image(_, _, title))) = events.get(start + 1) else { return None };
+    if title.trim().is_empty() {
+        return None;
+    }
+    let mut i = start + 2;
+    let mut depth = 1;
+    while depth > 0 {
+        match events.get(i)? {
+            Event::Start(Tag::Image(..)) => depth += 1,
+            Event::End(Tag::Image(..)) => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+    matches!(events.get(i), Some(Event::End(Tag::Paragraph))).then(|| (i, title.trim().to_string()))
+}
+
+/// Markdown pass expanding embed shortcodes written alone in a paragraph:
+///
+/// ```text
+/// {{< youtube dQw4w9WgXcQ title="Talk recording" >}}
+/// {{< gist octocat 6cad326836d38bd3a7ae script.rs >}}
+/// ```
+///
+/// Embeds are rendered as link cards rather than iframes, so readers'
+/// browsers contact no third party until they follow the link. The
+/// shortcode is read from `source` through the event offsets, as smart
+/// punctuation would otherwise have rewritten its quotes and dashes.
+/// Unknown or malformed shortcodes are left as text.
+pub fn embed_shortcodes<'a>(source: &'a str, events: Vec<(Event<'a>, Range<usize>)>) -> Vec<(Event<'a>, Range<usize>)> {
+    let mut out = Vec::with_capacity(events.len());
+    let mut i = 0;
+    while i < events.len() {
+        if matches!(events[i].0, Event::Start(Tag::Paragraph))
+            && let Some((end, raw)) = paragraph_text(source, &events, i)
+            && let Some(card) = shortcode(raw.trim()) {
+            out.push((Event::Html(card.into()), events[i].1.clone()));
+            i = end + 1;
+            continue;
+        }
+        out.push(events[i].clone());
+        i += 1;
+    }
+    out
+}
+
+/// Raw source of a paragraph made only of text, with the index of its end.
+fn paragraph_text(source: &str, events: &[(Event, Range<usize>)], start: usize) -> Option<(usize, String)> {
+    let mut raw = String::new();
+    for (idx, (event, range)) in events.iter().enumerate().skip(start + 1) {
+        match event {
+            Event::Text(_) => raw.push_str(source.get(range.clone())?),
+            Event::SoftBreak => raw.push(' '),
+            Event::End(Tag::Paragraph) => return Some((idx, raw)),
+            _ => return None,
+        }
+    }
+    None
+}
+
+/// HTML for a `{{< name args >}}` shortcode, if it is a known and valid one.
+fn shortcode(text: &str) -> Option<String> {
+    let inner = text.strip_prefix("{{<")?.strip_suffix(">}}")?;
+    let args = split_args(inner)?;
+    let (name, args) = args.split_first()?;
+    let mut positional = Vec::new();
+    let mut named = Vec::new();
+    for arg in args {
+        match arg.split_once('=') {
+            Some((key, value)) if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphabetic()) => {
+                named.push((key.to_string(), value.to_string()));
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+    let get = |key: &str, index: usize| {
+        named.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone()).or_else(|| positional.get(index).cloned())
+    };
+
+    match name.as_str() {
+        "youtube" => {
+            let id = get("id", 0)?;
+            let valid = id.len() == 11 && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+            if !valid {
+                return None;
+            }
+            let title = get("title", 1).unwrap_or_else(|| "Watch on YouTube".to_string());
+            Some(card("youtube", "YouTube", &format!("https://www.youtube.com/watch?v={}", id), &title))
+        }
+        "gist" => {
+            let user = get("user", 0)?;
+            let id = get("id", 1)?;
+            let valid_user = !user.is_empty() && user.len() <= 39
+                && user.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+            let valid_id = !id.is_empty() && id.len() <= 64 && id.bytes().all(|b| b.is_ascii_alphanumeric());
+            if !valid_user || !valid_id {
+                return None;
+            }
+            let mut url = format!("https://gist.github.com/{}/{}", user, id);
+            let title = match get("file", 2) {
+                Some(file) => {
+                    // GitHub anchors each file as `file-<name>` with dots replaced.
+                    let anchor: String = file.chars()
+                        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c.to_ascii_lowercase() } else { '-' })
+                        .collect();
+                    url.push_str(&format!("#file-{}", anchor));
+                    file
+                }
+                None => format!("{}/{}", user, id),
+            };
+            Some(card("gist", "GitHub Gist", &url, &title))
+        }
+        _ => None,
+    }
+}
+
+fn card(kind: &str, label: &str, url: &str, title: &str) -> String {
+    let display_url = url.trim_start_matches("https://").trim_start_matches("www.");
+    format!(
+        "<div class=\"embed-card embed-{}\">\n<a href=\"{}\" rel=\"noopener noreferrer nofollow\">\
+         <span class=\"embed-label\">{}</span>\
+         <span class=\"embed-title\">{}</span>\
+         <span class=\"embed-url\">{}</span></a>\n</div>\n",
+        kind,
+        escape_html(url),
+        escape_html(label),
+        escape_html(title),
+        escape_html(display_url)
+    )
+}
+
+/// Splits shortcode arguments on whitespace, keeping double-quoted values
+/// (`title="Two words"`) together. `None` on an unterminated quote.
+fn split_args(text: &str) -> Option<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut started = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    args.push(std::mem::take(&mut current));
+                    started = false;
+                }
+            }
+            c => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+    if quoted {
+        return None;
+    }
+    if started {
+        args.push(current);
+    }
+    Some(args)
+}
+
+/// Markdown pass writing images as `<img>` tags that load lazily. Images
+/// under the static root also get their intrinsic `width` and `height`, so
+/// the page does not shift as they load, and a `srcset` of the resized
+/// widths the server offers below the original size. `slug` is the post the
+/// images appear in, against which relative paths resolve.
+pub fn responsive_images<'a, I: Iterator<Item = Event<'a>>>(
+    events: I,
+    slug: &str,
+    images: Option<&ImageContext>,
+) -> Vec<Event<'a>> {
+    let mut out = Vec::new();
+    // Source and title of the open image, and its alt text so far.
+    let mut open: Option<(String, String, String)> = None;
+    let mut depth = 0;
+    for event in events {
+        match event {
+            Event::Start(Tag::Image(_, dest, title)) => {
+                depth += 1;
+                if depth == 1 {
+                    open = Some((dest.to_string(), title.to_string(), String::new()));
+                }
+            }
+            Event::End(Tag::Image(..)) => {
+                depth -= 1;
+                if depth > 0 {
+                    continue;
+                }
+                let Some((src, title, alt)) = open.take() else { continue };
+                let mut tag = format!("<img src=\"{}\" alt=\"{}\"", escape_html(&src), escape_html(&alt));
+                if !title.is_empty() {
+                    tag.push_str(&format!(" title=\"{}\"", escape_html(&title)));
+                }
+                if let Some(info) = images.and_then(|ctx| ctx.describe(slug, &src)) {
+                    tag.push_str(&format!(" width=\"{}\" height=\"{}\"", info.width, info.height));
+                    if let Some(srcset) = info.srcset {
+                        tag.push_str(&format!(
+                            " srcset=\"{}\" sizes=\"(max-width: {}px) 100vw, {}px\"",
+                            escape_html(&srcset), info.width, info.width
+                        ));
+                    }
+                }
+                tag.push_str(" loading=\"lazy\" decoding=\"async\" />");
+                out.push(Event::Html(tag.into()));
+            }
+            // Only the text of what is inside an image becomes its alt text.
+            Event::Text(text) | Event::Code(text) if open.is_some() => {
+                if let Some((_, _, alt)) = open.as_mut() {
+                    alt.push_str(&text);
+                }
+            }
+            _ if open.is_some() => {}
+            event => out.push(event),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::{html, Options, Parser};
+
+    use super::*;
+
+    fn render(markdown: &str) -> String {
+        let events = Parser::new_ext(markdown, Options::all()).into_offset_iter().collect();
+        let events = embed_shortcodes(markdown, events).into_iter().map(|(event, _)| event);
+        let mut out = String::new();
+        html::push_html(&mut out, figures(events).into_iter());
+        out
+    }
+
+    #[test]
+    fn valid_shortcodes_become_link_cards() {
+        let html = render("{{< youtube dQw4w9WgXcQ title=\"Talk -- recording\" >}}");
+        assert!(html.contains("<a href=\"https://www.youtube.com/watch?v=dQw4w9WgXcQ\""), "{}", html);
+        assert!(html.contains("<span class=\"embed-title\">Talk -- recording</span>"));
+        assert!(!html.contains("<iframe"));
+        let html = render("{{< gist octocat 6cad326836d38bd3a7ae script.rs >}}");
+        assert!(html.contains("https://gist.github.com/octocat/6cad326836d38bd3a7ae#file-script-rs"), "{}", html);
+    }
+
+    #[test]
+    fn malformed_shortcodes_stay_text() {
+        for source in [
+            "{{< youtube short >}}",
+            "{{< youtube dQw4w9WgXc\" >}}",
+            "{{< youtube dQw4w9WgXcQ title=\"unterminated >}}",
+            "{{< gist oct/cat 6cad326836d38bd3a7ae >}}",
+            "{{< gist octocat not-hex! >}}",
+            "{{< vimeo 12345 >}}",
+            "{{< youtube >}}",
+            "{{ youtube dQw4w9WgXcQ }}",
+            "Watch {{< youtube dQw4w9WgXcQ >}} here",
+        ] {
+            let html = render(source);
+            assert!(!html.contains("embed-card"), "{} rendered as {}", source, html);
+            assert!(html.starts_with("<p>"), "{} rendered as {}", source, html);
+        }
+    }
+
+    #[test]
+    fn titled_lone_images_become_figures() {
+        assert_eq!(
+            render("![Alt](a.png \"Caption & more\")"),
+            "<figure>\n<img src=\"a.png\" alt=\"Alt\" title=\"Caption &amp; more\" />\n<figcaption>Caption &amp; more</figcaption>\n</figure>\n"
+        );
+        assert!(!render("![Alt](a.png)").contains("<figure>"));
+        assert!(!render("Text ![Alt](a.png \"Title\")").contains("<figure>"));
+    }
+
+    #[test]
+    fn images_load_lazily_with_escaped_attributes() {
+        let events = responsive_images(Parser::new("![a \"quoted\" *alt*](x.png)"), "post", None);
+        let mut out = String::new();
+        html::push_html(&mut out, events.into_iter());
+        assert_eq!(
+            out,
+            "<p><img src=\"x.png\" alt=\"a &quot;quoted&quot; alt\" loading=\"lazy\" decoding=\"async\" /></p>\n"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use serde::Serialize;
+
+use crate::blog::{self, BlogPost};
+use crate::config::{BlogConfig, SiteConfig};
+use crate::date::Date;
+use crate::feed::absolute_url;
+
+/// Descriptions longer than this are cut at a word boundary; search engines
+/// and social cards truncate around here anyway.
+const DESCRIPTION_CHARS: usize = 160;
+
+/// Everything a post page needs for its `<meta>` description, canonical
+/// link, Open Graph and Twitter Card tags and `BlogPosting` JSON-LD.
+/// URLs are absolute, as crawlers require.
+#[derive(Debug, Serialize)]
+pub struct PageMeta {
+    pub title: String,
+    pub description: String,
+    pub canonical_url: String,
+    pub site_name: String,
+    /// Open Graph locale such as `en_US`, derived from `site.language`.
+    pub locale: String,
+    pub image: Option<String>,
+    /// `summary_large_image` when there is an image, `summary` otherwise.
+    pub twitter_card: &'static str,
+    pub twitter_site: Option<String>,
+    pub author: Option<String>,
+    pub published_time: Option<String>,
+    pub modified_time: Option<String>,
+    pub tags: Vec<String>,
+    /// The JSON-LD object, escaped so it can be embedded in a `<script>`.
+    pub json_ld: String,
+}
+
+impl PageMeta {
+    /// Metadata of a post page. The post's front matter wins over site-wide
+    /// defaults: `summary` over the generated excerpt and the site
+    /// description, `cover_image` over `site.image`, `author` over
+    /// `site.author` and `canonical_url` over the post's own URL.
+    pub fn for_post(post: &BlogPost, site: &SiteConfig, blog: &BlogConfig) -> PageMeta {
+        let base = site.base_url.as_str();
+        let canonical_url = post.canonical_url.as_deref()
+            .map(|url| absolute_url(url, base))
+            .unwrap_or_else(|| format!("{}{}", base, blog.post_url(&post.slug)));
+        let description = post.summary.as_deref()
+            .filter(|s| !s.trim().is_empty())
+            .or(Some(post.excerpt.as_str()).filter(|s| !s.trim().is_empty()))
+            .unwrap_or(&site.description);
+        let description = description.split_whitespace().collect::<Vec<_>>().join(" ");
+        let description = blog::truncate_at_word(&description, DESCRIPTION_CHARS);
+        let image = post.cover_image.as_deref()
+            .or(site.image.as_deref())
+            .map(|img| absolute_url(img, base));
+        let author = post.author.clone()
+            .or_else(|| (!site.author.is_empty()).then(|| site.author.clone()));
+        let published_time = post.date.map(|d| d.to_iso());
+        let modified_time = post.modified.map(|m| Date::from_system_time(m).to_iso());
+        let site_name = site.name.clone().unwrap_or_else(|| site.title.clone());
+
+        let json_ld = serde_json::json!({
+            "@context": "https://schema.org",
+            "@type": "BlogPosting",
+            "headline": &post.title,
+            "description": &description,
+            "url": &canonical_url,
+            "mainEntityOfPage": { "@type": "WebPage", "@id": &canonical_url },
+            "image": &image,
+            "datePublished": &published_time,
+            "dateModified": modified_time.as_ref().or(published_time.as_ref()),
+            "author": author.as_ref().map(|name| serde_json::json!({ "@type": "Person", "name": name })),
+            "publisher": { "@type": "Organization", "name": &site_name, "url": base },
+            "keywords": (!post.tags.is_empty()).then(|| post.tags.join(", ")),
+            "wordCount": post.word_count,
+            "inLanguage": &site.language,
+        });
+        // Null fields are dropped rather than published as `null`.
+        let json_ld = match json_ld {
+            serde_json::Value::Object(map) => serde_json::Value::Object(
+                map.into_iter().filter(|(_, v)| !v.is_null()).collect()
+            ),
+            other => other,
+        };
+
+        PageMeta {
+            title: post.title.clone(),
+            description,
+            canonical_url,
+            site_name,
+            locale: og_locale(&site.language),
+            twitter_card: if image.is_some() { "summary_large_image" } else { "summary" },
+            image,
+            twitter_site: site.twitter.as_ref().map(|handle| format!("@{}", handle.trim_start_matches('@'))),
+            author,
+            published_time,
+            modified_time,
+            tags: post.tags.clone(),
+            json_ld: script_safe_json(&json_ld),
+        }
+    }
+}
+
+/// Serializes JSON for inline `<script>` use: `<`, `>` and `&` are written
+/// as unicode escapes, so neither `</script>` nor `<!--` can appear in it.
+fn script_safe_json(value: &serde_json::Value) -> String {
+    serde_json::to_string(value)
+        .unwrap_or_default()
+        .replace('<', "\\u003c")
+        .replace('>', "\\u003e")
+        .replace('&', "\\u0026")
+}
+
+/// Open Graph writes locales with an underscore: `en-GB` becomes `en_GB`.
+fn og_locale(language: &str) -> String {
+    language.replace('-', "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{get, site};
+
+    const SITE: &str = "\nsite:\n  base_url: https://example.com/\n  title: Example\n  description: The site description\n  \
+                        author: Site Author\n  language: en-GB\n  image: /og.png\n  twitter: example\n";
+
+    fn post(front: &str, body: &str) -> BlogPost {
+        BlogPost::parse("hello", &format!("---\ntitle: Hello\ndate: 2024-03-05\ntags: [Rust, Web]\n{}---\n{}", front, body), None)
+    }
+
+    fn without_image_or_author(site: &SiteConfig) -> SiteConfig {
+        SiteConfig { image: None, author: String::new(), ..site.clone() }
+    }
+
+    #[test]
+    fn site_defaults_fill_in() {
+        let site = site("meta-defaults", &[], SITE);
+        let cfg = site.server.config.as_ref().unwrap();
+        let meta = PageMeta::for_post(&post("", "First   words\nof the post."), &cfg.site, &cfg.blog);
+        assert_eq!(meta.canonical_url, "https://example.com/blogs/hello");
+        assert_eq!(meta.description, "First words of the post.");
+        assert_eq!(meta.site_name, "Example");
+        assert_eq!(meta.locale, "en_GB");
+        assert_eq!(meta.image.as_deref(), Some("https://example.com/og.png"));
+        assert_eq!(meta.twitter_card, "summary_large_image");
+        assert_eq!(meta.twitter_site.as_deref(), Some("@example"));
+        assert_eq!(meta.author.as_deref(), Some("Site Author"));
+        assert_eq!(meta.published_time.as_deref(), Some("2024-03-05"));
+        assert_eq!(meta.tags, ["Rust", "Web"]);
+
+        let ld: serde_json::Value = serde_json::from_str(&meta.json_ld).unwrap();
+        assert_eq!(ld["@type"], "BlogPosting");
+        assert_eq!(ld["headline"], "Hello");
+        assert_eq!(ld["mainEntityOfPage"]["@id"], "https://example.com/blogs/hello");
+        assert_eq!(ld["datePublished"], "2024-03-05");
+        assert_eq!(ld["dateModified"], "2024-03-05");
+        assert_eq!(ld["author"]["name"], "Site Author");
+        assert_eq!(ld["publisher"]["url"], "https://example.com");
+        assert_eq!(ld["keywords"], "Rust, Web");
+        assert_eq!(ld["inLanguage"], "en-GB");
+
+        // Without an image or author there is nothing to publish as null.
+        let bare = without_image_or_author(&cfg.site);
+        let meta = PageMeta::for_post(&BlogPost::parse("bare", "# Bare", None), &bare, &cfg.blog);
+        assert_eq!(meta.twitter_card, "summary");
+        assert_eq!(meta.description, "The site description");
+        let ld: serde_json::Value = serde_json::from_str(&meta.json_ld).unwrap();
+        for key in ["image", "author", "datePublished", "dateModified", "keywords"] {
+            assert!(ld.get(key).is_none(), "{}", key);
+        }
+    }
+
+    #[test]
+    fn front_matter_wins() {
+        let site = site("meta-front", &[], SITE);
+        let cfg = site.server.config.as_ref().unwrap();
+        let front = "summary: A summary\nauthor: Post Author\ncover_image: /blogs/cover.png\n\
+                     canonical_url: /elsewhere/hello\n";
+        let meta = PageMeta::for_post(&post(front, "Body"), &cfg.site, &cfg.blog);
+        assert_eq!(meta.description, "A summary");
+        assert_eq!(meta.author.as_deref(), Some("Post Author"));
+        assert_eq!(meta.image.as_deref(), Some("https://example.com/blogs/cover.png"));
+        assert_eq!(meta.canonical_url, "https://example.com/elsewhere/hello");
+
+        let long = format!("summary: {}\n", "word ".repeat(60));
+        let meta = PageMeta::for_post(&post(&long, "Body"), &cfg.site, &cfg.blog);
+        assert!(meta.description.chars().count() <= DESCRIPTION_CHARS + 1, "{}", meta.description);
+        assert!(meta.description.starts_with("word word"));
+    }
+
+    #[test]
+    fn locales_use_underscores() {
+        assert_eq!(og_locale("en"), "en");
+        assert_eq!(og_locale("en-US"), "en_US");
+        assert_eq!(og_locale("zh-Hant-TW"), "zh_Hant_TW");
+    }
+
+    #[test]
+    fn json_cannot_close_the_script() {
+        let value = serde_json::json!({ "headline": "</script><script>alert(1)</script> <!-- & -->" });
+        let json = script_safe_json(&value);
+        assert!(!json.contains('<') && !json.contains('>') && !json.contains('&'), "{}", json);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&json).unwrap(), value);
+
+        let site = site("meta-script", &[("hello.md", "---\ntitle: </script><script>alert(1)</script>\n---\nBody")], SITE);
+        let page = String::from_utf8_lossy(&get(&site, "/blogs/hello").body).into_owned();
+        assert_eq!(page.matches("<script>alert(1)").count(), 0, "{}", page);
+        let start = page.find("<script type=\"application/ld+json\">").unwrap();
+        let end = start + page[start..].find("</script>").unwrap();
+        let ld: serde_json::Value = serde_json::from_str(&page[start + 35..end]).unwrap();
+        assert_eq!(ld["headline"], "</script><script>alert(1)</script>");
+    }
+}
@@ -0,0 +1,187 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::blog::{self, BlogPost};
+use crate::config::BlogConfig;
+use crate::search;
+use crate::tags;
+
+/// How many related posts are suggested below a post.
+pub const RELATED_LIMIT: usize = 3;
+
+// A shared tag is a strong signal; text similarity (0 to 1) breaks ties and
+// finds related posts that were never tagged alike.
+const TAG_WEIGHT: f64 = 1.0;
+const TEXT_WEIGHT: f64 = 1.0;
+const MIN_TEXT_SIMILARITY: f64 = 0.05;
+
+/// Just enough of another post to link to it.
+#[derive(Debug, Clone, Serialize)]
+pub struct PostLink {
+    pub slug: String,
+    pub title: String,
+    pub url: String,
+    pub published_date: String,
+}
+
+impl PostLink {
+    pub fn new(post: &BlogPost, blog: &BlogConfig) -> PostLink {
+        PostLink {
+            slug: post.slug.clone(),
+            title: post.title.clone(),
+            url: blog.post_url(&post.slug),
+            published_date: post.published_date.clone(),
+        }
+    }
+}
+
+/// Links shown at the end of a post: the posts just before and after it by
+/// date, and a few others on similar topics.
+#[derive(Debug, Clone, Serialize)]
+pub struct PostNavigation {
+    /// The next older post.
+    pub previous: Option<PostLink>,
+    /// The next newer post.
+    pub next: Option<PostLink>,
+    pub related: Vec<PostLink>,
+}
+
+type TermVector = HashMap<String, f64>;
+
+/// Term counts of each post's text, kept until the `BlogStore` generation
+/// changes, so a page view does not re-read every post's markdown.
+#[derive(Debug, Default)]
+pub struct TermCache {
+    entries: Mutex<(u64, HashMap<String, Arc<TermVector>>)>,
+}
+
+impl TermCache {
+    fn vectors(&self, generation: u64, posts: &[&BlogPost]) -> Vec<Arc<TermVector>> {
+        let mut guard = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if guard.0 != generation {
+            *guard = (generation, HashMap::new());
+        }
+        posts.iter()
+            .map(|post| guard.1.entry(post.slug.clone()).or_insert_with(|| Arc::new(term_counts(post))).clone())
+            .collect()
+    }
+}
+
+/// Navigation for `current` among `posts`. `current` does not have to be one
+/// of `posts` (a draft seen in preview mode still gets neighbours by date).
+/// `generation` is that of the store the posts came from.
+pub fn navigation(
+    current: &BlogPost,
+    posts: &[Arc<BlogPost>],
+    blog: &BlogConfig,
+    terms: &TermCache,
+    generation: u64,
+) -> PostNavigation {
+    let mut ordered: Vec<&BlogPost> = posts.iter()
+        .map(|post| post.as_ref())
+        .filter(|post| post.slug != current.slug)
+        .chain(std::iter::once(current))
+        .collect();
+    blog::sort_posts(&mut ordered);
+
+    // Newest first, so the newer neighbour comes before `current`.
+    let idx = ordered.iter().position(|post| post.slug == current.slug).unwrap_or(0);
+    let next = idx.checked_sub(1).map(|i| PostLink::new(ordered[i], blog));
+    let previous = ordered.get(idx + 1).map(|post| PostLink::new(post, blog));
+
+    let vectors = terms.vectors(generation, &ordered);
+    PostNavigation { previous, next, related: related(current, &ordered, &vectors, blog) }
+}
+
+/// Up to `RELATED_LIMIT` posts ranked by shared tags plus TF-IDF cosine
+/// similarity of their text. Posts with nothing in common are left out.
+fn related(current: &BlogPost, posts: &[&BlogPost], vectors: &[Arc<TermVector>], blog: &BlogConfig) -> Vec<PostLink> {
+    // Inverse document frequency over the candidate set, so words every post
+    // uses count for little.
+    let mut doc_freq: HashMap<&str, usize> = HashMap::new();
+    for vector in vectors {
+        for term in vector.keys() {
+            *doc_freq.entry(term.as_str()).or_insert(0) += 1;
+        }
+    }
+    let total = posts.len() as f64;
+    let weigh = |vector: &Arc<TermVector>| -> TermVector {
+        vector.iter()
+            .map(|(term, tf)| {
+                let df = doc_freq.get(term.as_str()).copied().unwrap_or(1) as f64;
+                (term.clone(), tf * (total / df).ln())
+            })
+            .collect()
+    };
+    let weighted: Vec<TermVector> = vectors.iter().map(weigh).collect();
+
+    let Some(current_idx) = posts.iter().position(|post| post.slug == current.slug) else {
+        return Vec::new();
+    };
+    let current_tags: HashSet<String> = current.tags.iter().map(|tag| tags::tag_slug(tag)).collect();
+
+    let mut scored: Vec<(f64, &BlogPost)> = posts.iter()
+        .enumerate()
+        .filter(|(idx, _)| *idx != current_idx)
+        .filter_map(|(idx, post)| {
+            let post_tags: HashSet<String> = post.tags.iter().map(|tag| tags::tag_slug(tag)).collect();
+            let shared = current_tags.intersection(&post_tags).count() as f64;
+            let similarity = cosine(&weighted[current_idx], &weighted[idx]);
+            if shared == 0.0 && similarity < MIN_TEXT_SIMILARITY {
+                return None;
+            }
+            Some((shared * TAG_WEIGHT + similarity * TEXT_WEIGHT, *post))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.slug.cmp(&b.1.slug)));
+
+    scored.into_iter()
+        .take(RELATED_LIMIT)
+        .map(|(_, post)| PostLink::new(post, blog))
+        .collect()
+}
+
+fn term_counts(post: &BlogPost) -> TermVector {
+    let (headings, text) = search::extract_text(&post.body);
+    let mut counts = HashMap::new();
+    for field in [&post.title, &headings, &text] {
+        for term in search::terms(field) {
+            *counts.entry(term).or_insert(0.0) += 1.0;
+        }
+    }
+    counts
+}
+
+fn cosine(a: &TermVector, b: &TermVector) -> f64 {
+    let dot: f64 = a.iter()
+        .filter_map(|(term, x)| b.get(term).map(|y| x * y))
+        .sum();
+    let norm = |v: &TermVector| v.values().map(|x| x * x).sum::<f64>().sqrt();
+    let denom = norm(a) * norm(b);
+    if denom == 0.0 { 0.0 } else { dot / denom }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn term_vectors_are_reused_until_the_store_changes() {
+        let rust = BlogPost::parse("rust", "# Rust\n\nOwnership and borrowing in Rust.", None);
+        let cargo = BlogPost::parse("cargo", "# Cargo\n\nBuilding Rust crates with cargo.", None);
+        let posts = [&rust, &cargo];
+        let cache = TermCache::default();
+
+        let first = cache.vectors(1, &posts);
+        let again = cache.vectors(1, &posts);
+        assert!(Arc::ptr_eq(&first[0], &again[0]) && Arc::ptr_eq(&first[1], &again[1]));
+        assert_eq!(first[0].get("ownership"), Some(&1.0));
+
+        let edited = BlogPost::parse("rust", "# Rust\n\nLifetimes.", None);
+        let changed = cache.vectors(2, &[&edited, &cargo]);
+        assert!(!Arc::ptr_eq(&first[1], &changed[1]));
+        assert_eq!(changed[0].get("ownership"), None);
+    }
+}
@@ -0,0 +1,358 @@
+use crate::escape::escape_html;
+
+/// Tags kept in rendered markdown. Anything else is dropped, keeping its text.
+const ALLOWED_TAGS: &[&str] = &[
+    "a", "abbr", "b", "blockquote", "br", "code", "dd", "del", "details", "div", "dl", "dt", "em",
+    "figcaption", "figure", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "i", "img", "input", "kbd",
+    "li", "mark", "ol", "p", "pre", "s", "span", "strong", "sub", "summary", "sup", "table",
+    "tbody", "td", "tfoot", "th", "thead", "tr", "u", "ul",
+];
+
+/// Tags whose content is dropped along with the tag itself.
+const DROPPED_WITH_CONTENT: &[&str] = &[
+    "script", "style", "iframe", "object", "embed", "noscript", "template", "textarea", "title",
+    "svg",
+];
+
+/// MathML elements produced by the math pass (and allowed when written by
+/// hand). None of them can load resources or run script.
+const MATHML_TAGS: &[&str] = &[
+    "math", "annotation", "menclose", "merror", "mfrac", "mi", "mmultiscripts", "mn", "mo",
+    "mover", "mpadded", "mphantom", "mroot", "mrow", "ms", "mspace", "msqrt", "mstyle", "msub",
+    "msubsup", "msup", "mtable", "mtd", "mtext", "mtr", "munder", "munderover", "semantics",
+];
+
+/// Presentation attributes allowed on any MathML element.
+const MATHML_ATTRIBUTES: &[&str] = &[
+    "accent", "accentunder", "columnalign", "columnspacing", "display", "displaystyle",
+    "encoding", "fence", "form", "largeop", "linethickness", "lspace", "mathvariant",
+    "movablelimits", "rowspacing", "rspace", "scriptlevel", "separator", "stretchy", "width",
+];
+
+const VOID_TAGS: &[&str] = &["br", "hr", "img", "input"];
+
+const GLOBAL_ATTRIBUTES: &[&str] = &["class", "id", "title", "lang", "dir"];
+
+/// Attributes allowed on specific tags, in addition to `GLOBAL_ATTRIBUTES`.
+const TAG_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href", "name", "rel"]),
+    ("img", &["src", "alt", "width", "height", "loading", "decoding", "srcset", "sizes"]),
+    ("input", &["type", "checked", "disabled"]),
+    ("ol", &["start"]),
+    ("td", &["align", "colspan", "rowspan", "style"]),
+    ("th", &["align", "colspan", "rowspan", "style"]),
+    ("details", &["open"]),
+    ("blockquote", &["cite"]),
+];
+
+const URL_ATTRIBUTES: &[&str] = &["href", "src", "cite"];
+
+/// Cleans HTML produced from markdown, which may carry raw HTML written by
+/// the author, down to an allowlist of tags and attributes. URLs must be
+/// relative or use http, https or mailto; inline styles are limited to the
+/// `text-align` that table rendering emits. Event handlers, `javascript:`
+/// links, comments and script-like elements are removed.
+pub fn sanitize_html(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(pos) = rest.find('<') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = after.find("-->").map(|end| &after[end + 3..]).unwrap_or("");
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map(|end| &rest[end + 1..]).unwrap_or("");
+            continue;
+        }
+
+        let Some(tag) = parse_tag(rest) else {
+            // Not markup, just a literal '<'.
+            out.push_str("&lt;");
+            rest = &rest[1..];
+            continue;
+        };
+        rest = &rest[tag.len..];
+
+        if DROPPED_WITH_CONTENT.contains(&tag.name.as_str()) {
+            if !tag.closing && !tag.self_closing {
+                rest = skip_past_close(rest, &tag.name);
+            }
+            continue;
+        }
+        if !ALLOWED_TAGS.contains(&tag.name.as_str()) && !MATHML_TAGS.contains(&tag.name.as_str()) {
+            continue;
+        }
+
+        if tag.closing {
+            if !VOID_TAGS.contains(&tag.name.as_str()) {
+                out.push_str(&format!("</{}>", tag.name));
+            }
+            continue;
+        }
+
+        out.push('<');
+        out.push_str(&tag.name);
+        for (name, value) in &tag.attributes {
+            if let Some(value) = clean_attribute(&tag.name, name, value.as_deref()) {
+                match value {
+                    Some(value) => out.push_str(&format!(" {}=\"{}\"", name, escape_html(&value))),
+                    None => out.push_str(&format!(" {}", name)),
+                }
+            }
+        }
+        if VOID_TAGS.contains(&tag.name.as_str()) {
+            out.push_str(" />");
+        } else {
+            out.push('>');
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+struct ParsedTag {
+    name: String,
+    closing: bool,
+    self_closing: bool,
+    attributes: Vec<(String, Option<String>)>,
+    /// Bytes consumed from the input, including the final '>'.
+    len: usize,
+}
+
+/// Parses the tag at the start of `input` (which begins with '<'). Returns
+/// `None` when the '<' does not start a tag.
+fn parse_tag(input: &str) -> Option<ParsedTag> {
+    let bytes = input.as_bytes();
+    let mut i = 1;
+    let closing = bytes.get(i) == Some(&b'/');
+    if closing {
+        i += 1;
+    }
+    let name_start = i;
+    if !bytes.get(i)?.is_ascii_alphabetic() {
+        return None;
+    }
+    while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'-') {
+        i += 1;
+    }
+    let name = input[name_start..i].to_ascii_lowercase();
+
+    let mut attributes = Vec::new();
+    let mut self_closing = false;
+    loop {
+        while i < bytes.len() && (bytes[i].is_ascii_whitespace() || bytes[i] == b'/') {
+            self_closing = bytes[i] == b'/';
+            i += 1;
+        }
+        match bytes.get(i) {
+            // An unterminated tag swallows the rest of the input.
+            None => return Some(ParsedTag { name, closing, self_closing, attributes, len: input.len() }),
+            Some(b'>') => break,
+            _ => {}
+        }
+        self_closing = false;
+
+        let attr_start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !matches!(bytes[i], b'=' | b'>' | b'/') {
+            i += 1;
+        }
+        let attr_name = input[attr_start..i].to_ascii_lowercase();
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+
+        let mut value = None;
+        if bytes.get(i) == Some(&b'=') {
+            i += 1;
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            match bytes.get(i) {
+                Some(&quote) if quote == b'"' || quote == b'\'' => {
+                    let start = i + 1;
+                    let end = input[start..].find(quote as char).map(|p| start + p).unwrap_or(input.len());
+                    value = Some(decode_entities(&input[start..end]));
+                    i = (end + 1).min(input.len());
+                }
+                _ => {
+                    let start = i;
+                    while i < bytes.len() && !bytes[i].is_ascii_whitespace() && bytes[i] != b'>' {
+                        i += 1;
+                    }
+                    value = Some(decode_entities(&input[start..i]));
+                }
+            }
+        }
+        if !attr_name.is_empty() {
+            attributes.push((attr_name, value));
+        }
+    }
+
+    Some(ParsedTag { name, closing, self_closing, attributes, len: i + 1 })
+}
+
+/// Returns the input after the matching close tag, or nothing if it is missing.
+fn skip_past_close<'a>(input: &'a str, name: &str) -> &'a str {
+    let lower = input.to_ascii_lowercase();
+    let needle = format!("</{}", name);
+    match lower.find(&needle) {
+        Some(pos) => input[pos..].find('>').map(|end| &input[pos + end + 1..]).unwrap_or(""),
+        None => "",
+    }
+}
+
+/// Decides whether an attribute survives. The outer `Option` is whether to
+/// keep it, the inner one whether it has a value.
+fn clean_attribute(tag: &str, name: &str, value: Option<&str>) -> Option<Option<String>> {
+    let allowed = GLOBAL_ATTRIBUTES.contains(&name)
+        || TAG_ATTRIBUTES.iter().any(|(t, attrs)| *t == tag && attrs.contains(&name))
+        || (MATHML_TAGS.contains(&tag) && MATHML_ATTRIBUTES.contains(&name));
+    if !allowed {
+        return None;
+    }
+
+    if URL_ATTRIBUTES.contains(&name) {
+        let url = value?;
+        return is_safe_url(url).then(|| Some(url.to_string()));
+    }
+    match name {
+        // Every candidate URL in a srcset must be safe on its own.
+        "srcset" => {
+            let srcset = value?;
+            srcset.split(',')
+                .all(|candidate| candidate.split_whitespace().next().is_none_or(is_safe_url))
+                .then(|| Some(srcset.to_string()))
+        }
+        "style" => {
+            let style = value?.trim().trim_end_matches(';').replace(' ', "").to_ascii_lowercase();
+            matches!(style.as_str(), "text-align:left" | "text-align:center" | "text-align:right")
+                .then(|| Some(style.replacen(':', ": ", 1)))
+        }
+        "type" => (value? == "checkbox").then(|| Some("checkbox".to_string())),
+        _ => Some(value.map(str::to_string)),
+    }
+}
+
+fn is_safe_url(url: &str) -> bool {
+    // Browsers ignore whitespace and control characters inside a scheme.
+    let compact: String = url.chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    match compact.find(':') {
+        None => true,
+        Some(colon) => {
+            let scheme = &compact[..colon];
+            // A ':' after a path, query or fragment delimiter is not a scheme.
+            scheme.contains(['/', '?', '#']) || matches!(scheme, "http" | "https" | "mailto")
+        }
+    }
+}
+
+/// Decodes the character references that can be used to disguise a URL
+/// scheme, so `&#106;avascript:` is checked as `javascript:`.
+fn decode_entities(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(pos) = rest.find('&') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos..];
+        let end = rest.find(';').filter(|&end| end <= 10);
+        let decoded = end.and_then(|end| {
+            let entity = &rest[1..end];
+            match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "colon" => Some(':'),
+                "tab" => Some('\t'),
+                "newline" => Some('\n'),
+                _ => {
+                    let number = entity.strip_prefix('#')?;
+                    let code = match number.strip_prefix(['x', 'X']) {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                        None => number.parse().ok()?,
+                    };
+                    char::from_u32(code)
+                }
+            }
+        });
+        match (decoded, end) {
+            (Some(ch), Some(end)) => {
+                out.push(ch);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_and_frames_are_dropped_with_their_content() {
+        let html = sanitize_html("<p>a</p><script>alert(1)</script><iframe src=\"https://evil.example\">x</iframe><p>b</p>");
+        assert_eq!(html, "<p>a</p><p>b</p>");
+        let html = sanitize_html("<SCRIPT type=\"text/javascript\">alert(1)</SCRIPT >ok");
+        assert_eq!(html, "ok");
+        assert!(!sanitize_html("<style>body{}</style><object data=x></object>").contains("body{}"));
+    }
+
+    #[test]
+    fn event_handlers_are_stripped() {
+        let html = sanitize_html("<img src=\"a.png\" onerror=\"alert(1)\" alt=\"a\"><p onclick='alert(1)' ONMOUSEOVER=alert(1)>x</p>");
+        assert!(!html.to_ascii_lowercase().contains("onerror"), "{}", html);
+        assert!(!html.to_ascii_lowercase().contains("onclick"), "{}", html);
+        assert!(!html.to_ascii_lowercase().contains("onmouseover"), "{}", html);
+        assert!(html.contains("src=\"a.png\""));
+        assert!(html.contains("<p>x</p>"));
+    }
+
+    #[test]
+    fn dangerous_url_schemes_are_removed() {
+        for url in [
+            "javascript:alert(1)",
+            "JaVaScRiPt:alert(1)",
+            "&#106;avascript:alert(1)",
+            "&#x6A;avascript:alert(1)",
+            "&#106&#97;vascript:alert(1)",
+            "java\tscript:alert(1)",
+            "java&#x09;script:alert(1)",
+            " \n javascript:alert(1)",
+            "vbscript:msgbox(1)",
+            "data:text/html;base64,PHNjcmlwdD4=",
+            "javascript&colon;alert(1)",
+        ] {
+            for tag in ["<a href=\"{}\">x</a>", "<img src=\"{}\">", "<img srcset=\"a.png 1x, {} 2x\">"] {
+                let input = tag.replace("{}", url);
+                let html = sanitize_html(&input);
+                let lower = html.to_ascii_lowercase();
+                // Whatever is left must not decode to a scheme in the browser:
+                // no scheme survives, and no character reference is left unescaped.
+                let survived = ["javascript:", "vbscript:", "data:"].iter().any(|s| lower.contains(s));
+                assert!(!survived && !lower.contains("&#"), "{} became {}", input, html);
+            }
+        }
+    }
+
+    #[test]
+    fn safe_urls_are_kept() {
+        assert_eq!(sanitize_html("<a href=\"https://example.com/a?b=c#d\">x</a>"), "<a href=\"https://example.com/a?b=c#d\">x</a>");
+        assert_eq!(sanitize_html("<a href=\"/blogs/post\">x</a>"), "<a href=\"/blogs/post\">x</a>");
+        assert_eq!(sanitize_html("<a href=\"mailto:me@example.com\">x</a>"), "<a href=\"mailto:me@example.com\">x</a>");
+        assert!(sanitize_html("<img srcset=\"a.png?w=320 320w, a.png 640w\">").contains("srcset=\"a.png?w=320 320w, a.png 640w\""));
+    }
+}
@@ -0,0 +1,407 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use pulldown_cmark::{Event, Parser, Options, Tag};
+use serde::Serialize;
+
+use crate::blog::BlogPost;
+use crate::blog_store::BlogStore;
+use crate::date::Date;
+use crate::escape::escape_html;
+
+// BM25 tuning constants (the usual defaults).
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+// A term in a title counts as much as three in the body, a heading as two.
+const TITLE_WEIGHT: f64 = 3.0;
+const HEADING_WEIGHT: f64 = 2.0;
+const BODY_WEIGHT: f64 = 1.0;
+
+const SNIPPET_CHARS: usize = 160;
+
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "but", "by", "for", "if", "in", "into", "is", "it",
+    "of", "on", "or", "so", "that", "the", "their", "then", "there", "these", "this", "to", "was",
+    "will", "with",
+];
+
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub slug: String,
+    pub title: String,
+    pub url: String,
+    /// HTML-escaped excerpt with matching words wrapped in `<mark>`.
+    pub snippet: String,
+    pub score: f64,
+}
+
+#[derive(Debug)]
+struct Document {
+    slug: String,
+    title: String,
+    /// Plain text of the post body, used for snippets.
+    text: String,
+    length: f64,
+}
+
+#[derive(Debug)]
+struct Posting {
+    doc: usize,
+    weighted_tf: f64,
+}
+
+/// Inverted index over blog titles, headings and body text, ranked with BM25.
+#[derive(Debug)]
+pub struct SearchIndex {
+    generation: u64,
+    /// Day the index was built for; scheduled posts join it once their date
+    /// is reached.
+    day: Date,
+    docs: Vec<Document>,
+    postings: HashMap<String, Vec<Posting>>,
+    avg_length: f64,
+}
+
+impl SearchIndex {
+    pub fn build<P: AsRef<BlogPost>>(posts: &[P], generation: u64, day: Date) -> SearchIndex {
+        let mut docs = Vec::with_capacity(posts.len());
+        let mut postings: HashMap<String, Vec<Posting>> = HashMap::new();
+
+        for (doc_id, post) in posts.iter().enumerate() {
+            let post = post.as_ref();
+            let (headings, text) = extract_text(&post.body);
+
+            let mut weights: HashMap<String, f64> = HashMap::new();
+            let mut length = 0.0;
+            for (field, weight) in [(&post.title, TITLE_WEIGHT), (&headings, HEADING_WEIGHT), (&text, BODY_WEIGHT)] {
+                for token in terms(field) {
+                    *weights.entry(token).or_insert(0.0) += weight;
+                    length += weight;
+                }
+            }
+            for (term, weighted_tf) in weights {
+                postings.entry(term).or_default().push(Posting { doc: doc_id, weighted_tf });
+            }
+
+            docs.push(Document {
+                slug: post.slug.clone(),
+                title: post.title.clone(),
+                text,
+                length,
+            });
+        }
+
+        let avg_length = if docs.is_empty() {
+            0.0
+        } else {
+            docs.iter().map(|d| d.length).sum::<f64>() / docs.len() as f64
+        };
+
+        SearchIndex { generation, day, docs, postings, avg_length }
+    }
+
+    /// Ranks posts against `query`; hit URLs are `url_prefix/<slug>`.
+    pub fn search(&self, query: &str, limit: usize, url_prefix: &str) -> Vec<SearchHit> {
+        let mut query_terms = terms(query);
+        query_terms.sort();
+        query_terms.dedup();
+
+        let n = self.docs.len() as f64;
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+        for term in &query_terms {
+            let Some(list) = self.postings.get(term) else { continue };
+            let df = list.len() as f64;
+            let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+            for posting in list {
+                let doc = &self.docs[posting.doc];
+                let norm = 1.0 - B + B * doc.length / self.avg_length.max(1.0);
+                let tf = posting.weighted_tf;
+                *scores.entry(posting.doc).or_insert(0.0) += idf * tf * (K1 + 1.0) / (tf + K1 * norm);
+            }
+        }
+
+        let mut ranked: Vec<(usize, f64)> = scores.into_iter().collect();
+        ranked.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| self.docs[a.0].slug.cmp(&self.docs[b.0].slug))
+        });
+
+        ranked.into_iter()
+            .take(limit)
+            .map(|(doc_id, score)| {
+                let doc = &self.docs[doc_id];
+                SearchHit {
+                    slug: doc.slug.clone(),
+                    title: doc.title.clone(),
+                    url: format!("{}/{}", url_prefix, doc.slug),
+                    snippet: snippet(&doc.text, &query_terms),
+                    score: (score * 1000.0).round() / 1000.0,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Keeps a `SearchIndex` of the published posts in step with a `BlogStore`,
+/// rebuilding it whenever the store reports that posts were added, changed or
+/// removed, and at the start of each day for newly due scheduled posts.
+#[derive(Debug, Default)]
+pub struct Searcher {
+    index: Mutex<Option<Arc<SearchIndex>>>,
+}
+
+impl Searcher {
+    pub fn index(&self, store: &BlogStore) -> Result<Arc<SearchIndex>, String> {
+        // Listing the posts refreshes the store, which updates its generation.
+        let today = Date::today();
+        let posts = store.published(today)?;
+        let generation = store.generation();
+
+        let mut guard = self.index.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(index) = guard.as_ref()
+            && index.generation == generation
+            && index.day == today {
+            return Ok(index.clone());
+        }
+        let index = Arc::new(SearchIndex::build(&posts, generation, today));
+        *guard = Some(index.clone());
+        Ok(index)
+    }
+}
+
+/// Splits markdown into heading text and body text, dropping markup.
+pub fn extract_text(markdown: &str) -> (String, String) {
+    let mut headings = String::new();
+    let mut body = String::new();
+    let mut in_heading = false;
+    for event in Parser::new_ext(markdown, Options::all()) {
+        match event {
+            Event::Start(Tag::Heading(..)) => in_heading = true,
+            Event::End(Tag::Heading(..)) => {
+                in_heading = false;
+                headings.push('\n');
+            }
+            Event::Text(text) | Event::Code(text) => {
+                let target = if in_heading { &mut headings } else { &mut body };
+                target.push_str(&text);
+            }
+            Event::SoftBreak | Event::HardBreak => body.push(' '),
+            Event::End(Tag::Paragraph) | Event::End(Tag::Item) | Event::End(Tag::CodeBlock(_)) => body.push(' '),
+            _ => {}
+        }
+    }
+    (headings, body)
+}
+
+/// Lowercased, stemmed index terms with stop words removed.
+pub fn terms(text: &str) -> Vec<String> {
+    words(text)
+        .into_iter()
+        .filter_map(|(start, end)| normalize(&text[start..end]))
+        .collect()
+}
+
+fn normalize(word: &str) -> Option<String> {
+    let lower = word.to_lowercase();
+    if STOP_WORDS.contains(&lower.as_str()) {
+        return None;
+    }
+    Some(stem(&lower))
+}
+
+/// Byte ranges of alphanumeric runs in `text`.
+fn words(text: &str) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut start = None;
+    for (idx, ch) in text.char_indices() {
+        if ch.is_alphanumeric() {
+            start.get_or_insert(idx);
+        } else if let Some(s) = start.take() {
+            out.push((s, idx));
+        }
+    }
+    if let Some(s) = start {
+        out.push((s, text.len()));
+    }
+    out
+}
+
+/// A light suffix-stripping stemmer covering the common English inflections
+/// (plurals, -ed, -ing, -ly and a few derivational endings). It only needs to
+/// map related words onto the same key, not produce real words.
+fn stem(word: &str) -> String {
+    if word.len() <= 3 || !word.is_ascii() {
+        return word.to_string();
+    }
+    let mut w = word.to_string();
+
+    // Plurals.
+    if let Some(base) = w.strip_suffix("sses") {
+        w = format!("{}ss", base);
+    } else if let Some(base) = w.strip_suffix("ies") {
+        w = format!("{}y", base);
+    } else if w.ends_with('s') && !w.ends_with("ss") && !w.ends_with("us") && !w.ends_with("is") {
+        w.pop();
+    }
+
+    // Past tense and progressive forms, only when a vowel remains in the stem.
+    for suffix in ["ing", "ed"] {
+        if let Some(base) = w.strip_suffix(suffix)
+            && base.len() >= 3 && has_vowel(base) {
+            w = base.to_string();
+            // "running" -> "runn" -> "run"
+            let bytes = w.as_bytes();
+            let n = bytes.len();
+            if n >= 2 && bytes[n - 1] == bytes[n - 2] && !matches!(bytes[n - 1], b'l' | b's' | b'z') {
+                w.pop();
+            }
+            break;
+        }
+    }
+
+    for (suffix, replacement) in [
+        ("ational", "ate"),
+        ("ization", "ize"),
+        ("fulness", "ful"),
+        ("iveness", "ive"),
+        ("ousness", "ous"),
+        ("ations", "ate"),
+        ("ation", "ate"),
+        ("ments", ""),
+        ("ment", ""),
+        ("ness", ""),
+        ("ally", "al"),
+        ("ly", ""),
+    ] {
+        if let Some(base) = w.strip_suffix(suffix)
+            && base.len() >= 3 {
+            w = format!("{}{}", base, replacement);
+            break;
+        }
+    }
+
+    if w.len() > 3 && w.ends_with('e') {
+        w.pop();
+    }
+    w
+}
+
+fn has_vowel(s: &str) -> bool {
+    s.chars().any(|c| matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y'))
+}
+
+/// Picks a window of `text` around the first matching word and highlights
+/// every matching word inside it.
+fn snippet(text: &str, query_terms: &[String]) -> String {
+    let words = words(text);
+    let is_match = |&(s, e): &(usize, usize)| {
+        normalize(&text[s..e]).is_some_and(|t| query_terms.contains(&t))
+    };
+
+    let first = words.iter().find(|w| is_match(w)).map(|w| w.0).unwrap_or(0);
+    let mut start = floor_char_boundary(text, first.saturating_sub(SNIPPET_CHARS / 3));
+    // Begin on a word boundary rather than mid-word.
+    if start > 0
+        && let Some(w) = words.iter().find(|w| w.0 >= start) {
+        start = w.0;
+    }
+    let end = floor_char_boundary(text, (start + SNIPPET_CHARS).min(text.len()));
+
+    let mut out = String::new();
+    if start > 0 {
+        out.push('…');
+    }
+    let mut cursor = start;
+    for w in words.iter().filter(|w| w.0 >= start && w.1 <= end) {
+        if is_match(w) {
+            out.push_str(&escape_html(&text[cursor..w.0]));
+            out.push_str("<mark>");
+            out.push_str(&escape_html(&text[w.0..w.1]));
+            out.push_str("</mark>");
+            cursor = w.1;
+        }
+    }
+    out.push_str(&escape_html(&text[cursor..end]));
+    if end < text.len() {
+        out.push('…');
+    }
+    out.trim().to_string()
+}
+
+fn floor_char_boundary(text: &str, mut idx: usize) -> usize {
+    while idx > 0 && !text.is_char_boundary(idx) {
+        idx -= 1;
+    }
+    idx
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_support::site;
+
+    fn post(slug: &str, title: &str, body: &str) -> BlogPost {
+        BlogPost::parse(slug, &format!("---\ntitle: {}\n---\n{}", title, body), None)
+    }
+
+    fn ranking(posts: &[BlogPost], query: &str) -> Vec<String> {
+        let index = SearchIndex::build(posts, 0, Date::today());
+        index.search(query, 10, "/blogs").into_iter().map(|hit| hit.slug).collect()
+    }
+
+    #[test]
+    fn title_and_heading_hits_rank_first() {
+        let filler = "Some words about other things. ".repeat(5);
+        let posts = [
+            post("body", "Notes", &format!("{} We mention borrowing once.", filler)),
+            post("title", "Borrowing explained", &filler),
+            post("heading", "Notes again", &format!("## Borrowing\n\n{}", filler)),
+            post("unrelated", "Cooking", &filler),
+        ];
+        assert_eq!(ranking(&posts, "borrowing"), ["title", "heading", "body"]);
+        assert!(ranking(&posts, "the of and").is_empty());
+    }
+
+    #[test]
+    fn stemming_matches_inflections() {
+        assert_eq!(terms("running runs run"), ["run", "run", "run"]);
+        assert_eq!(terms("Libraries library"), ["library", "library"]);
+        assert_eq!(terms("the cached caching"), ["cach", "cach"]);
+        let posts = [post("marathon", "Marathon", "I keep running every morning.")];
+        assert_eq!(ranking(&posts, "runs"), ["marathon"]);
+    }
+
+    #[test]
+    fn snippets_escape_around_marks() {
+        let posts = [post("xss", "Tags", "Use <script>alert(1)</script> & the `<b>` tag: Rust & <em>rust</em>.")];
+        let index = SearchIndex::build(&posts, 0, Date::today());
+        let hits = index.search("rust", 10, "/blogs");
+        assert_eq!(hits[0].url, "/blogs/xss");
+        let snippet = &hits[0].snippet;
+        assert!(!snippet.contains("<script>") && !snippet.contains("<b>") && !snippet.contains("<em>"), "{}", snippet);
+        assert!(snippet.contains("&lt;b&gt;"), "{}", snippet);
+        assert!(snippet.contains("<mark>Rust</mark> &amp; "), "{}", snippet);
+        assert!(snippet.contains("<mark>rust</mark>"), "{}", snippet);
+    }
+
+    #[test]
+    fn index_rebuilds_when_the_store_changes() {
+        let site = site("search", &[("first.md", "---\ntitle: First\n---\nAbout ferris.")], "");
+        let store = &site.server.blog_store;
+        let searcher = Searcher::default();
+
+        let index = searcher.index(store).unwrap();
+        assert!(Arc::ptr_eq(&index, &searcher.index(store).unwrap()));
+        assert_eq!(index.search("ferris", 10, "/blogs").len(), 1);
+
+        fs::write(site.dir.join("public/blogs/second.md"), "---\ntitle: Second\n---\nFerris again.").unwrap();
+        let rebuilt = searcher.index(store).unwrap();
+        assert!(!Arc::ptr_eq(&index, &rebuilt));
+        assert!(rebuilt.generation > index.generation);
+        assert_eq!(rebuilt.search("ferris", 10, "/blogs").len(), 2);
+    }
+}
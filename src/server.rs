@@ -1,14 +1,37 @@
 use std::io::{BufReader, prelude::*};
-use std::net::{TcpListener, TcpStream};
 use std::fs;
-use std::collections::HashMap;
 use std::path::Path;
-
-use crate::config::AppConfig;
-use crate::static_files::{resolve_content_type, StaticFileResolver};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::blog::{self, BlogPost, BlogQuery, PostStatus};
+use crate::blog_store::BlogStore;
+use crate::search::{SearchHit, Searcher};
+use crate::related::{self, PostNavigation, TermCache};
+use crate::sitemap;
+use crate::config::{AppConfig, BlogConfig};
+use crate::date::Date;
+use crate::error_pages;
+use crate::feed::Feed;
+use crate::highlight;
+use crate::images;
+use crate::http::{self, Response};
+use crate::meta::PageMeta;
+use crate::listener::{self, Connection, Endpoint, Listener};
+use crate::upgrade;
+use crate::static_files::{ensure_within, resolve_content_type, StaticFileResolver};
+use crate::tags;
+use crate::template::Templates;
+use crate::toc::TocEntry;
 
 use serde::{Deserialize, Serialize};
-use pulldown_cmark::{Parser, Options, html};
+
+/// How long an accept loop waits for a connection before checking whether
+/// the server is draining.
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
 pub struct Route {
     
@@ -16,16 +39,33 @@ pub struct Route {
 pub struct Server {
     pub host: String,
     pub port: String,
-    pub address: Option<String>,
-    pub listener: Option<TcpListener>,
+    pub endpoints: Vec<Endpoint>,
+    pub listeners: Vec<Listener>,
     pub config: Option<AppConfig>,
     pub resolver: Option<StaticFileResolver>,
+    /// Set once a successor process has taken over the listeners; accept
+    /// loops return when they see it.
+    pub draining: AtomicBool,
+    pub blog_store: BlogStore,
+    pub searcher: Searcher,
+    pub term_cache: TermCache,
+    pub templates: Templates,
 }
 
 impl Server {
     pub fn setup_server(&mut self) {
-        self.address = Some(format!("{}:{}", self.host, self.port));
-        // initialize resolver if config present
+        self.endpoints = match &self.config {
+            Some(cfg) if !cfg.server.listen.is_empty() => {
+                cfg.server.listen.iter().map(|spec| Endpoint::parse(spec)).collect()
+            }
+            _ => vec![Endpoint::Tcp(format!("{}:{}", self.host, self.port))],
+        };
+        self.init_resolver();
+        self.setup_listener();
+    }
+
+    /// Initializes the static file resolver if a config is present.
+    pub fn init_resolver(&mut self) {
         if let Some(cfg) = &self.config {
             match StaticFileResolver::from_config(&cfg.static_cfg) {
                 Ok(res) => {
@@ -36,56 +76,119 @@ impl Server {
                 }
             }
         }
-        self.setup_listener();
     }
 
     fn setup_listener(&mut self) {
-        match &self.address {
-            Some(address) => {
-                let listener_result = TcpListener::bind(address);
-                match listener_result {
-                    Ok(listener) => {
-                        println!("Listening to: {}", address);
-                        self.listener = Some(listener);
-                        self.listen();
-                    }
-                    Err(error) => {
-                        eprintln!("ERROR: {:?}", error);
-                    }
+        // Sockets passed in by systemd or a previous server process take
+        // precedence over binding the configured endpoints ourselves.
+        match listener::inherited_listeners() {
+            Ok(inherited) if !inherited.is_empty() => {
+                for listener in inherited {
+                    println!("Adopted inherited listener: {}", listener.describe());
+                    self.listeners.push(listener);
+                }
+                upgrade::notify_ready();
+                self.listen();
+                return;
+            }
+            Ok(_) => {}
+            Err(error) => {
+                eprintln!("ERROR adopting inherited listeners: {:?}", error);
+            }
+        }
+
+        let socket_mode = self.config.as_ref().and_then(|cfg| cfg.server.socket_mode);
+        for endpoint in &self.endpoints {
+            match Listener::bind(endpoint, socket_mode) {
+                Ok(listener) => {
+                    println!("Listening to: {}", listener.describe());
+                    self.listeners.push(listener);
+                }
+                Err(error) => {
+                    eprintln!("ERROR binding {}: {:?}", endpoint, error);
                 }
             }
-            None => {}
+        }
+        if !self.listeners.is_empty() {
+            upgrade::notify_ready();
+            self.listen();
         }
     }
 
     fn listen(&self) {
-        match &self.listener {
-            Some(listener) => {
-                for stream in listener.incoming() {
-                    match stream {
-                        Ok(tcp_stream) => {
-                            self.handle_stream(tcp_stream);
+        upgrade::install_signal_handler();
+        // Listeners may be shared with a successor process, so accept must not
+        // block when the other process takes a pending connection first.
+        for listener in &self.listeners {
+            if let Err(error) = listener.set_nonblocking(true) {
+                eprintln!("ERROR configuring {}: {:?}", listener.describe(), error);
+            }
+        }
+        // One accept loop per listener; scoped threads let them borrow `self`.
+        // The scope only ends once every loop has returned, i.e. after an
+        // upgrade, with each accepted connection answered.
+        thread::scope(|scope| {
+            scope.spawn(|| self.watch_for_upgrade());
+            for listener in &self.listeners {
+                scope.spawn(move || {
+                    while !self.draining.load(Ordering::SeqCst) {
+                        match listener.wait_readable(ACCEPT_POLL_INTERVAL) {
+                            Ok(true) => {}
+                            Ok(false) => continue,
+                            Err(error) => {
+                                eprintln!("ERROR listening on {}: {:?}", listener.describe(), error);
+                                thread::sleep(ACCEPT_POLL_INTERVAL);
+                                continue;
+                            }
                         }
-                        Err(error) => {
-                            eprintln!("ERROR listening: {:?}", error);
+                        match listener.accept() {
+                            Ok(connection) => self.handle_stream(connection),
+                            // Another process sharing the socket was faster.
+                            Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {}
+                            Err(error) => {
+                                eprintln!("ERROR listening on {}: {:?}", listener.describe(), error);
+                            }
                         }
                     }
+                });
+            }
+        });
+        println!("Connections drained, exiting");
+    }
+
+    /// On SIGUSR2, hands the listeners to a freshly exec'd copy of the binary
+    /// and stops accepting once it is ready. Connections already accepted are
+    /// still answered; pending ones stay queued on the shared sockets for the
+    /// successor, so none is refused or dropped during the switch.
+    fn watch_for_upgrade(&self) {
+        while !self.draining.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(200));
+            if !upgrade::take_upgrade_request() {
+                continue;
+            }
+            println!("Upgrade requested, starting new process");
+            match upgrade::spawn_successor(&self.listeners) {
+                Ok(pid) => {
+                    println!("New process {} is ready, draining connections", pid);
+                    self.draining.store(true, Ordering::SeqCst);
+                }
+                Err(error) => {
+                    eprintln!("ERROR upgrading, continuing to serve: {:?}", error);
                 }
             }
-            None => {}
         }
     }
 
-    fn handle_stream(&self, mut tcp_stream: TcpStream) {
+    fn handle_stream(&self, mut tcp_stream: Connection) {
         let mut http_request_lines: Vec<String> = Vec::new();
         let mut bad_request = false;
-        let mut error_response = b"HTTP/1.1 400 Bad Request\r\n\r\nInvalid Request".to_vec();
+        let mut error_message = "Invalid request";
         let mut headers_map: HashMap<String, String> = HashMap::new();
         let mut request_body: Option<Vec<u8>> = None;
 
         // Read headers and body using BufReader
         {
-            let mut buf_reader = BufReader::new(&tcp_stream);
+            let mut buf_reader = BufReader::new(&mut tcp_stream);
             loop {
                 let mut line_buf: Vec<u8> = Vec::new();
                 
@@ -126,6 +229,7 @@ impl Server {
                                     break;
                                 }
                                 // Parse headers (skip request line)
+                                #[allow(clippy::collapsible_if)]
                                 if !http_request_lines.is_empty() {
                                     if let Some(colon_pos) = trimmed_line.find(':') {
                                         let key = trimmed_line[..colon_pos].trim().to_lowercase();
@@ -138,8 +242,7 @@ impl Server {
                             Err(e) => {
                                 // Handle non-UTF-8 data (e.g., HTTPS handshake)
                                 eprintln!("ERROR: Invalid UTF-8 in request header: {:?}", e);
-                                error_response =
-                                    b"HTTP/1.1 400 Bad Request\r\n\r\nInvalid UTF-8 in request".to_vec();
+                                error_message = "Invalid UTF-8 in request";
                                 bad_request = true;
                                 break;
                             }
@@ -154,15 +257,25 @@ impl Server {
             }
         } // `buf_reader` is dropped here
 
+        let accept = headers_map.get("accept").map(String::as_str);
+        // Errors are rendered for the requested route when the request line
+        // got that far, so malformed API requests still get JSON.
+        let error_route = http_request_lines.first()
+            .and_then(|line| line.split(' ').nth(1))
+            .map(|target| http::split_target(target).0)
+            .unwrap_or("/");
+
         // Handle any errors found during reading
         if bad_request {
-            tcp_stream.write_all(&error_response).ok(); // Ignore write errors
+            let response = self.error_response(Response::error(400, error_message), error_route, accept);
+            tcp_stream.write_all(&response.to_bytes()).ok(); // Ignore write errors
             return;
         }
 
         if http_request_lines.is_empty() {
             eprintln!("Received empty request");
-            tcp_stream.write_all(&error_response).ok();
+            let response = self.error_response(Response::error(400, error_message), error_route, accept);
+            tcp_stream.write_all(&response.to_bytes()).ok();
             return;
         }
 
@@ -172,181 +285,352 @@ impl Server {
         // Add a check to prevent panic on malformed header
         if http_header.len() < 2 {
             eprintln!("Malformed request line: {}", http_request_lines[0]);
-            let response = b"HTTP/1.1 400 Bad Request\r\n\r\nMalformed request line";
-            tcp_stream.write_all(response).ok();
+            let response = self.error_response(Response::error(400, "Malformed request line"), error_route, accept);
+            tcp_stream.write_all(&response.to_bytes()).ok();
             return;
         }
 
         let method = http_header[0];
-        let route = http_header[1];
-        println!("METHOD: {}, ROUTE: {}", method, route);
+        println!("METHOD: {}, ROUTE: {}", method, http_header[1]);
         // --- End of request parsing ---
 
-        // Handle /api/blogs route
-        if route == "/api/blogs" && method == "GET" {
-            match self.handle_blogs_list_api() {
-                Ok(json_response) => {
-                    let response_body = json_response.as_bytes();
-                    let headers = format!(
-                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\n\r\n",
-                        response_body.len()
-                    );
-                    let mut response = headers.into_bytes();
-                    response.extend_from_slice(response_body);
-                    tcp_stream.write_all(&response).ok();
-                    return;
+        let response = self.dispatch(method, http_header[1], accept, request_body.as_deref());
+
+        // Write the final response back to the stream
+        if let Err(error) = tcp_stream.write_all(&response.to_bytes()) {
+            eprintln!("ERROR writing response: {:?}", error);
+        }
+    }
+
+    /// Routes a request to its handler. `target` is the request target from
+    /// the request line, query string included, and `accept` its `Accept`
+    /// header, which decides how errors are rendered. Used both for live
+    /// requests and by the static exporter.
+    pub fn dispatch(&self, method: &str, target: &str, accept: Option<&str>, request_body: Option<&[u8]>) -> Response {
+        let (route, _) = http::split_target(target);
+        let response = self.route(method, target, request_body);
+        self.error_response(response, route, accept)
+    }
+
+    /// Renders an error returned by a handler: as JSON for API routes and
+    /// clients that only accept JSON, as the configured error page or the
+    /// built-in one otherwise. Other responses pass through unchanged.
+    fn error_response(&self, response: Response, route: &str, accept: Option<&str>) -> Response {
+        if response.error.is_none() {
+            return response;
+        }
+        let api_prefix = self.config.as_ref().map(|cfg| cfg.blog.api_prefix.as_str()).unwrap_or("/api");
+        let is_api = [api_prefix, "/api"].iter()
+            .any(|prefix| route == *prefix || route.starts_with(&format!("{}/", prefix)));
+        if is_api || error_pages::wants_json(accept) {
+            return error_pages::json(response);
+        }
+        let page = self.config.as_ref()
+            .and_then(|cfg| {
+                let file = cfg.error_pages.get(&response.status)?;
+                let root = Path::new(&cfg.static_cfg.root_dir);
+                match ensure_within(root, &root.join(file.trim_start_matches('/'))) {
+                    Ok(path) => Some(path),
+                    Err(e) => {
+                        eprintln!("Error page {} for status {} not usable: {:?}", file, response.status, e);
+                        None
+                    }
                 }
+            });
+        error_pages::html(response, page.as_deref())
+    }
+
+    fn route(&self, method: &str, target: &str, request_body: Option<&[u8]>) -> Response {
+        let (route, query) = http::split_target(target);
+
+        // Blog routes are only served while the blog is enabled in the config
+        let blog_cfg = self.config.as_ref().map(|cfg| &cfg.blog).filter(|blog| blog.enabled);
+
+        // Known routes asked with another method than theirs get a 405
+        // instead of falling through to the static files
+        if let Some(allowed) = self.allowed_method(blog_cfg, route)
+            && method != allowed {
+            return Response::error(405, format!("This route only accepts {} requests.", allowed))
+                .with_header("Allow", allowed);
+        }
+
+        // Handle /api/blogs route
+        if let Some(blog) = blog_cfg
+            && route == blog.api_path("blogs") && method == "GET" {
+            let params = http::parse_query(query);
+            let list_query = match BlogQuery::from_params(&params, blog.per_page) {
+                Ok(list_query) => list_query,
+                Err(e) => return Response::error(400, e).with_header("Access-Control-Allow-Origin", "*"),
+            };
+            return match self.handle_blogs_list_api(blog, &params, &list_query) {
+                Ok(json_response) => Response::json(200, json_response)
+                    .with_header("Access-Control-Allow-Origin", "*"),
                 Err(e) => {
                     eprintln!("Error handling blogs list API: {:?}", e);
-                    let error_response = format!(
-                        "HTTP/1.1 500 Internal Server Error\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{{\"error\":\"{}\"}}",
-                        e.len(),
-                        e
-                    );
-                    tcp_stream.write_all(error_response.as_bytes()).ok();
-                    return;
+                    Response::error(500, e)
+                }
+            };
+        }
+
+        // Handle /api/search route
+        if let Some(blog) = blog_cfg
+            && route == blog.api_path("search") && method == "GET" {
+            return match self.handle_search_api(blog, &http::parse_query(query)) {
+                Ok(json_response) => Response::json(200, json_response)
+                    .with_header("Access-Control-Allow-Origin", "*"),
+                Err(e) => {
+                    eprintln!("Error handling search API: {:?}", e);
+                    Response::error(500, e)
+                }
+            };
+        }
+
+        // Handle /feed.xml, /atom.xml and /feed.json routes
+        if let Some(blog) = blog_cfg
+            && matches!(route, "/feed.xml" | "/atom.xml" | "/feed.json") && method == "GET" {
+            return match self.handle_feed(blog, route) {
+                Ok((content_type, body)) => Response::new(200, content_type, body),
+                Err(e) => {
+                    eprintln!("Error generating feed: {:?}", e);
+                    Response::error(500, "Failed to generate feed")
+                }
+            };
+        }
+
+        // Handle /highlight.css route - code block theme, `?theme=` overrides the configured one
+        if route == "/highlight.css" && method == "GET" {
+            let params = http::parse_query(query);
+            let configured = self.config.as_ref().map(|cfg| cfg.highlight.theme.as_str()).unwrap_or("light");
+            let theme = params.get("theme").map(String::as_str).unwrap_or(configured);
+            return match highlight::theme_css(theme) {
+                Some(css) => Response::new(200, "text/css; charset=utf-8", css),
+                None => Response::error(404, format!("Unknown theme: {}", theme)),
+            };
+        }
+
+        // Handle /sitemap.xml and /robots.txt routes
+        if matches!(route, "/sitemap.xml" | "/robots.txt") && method == "GET" {
+            return match self.handle_crawler_file(route) {
+                Ok((content_type, body)) => Response::new(200, content_type, body),
+                Err(e) => {
+                    eprintln!("Error generating {}: {:?}", route, e);
+                    Response::error(500, format!("Failed to generate {}", route))
                 }
+            };
+        }
+
+        // Handle /api/blogs/stats route - blog cache counters, only for
+        // requests that may preview posts as they describe the server itself
+        if let Some(blog) = blog_cfg
+            && route == blog.api_path("blogs/stats") && method == "GET" {
+            if !blog.preview_allowed(http::parse_query(query).get("preview").map(String::as_str)) {
+                return Response::error(404, "Not found");
             }
+            let body = serde_json::to_string(&self.blog_store.stats()).unwrap_or_default();
+            return Response::json(200, body);
         }
 
-        // Handle /api/blog/:slug route
-        if route.starts_with("/api/blog/") && method == "GET" {
-            let slug = route.strip_prefix("/api/blog/").unwrap_or("");
-            match self.handle_blog_post_api(slug) {
-                Ok(json_response) => {
-                    let response_body = json_response.as_bytes();
-                    let headers = format!(
-                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\n\r\n",
-                        response_body.len()
-                    );
-                    let mut response = headers.into_bytes();
-                    response.extend_from_slice(response_body);
-                    tcp_stream.write_all(&response).ok();
-                    return;
+        // Handle /api/tags and /api/tags/:tag routes
+        if let Some(blog) = blog_cfg
+            && route == blog.api_path("tags") && method == "GET" {
+            return match self.handle_tags_api(blog) {
+                Ok(body) => Response::json(200, body),
+                Err(e) => {
+                    eprintln!("Error handling tags API: {:?}", e);
+                    Response::error(500, e)
                 }
+            };
+        }
+        if let Some(blog) = blog_cfg
+            && let Some(segment) = route.strip_prefix(blog.api_path("tags/").as_str())
+            && method == "GET" {
+            return match self.handle_tag_api(blog, segment) {
+                Ok(Some(body)) => Response::json(200, body),
+                Ok(None) => Response::error(404, "Tag not found"),
+                Err(e) => {
+                    eprintln!("Error handling tag API: {:?}", e);
+                    Response::error(500, e)
+                }
+            };
+        }
+
+        // Handle /api/blog/:slug route
+        if let Some(blog) = blog_cfg
+            && let Some(segment) = route.strip_prefix(blog.api_path("blog/").as_str())
+            && method == "GET" {
+            let Some(slug) = blog::parse_slug(segment) else {
+                return Response::error(404, "Blog post not found");
+            };
+            let preview = blog.preview_allowed(http::parse_query(query).get("preview").map(String::as_str));
+            return match self.handle_blog_post_api(blog, &slug, preview) {
+                Ok(Some(json_response)) => Response::json(200, json_response)
+                    .with_header("Access-Control-Allow-Origin", "*"),
+                Ok(None) => Response::error(404, "Blog post not found")
+                    .with_header("Access-Control-Allow-Origin", "*"),
                 Err(e) => {
                     eprintln!("Error handling blog post API: {:?}", e);
-                    let error_response = format!(
-                        "HTTP/1.1 500 Internal Server Error\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{{\"error\":\"{}\"}}",
-                        e.len(),
-                        e
-                    );
-                    tcp_stream.write_all(error_response.as_bytes()).ok();
-                    return;
+                    Response::error(500, e)
                 }
-            }
+            };
+        }
+
+        // Handle /blogs/tags/:tag route - posts with a tag as an HTML page
+        if let Some(blog) = blog_cfg
+            && let Some(segment) = route.strip_prefix(format!("{}/tags/", blog.page_prefix).as_str())
+            && method == "GET" {
+            return match self.handle_tag_page(blog, segment) {
+                Ok(Some(html)) => Response::new(200, "text/html", html),
+                Ok(None) => Response::error(404, "No posts carry this tag."),
+                Err(e) => {
+                    eprintln!("Error handling tag page: {:?}", e);
+                    Response::error(500, e)
+                }
+            };
         }
 
         // Handle /blogs/:slug route - serve individual blog posts as HTML pages
-        if route.starts_with("/blogs/") && method == "GET" {
-            let slug = route.strip_prefix("/blogs/").unwrap_or("");
+        if let Some(blog) = blog_cfg
+            && let Some(slug) = route.strip_prefix(format!("{}/", blog.page_prefix).as_str())
+            && method == "GET"
             // Don't treat /blogs as a slug (it should be handled by static file resolver)
-            if !slug.is_empty() {
-                match self.handle_blog_post_page(slug) {
-                    Ok(html_response) => {
-                        let response_body = html_response.as_bytes();
-                        let headers = format!(
-                            "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n",
-                            response_body.len()
-                        );
-                        let mut response = headers.into_bytes();
-                        response.extend_from_slice(response_body);
-                        tcp_stream.write_all(&response).ok();
-                        return;
-                    }
-                    Err(e) => {
-                        eprintln!("Error handling blog post page: {:?}", e);
-                        let error_response = format!(
-                            "HTTP/1.1 404 Not Found\r\nContent-Type: text/html\r\nContent-Length: {}\r\n\r\n<html><body><h1>Blog Post Not Found</h1><p>{}</p></body></html>",
-                            e.len() + 50,
-                            e
-                        );
-                        tcp_stream.write_all(error_response.as_bytes()).ok();
-                        return;
-                    }
+            && !slug.is_empty()
+            // Nor files stored next to the posts, such as images
+            && (blog::parse_slug(slug).is_some() || self.static_file(route).is_none()) {
+            let Some(slug) = blog::parse_slug(slug) else {
+                return Response::error(404, "This blog post does not exist.");
+            };
+            let preview = blog.preview_allowed(http::parse_query(query).get("preview").map(String::as_str));
+            return match self.handle_blog_post_page(blog, &slug, preview) {
+                Ok(Some(html_response)) => Response::new(200, "text/html", html_response),
+                Ok(None) => Response::error(404, "This blog post does not exist."),
+                Err(e) => {
+                    eprintln!("Error handling blog post page: {:?}", e);
+                    Response::error(500, e)
                 }
-            }
+            };
         }
 
         // Handle /api/chat route
         if route == "/api/chat" && method == "POST" {
-            if let Some(body) = request_body {
-                println!("Content Length: {}", body.len());
-                println!("Body: {:?}", body);
-                match self.handle_chat_api(&body) {
-                    Ok(json_response) => {
-                        let response_body = json_response.as_bytes();
-                        let headers = format!(
-                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\n\r\n",
-                            response_body.len()
-                        );
-                        let mut response = headers.into_bytes();
-                        response.extend_from_slice(response_body);
-                        tcp_stream.write_all(&response).ok();
-                        return;
-                    }
-                    Err(e) => {
-                        eprintln!("Error handling chat API: {:?}", e);
-                        let error_response = format!(
-                            "HTTP/1.1 500 Internal Server Error\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{{\"error\":\"{}\"}}",
-                            e.len(),
-                            e
-                        );
-                        tcp_stream.write_all(error_response.as_bytes()).ok();
-                        return;
-                    }
+            let Some(body) = request_body else {
+                return Response::error(400, "Missing request body");
+            };
+            println!("Content Length: {}", body.len());
+            println!("Body: {:?}", body);
+            return match self.handle_chat_api(body) {
+                Ok(json_response) => Response::json(200, json_response)
+                    .with_header("Access-Control-Allow-Origin", "*"),
+                Err(e) => {
+                    eprintln!("Error handling chat API: {:?}", e);
+                    Response::error(500, e)
                 }
-            } else {
-                let response = b"HTTP/1.1 400 Bad Request\r\n\r\nMissing request body";
-                tcp_stream.write_all(response).ok();
-                return;
-            }
+            };
         }
 
         // --- Start of your original file-serving logic ---
-        
-        // Default response
-        let mut response: Vec<u8> =
-            b"HTTP/1.1 404 ERROR\r\nContent-Length: 13\r\n\r\nFile Not Found".to_vec();
 
+        #[allow(clippy::collapsible_if)]
         if let Some(cfg) = &self.config {
             if let Some(resolver) = &self.resolver {
                 match resolver.resolve(route) {
-                    Ok(path) => {
-                        match fs::read(&path) {
-                            Ok(bytes) => {
-                                // File found, build 200 OK response
-                                let content_type = resolve_content_type(&path, &cfg.content_types);
-                                let headers = format!(
-                                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Type: {}\r\n\r\n",
-                                    bytes.len(),
-                                    content_type
-                                );
-                                let mut buf = headers.into_bytes();
-                                buf.extend_from_slice(&bytes);
-                                response = buf;
-                            }
-                            Err(_e) => {
-                                // File read error, default 404 response is already set
-                                eprintln!("File not found or unreadable: {:?}", path);
-                            }
-                        }
+                    Ok(path) if blog::is_post_source(&path, &cfg.blog.content_dir) => {
+                        eprintln!("Refusing to serve post source: {}", route);
                     }
+                    Ok(path) if cfg.images.enabled && images::is_raster(&path) && path.is_file() => {
+                        return self.handle_image(&path, &http::parse_query(query));
+                    }
+                    Ok(path) => match fs::read(&path) {
+                        Ok(bytes) => {
+                            let content_type = resolve_content_type(&path, &cfg.content_types);
+                            return Response::new(200, &content_type, bytes);
+                        }
+                        Err(_e) => {
+                            eprintln!("File not found or unreadable: {:?}", path);
+                        }
+                    },
                     Err(_e) => {
-                        // Route not resolved, default 404 response is already set
                         eprintln!("Route not resolved: {}", route);
                     }
                 }
             }
         }
 
-        // Write the final response back to the stream
-        match tcp_stream.write_all(&response) {
-            Ok(_result) => {}
-            Err(error) => {
-                eprintln!("ERROR writing response: {:?}", error)
+        Response::error(404, "The page you are looking for does not exist.")
+    }
+
+    /// The method a route handled by `route` answers, `None` for routes left
+    /// to the static files.
+    fn allowed_method(&self, blog_cfg: Option<&BlogConfig>, route: &str) -> Option<&'static str> {
+        if route == "/api/chat" {
+            return Some("POST");
+        }
+        if matches!(route, "/highlight.css" | "/sitemap.xml" | "/robots.txt") {
+            return Some("GET");
+        }
+        let blog = blog_cfg?;
+        let api_route = ["blogs", "search", "blogs/stats", "tags"].iter()
+            .any(|path| route == blog.api_path(path))
+            || [blog.api_path("tags/"), blog.api_path("blog/"), format!("{}/tags/", blog.page_prefix)].iter()
+                .any(|prefix| route.starts_with(prefix.as_str()));
+        let post_page = route.strip_prefix(format!("{}/", blog.page_prefix).as_str())
+            .and_then(blog::parse_slug)
+            .is_some();
+        let generated = api_route || post_page || matches!(route, "/feed.xml" | "/atom.xml" | "/feed.json");
+        generated.then_some("GET")
+    }
+
+    /// The file under the static root serving `route`, if there is one.
+    fn static_file(&self, route: &str) -> Option<std::path::PathBuf> {
+        let path = self.resolver.as_ref()?.resolve(route).ok()?;
+        let content_dir = &self.config.as_ref()?.blog.content_dir;
+        (path.is_file() && !blog::is_post_source(&path, content_dir)).then_some(path)
+    }
+
+    /// Serves a static image, resized or converted when the query asks for
+    /// it with `w` and `format`.
+    fn handle_image(&self, path: &Path, params: &HashMap<String, String>) -> Response {
+        let Some(cfg) = &self.config else {
+            return Response::error(500, "Server configuration missing");
+        };
+        let transform = match images::Transform::from_query(params, &cfg.images) {
+            Ok(transform) => transform,
+            Err(e) => return Response::error(400, e),
+        };
+        let result = match transform {
+            Some(transform) => images::transform(path, transform, &cfg.images)
+                .map(|(bytes, content_type)| (bytes, content_type.to_string())),
+            None => fs::read(path)
+                .map(|bytes| (bytes, resolve_content_type(path, &cfg.content_types)))
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e)),
+        };
+        match result {
+            Ok((bytes, content_type)) => Response::new(200, &content_type, bytes),
+            Err(e) => {
+                eprintln!("Error serving image: {}", e);
+                Response::error(500, "Failed to process image")
             }
-        };        
+        }
+    }
+
+    fn handle_feed(&self, blog: &BlogConfig, route: &str) -> Result<(&'static str, String), String> {
+        let cfg = self.config.as_ref().ok_or("Server configuration missing")?;
+        let feed = Feed::new(self.blog_store.published(Date::today())?, &cfg.site, blog, &cfg.feed);
+        match route {
+            "/feed.xml" => Ok(("application/rss+xml; charset=utf-8", feed.to_rss())),
+            "/atom.xml" => Ok(("application/atom+xml; charset=utf-8", feed.to_atom())),
+            _ => Ok(("application/feed+json; charset=utf-8", feed.to_json()?)),
+        }
+    }
+
+    fn handle_crawler_file(&self, route: &str) -> Result<(&'static str, String), String> {
+        let cfg = self.config.as_ref().ok_or("Server configuration missing")?;
+        if route == "/robots.txt" {
+            return Ok(("text/plain; charset=utf-8", sitemap::robots_txt(&cfg.robots, &cfg.site)));
+        }
+        let resolver = self.resolver.as_ref().ok_or("Static file resolver not initialized")?;
+        let posts = if cfg.blog.enabled { self.blog_store.published(Date::today())? } else { Vec::new() };
+        let entries = sitemap::collect_entries(resolver, &posts, &cfg.blog, &cfg.robots);
+        Ok(("application/xml; charset=utf-8", sitemap::sitemap_xml(&entries, &cfg.site)))
     }
 
     fn handle_chat_api(&self, body: &[u8]) -> Result<String, String> {
@@ -526,270 +810,227 @@ impl Server {
         trimmed.to_string()
     }
 
-    fn handle_blogs_list_api(&self) -> Result<String, String> {
-        let blogs_dir = Path::new("public/blogs");
-        
-        if !blogs_dir.exists() {
-            return Ok(r#"{"blogs":[]}"#.to_string());
-        }
-
-        let entries = fs::read_dir(blogs_dir)
-            .map_err(|e| format!("Failed to read blogs directory: {}", e))?;
-
-        let mut blogs: Vec<BlogMetadata> = Vec::new();
-
-        for entry in entries {
-            let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
-            let path = entry.path();
-            
-            if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("md") {
-                if let Some(file_name) = path.file_stem().and_then(|s| s.to_str()) {
-                    let slug = file_name.to_string();
-                    let content = fs::read_to_string(&path)
-                        .map_err(|e| format!("Failed to read blog file: {}", e))?;
-                    
-                    // Extract title and date from markdown (first line is usually title)
-                    let title = content.lines()
-                        .next()
-                        .unwrap_or(&slug)
-                        .trim_start_matches('#')
-                        .trim()
-                        .to_string();
-                    
-                    // Try to extract date from content (look for **Published:** pattern)
-                    let published_date = content.lines()
-                        .find(|line| line.contains("**Published:**") || line.contains("Published:"))
-                        .and_then(|line| {
-                            line.split("Published:").nth(1)
-                                .or_else(|| line.split("**Published:**").nth(1))
-                                .map(|s| s.trim().trim_matches('*').trim().to_string())
-                        })
-                        .unwrap_or_else(|| "Unknown".to_string());
-
-                    blogs.push(BlogMetadata {
-                        slug,
-                        title,
-                        published_date,
-                    });
-                }
+    fn handle_blogs_list_api(&self, blog: &BlogConfig, params: &HashMap<String, String>, query: &BlogQuery) -> Result<String, String> {
+        let today = Date::today();
+        let preview = blog.preview_allowed(params.get("preview").map(String::as_str));
+        let mut blogs = if preview { self.blog_store.posts()? } else { self.blog_store.published(today)? };
+
+        blogs.retain(|post| query.matches(post));
+        query.sort(&mut blogs);
+
+        let total = blogs.len();
+        let total_pages = total.div_ceil(query.per_page).max(1);
+        let start = (query.page - 1).saturating_mul(query.per_page).min(total);
+        let end = (start + query.per_page).min(total);
+        let list_path = blog.api_path("blogs");
+        // Pager links carry the preview token along so later pages stay in preview.
+        let token = params.get("preview").filter(|_| preview).cloned().unwrap_or_default();
+        let page_link = |page: usize| {
+            let mut link = format!("{}?{}", list_path, query.to_query_string(page));
+            if !token.is_empty() {
+                link.push('&');
+                link.push_str(&http::build_query(&[("preview", token.clone())]));
             }
-        }
-
-        // Sort by published date (most recent first)
-        blogs.sort_by(|a, b| b.published_date.cmp(&a.published_date));
+            link
+        };
 
         #[derive(Serialize)]
-        struct BlogListResponse {
-            blogs: Vec<BlogMetadata>,
+        struct ListedPost<'a> {
+            #[serde(flatten)]
+            post: &'a BlogPost,
+            status: PostStatus,
         }
 
         #[derive(Serialize)]
-        struct BlogMetadata {
-            slug: String,
-            title: String,
-            published_date: String,
+        struct BlogListResponse<'a> {
+            blogs: Vec<ListedPost<'a>>,
+            preview: bool,
+            total: usize,
+            page: usize,
+            per_page: usize,
+            total_pages: usize,
+            next: Option<String>,
+            prev: Option<String>,
         }
 
-        let response = BlogListResponse { blogs };
+        let response = BlogListResponse {
+            blogs: blogs[start..end].iter()
+                .map(|post| ListedPost { post, status: post.status(today) })
+                .collect(),
+            preview,
+            total,
+            page: query.page,
+            per_page: query.per_page,
+            total_pages,
+            next: (query.page < total_pages).then(|| page_link(query.page + 1)),
+            prev: (query.page > 1).then(|| page_link((query.page - 1).min(total_pages))),
+        };
         serde_json::to_string(&response)
             .map_err(|e| format!("Failed to serialize blog list: {}", e))
     }
 
-    fn handle_blog_post_api(&self, slug: &str) -> Result<String, String> {
-        let blog_path = Path::new("public/blogs").join(format!("{}.md", slug));
-        
-        if !blog_path.exists() {
-            return Err(format!("Blog post '{}' not found", slug));
-        }
+    fn handle_search_api(&self, blog: &BlogConfig, params: &HashMap<String, String>) -> Result<String, String> {
+        let q = params.get("q").map(|q| q.trim()).unwrap_or("");
+        let limit = params.get("limit")
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|l| *l > 0)
+            .unwrap_or(10)
+            .min(50);
+
+        let results = if q.is_empty() {
+            Vec::new()
+        } else {
+            self.searcher.index(&self.blog_store)?.search(q, limit, &blog.page_prefix)
+        };
 
-        let markdown_content = fs::read_to_string(&blog_path)
-            .map_err(|e| format!("Failed to read blog file: {}", e))?;
+        #[derive(Serialize)]
+        struct SearchResponse<'a> {
+            query: &'a str,
+            total: usize,
+            results: Vec<SearchHit>,
+        }
 
-        // Parse markdown to HTML
-        let parser = Parser::new_ext(&markdown_content, Options::all());
-        let mut html_output = String::new();
-        html::push_html(&mut html_output, parser);
+        let response = SearchResponse { query: q, total: results.len(), results };
+        serde_json::to_string(&response)
+            .map_err(|e| format!("Failed to serialize search results: {}", e))
+    }
 
-        // Extract metadata
-        let title = markdown_content.lines()
-            .next()
-            .unwrap_or(slug)
-            .trim_start_matches('#')
-            .trim()
-            .to_string();
-        
-        let published_date = markdown_content.lines()
-            .find(|line| line.contains("**Published:**") || line.contains("Published:"))
-            .and_then(|line| {
-                line.split("Published:").nth(1)
-                    .or_else(|| line.split("**Published:**").nth(1))
-                    .map(|s| s.trim().trim_matches('*').trim().to_string())
-            })
-            .unwrap_or_else(|| "Unknown".to_string());
+    /// `Ok(None)` when the post does not exist or is not visible.
+    fn handle_blog_post_api(&self, blog: &BlogConfig, slug: &str, preview: bool) -> Result<Option<String>, String> {
+        let today = Date::today();
+        let Some(post) = self.visible_post(slug, preview, today)? else { return Ok(None) };
+        let published = self.blog_store.published(today)?;
+        let navigation = related::navigation(&post, &published, blog, &self.term_cache, self.blog_store.generation());
 
         #[derive(Serialize)]
-        struct BlogPostResponse {
-            slug: String,
-            title: String,
-            published_date: String,
-            content: String,
+        struct BlogPostResponse<'a> {
+            #[serde(flatten)]
+            post: &'a BlogPost,
+            status: PostStatus,
+            content: &'a str,
+            toc: &'a [TocEntry],
+            #[serde(flatten)]
+            navigation: PostNavigation,
         }
 
         let response = BlogPostResponse {
-            slug: slug.to_string(),
-            title,
-            published_date,
-            content: html_output,
+            post: &post,
+            status: post.status(today),
+            content: &post.html,
+            toc: &post.toc,
+            navigation,
         };
 
         serde_json::to_string(&response)
+            .map(Some)
             .map_err(|e| format!("Failed to serialize blog post: {}", e))
     }
 
-    fn handle_blog_post_page(&self, slug: &str) -> Result<String, String> {
-        let blog_path = Path::new("public/blogs").join(format!("{}.md", slug));
-        
-        if !blog_path.exists() {
-            return Err(format!("Blog post '{}' not found", slug));
-        }
+    fn handle_tags_api(&self, blog: &BlogConfig) -> Result<String, String> {
+        let tags = tags::collect_tags(&self.blog_store.published(Date::today())?, blog);
+        let response = serde_json::json!({ "total": tags.len(), "tags": tags });
+        serde_json::to_string(&response)
+            .map_err(|e| format!("Failed to serialize tags: {}", e))
+    }
 
-        let markdown_content = fs::read_to_string(&blog_path)
-            .map_err(|e| format!("Failed to read blog file: {}", e))?;
+    /// `Ok(None)` when no post carries the tag.
+    fn handle_tag_api(&self, blog: &BlogConfig, segment: &str) -> Result<Option<String>, String> {
+        let posts = self.blog_store.published(Date::today())?;
+        let tags = tags::collect_tags(&posts, blog);
+        let Some(tag) = tags::find_tag(&tags, segment) else { return Ok(None) };
 
-        // Parse markdown to HTML
-        let parser = Parser::new_ext(&markdown_content, Options::all());
-        let mut html_content = String::new();
-        html::push_html(&mut html_content, parser);
+        let mut tagged: Vec<_> = posts.iter().filter(|post| tags::has_tag(post, &tag.slug)).collect();
+        blog::sort_posts(&mut tagged);
+        let blogs: Vec<&BlogPost> = tagged.into_iter().map(|post| post.as_ref()).collect();
 
-        // Extract metadata
-        let title = markdown_content.lines()
-            .next()
-            .unwrap_or(slug)
-            .trim_start_matches('#')
-            .trim()
-            .to_string();
-        
-        let published_date = markdown_content.lines()
-            .find(|line| line.contains("**Published:**") || line.contains("Published:"))
-            .and_then(|line| {
-                line.split("Published:").nth(1)
-                    .or_else(|| line.split("**Published:**").nth(1))
-                    .map(|s| s.trim().trim_matches('*').trim().to_string())
+        let response = serde_json::json!({ "tag": tag, "total": blogs.len(), "blogs": blogs });
+        serde_json::to_string(&response)
+            .map(Some)
+            .map_err(|e| format!("Failed to serialize tag posts: {}", e))
+    }
+
+    /// `Ok(None)` when no post carries the tag.
+    fn handle_tag_page(&self, blog: &BlogConfig, segment: &str) -> Result<Option<String>, String> {
+        let posts = self.blog_store.published(Date::today())?;
+        let tags = tags::collect_tags(&posts, blog);
+        let Some(tag) = tags::find_tag(&tags, segment) else { return Ok(None) };
+
+        let mut tagged: Vec<_> = posts.iter().filter(|post| tags::has_tag(post, &tag.slug)).collect();
+        blog::sort_posts(&mut tagged);
+
+        let mut context = self.template_context(blog);
+        context["tag"] = serde_json::json!(tag);
+        context["single"] = serde_json::json!(tag.count == 1);
+        context["posts"] = tagged.iter()
+            .map(|post| {
+                let post: &BlogPost = post;
+                serde_json::json!({ "post": post, "url": blog.post_url(&post.slug) })
             })
-            .unwrap_or_else(|| "Unknown".to_string());
-
-        // Generate HTML page
-        let html_page = format!(r#"<!DOCTYPE html>
-<html lang="en">
-    <head>
-        <meta charset="utf-8" />
-        <meta name="viewport" content="width=device-width, initial-scale=1" />
-        <title>{} — Blogs — Will Vincent Parrone</title>
-        <link rel="stylesheet" href="/index.css" />
-        <link rel="stylesheet" href="/chatbar.css" />
-    </head>
-    <body>
-        <header class="site-header">
-            <nav class="nav" aria-label="Primary">
-                <a class="brand" href="/">WVP</a>
-                <button class="nav-toggle" aria-expanded="false" aria-controls="nav-menu">Menu</button>
-                <ul id="nav-menu" class="nav-menu">
-                    <li><a href="/">Home</a></li>
-                    <li><a href="/blogs">Blogs</a></li>
-                </ul>
-            </nav>
-        </header>
-
-        <main>
-            <div class="container" style="padding: 3rem 0;">
-                <div class="blog-post">
-                    <button id="blog-back" class="blog-back" aria-label="Back to blogs">← Back to Blogs</button>
-                    <article class="blog-content">
-                        <header class="blog-header">
-                            <h1>{}</h1>
-                            <div class="blog-meta">Published: {}</div>
-                        </header>
-                        <div class="blog-body">{}</div>
-                    </article>
-                </div>
-            </div>
-        </main>
-
-        <footer class="site-footer">
-            <div class="container">
-                <small>© <span id="year"></span> Will Vincent Parrone</small>
-            </div>
-        </footer>
-
-        <!-- Chatbar Component -->
-        <div id="chatbar" class="chatbar">
-            <button class="chatbar-toggle" aria-label="Toggle chat" aria-expanded="false">
-                <svg width="24" height="24" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2">
-                    <path d="M21 15a2 2 0 0 1-2 2H7l-4 4V5a2 2 0 0 1 2-2h14a2 2 0 0 1 2 2z"/>
-                </svg>
-            </button>
-            <div class="chatbar-panel">
-                <div class="chatbar-header">
-                    <h3>Chat</h3>
-                    <button class="chatbar-close" aria-label="Close chat">
-                        <svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2">
-                            <line x1="18" y1="6" x2="6" y2="18"/>
-                            <line x1="6" y1="6" x2="18" y2="18"/>
-                        </svg>
-                    </button>
-                </div>
-                <div class="chatbar-messages" id="chatbar-messages">
-                    <div class="chatbar-message chatbar-message-system">
-                        <p>Hello! How can I help you today?</p>
-                    </div>
-                </div>
-                <div class="chatbar-input-container">
-                    <form id="chatbar-form" class="chatbar-form">
-                        <input 
-                            type="text" 
-                            id="chatbar-input" 
-                            class="chatbar-input" 
-                            placeholder="Type your message..." 
-                            autocomplete="off"
-                            aria-label="Message input"
-                        />
-                        <button type="submit" class="chatbar-send" aria-label="Send message">
-                            <svg width="20" height="20" viewBox="0 0 24 24" fill="none" stroke="currentColor" stroke-width="2">
-                                <line x1="22" y1="2" x2="11" y2="13"/>
-                                <polygon points="22 2 15 22 11 13 2 9 22 2"/>
-                            </svg>
-                        </button>
-                    </form>
-                </div>
-            </div>
-        </div>
-
-        <script src="/chatbar.js"></script>
-        <script>
-            const yearEl = document.getElementById('year');
-            if (yearEl) {{ yearEl.textContent = new Date().getFullYear(); }}
-            const toggle = document.querySelector('.nav-toggle');
-            const menu = document.getElementById('nav-menu');
-            if (toggle && menu) {{
-                toggle.addEventListener('click', () => {{
-                    const open = menu.classList.toggle('open');
-                    toggle.setAttribute('aria-expanded', String(open));
-                }});
-            }}
-
-            const blogBackBtn = document.getElementById('blog-back');
-            if (blogBackBtn) {{
-                blogBackBtn.addEventListener('click', () => {{
-                    window.location.href = '/blogs';
-                }});
-            }}
-        </script>
-    </body>
-</html>"#, title, title, published_date, html_content);
-
-        Ok(html_page)
+            .collect();
+
+        self.templates.render("tag.html", &context)
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
+
+    /// `Ok(None)` when the post does not exist or is not visible.
+    fn handle_blog_post_page(&self, blog: &BlogConfig, slug: &str, preview: bool) -> Result<Option<String>, String> {
+        let today = Date::today();
+        let Some(post) = self.visible_post(slug, preview, today)? else { return Ok(None) };
+
+        let mut context = self.template_context(blog);
+        context["post"] = serde_json::json!(&*post);
+        context["content"] = serde_json::json!(&post.html);
+        context["toc"] = serde_json::json!(if blog.toc { post.toc.as_slice() } else { &[] });
+        context["tags"] = post.tags.iter()
+            .map(|name| serde_json::json!({ "name": name, "url": tags::tag_url(blog, &tags::tag_slug(name)) }))
+            .collect();
+        let banner = match post.status(today) {
+            PostStatus::Published => None,
+            PostStatus::Draft => Some("Draft preview: this post is not published.".to_string()),
+            PostStatus::Scheduled => Some(format!(
+                "Scheduled preview: this post goes live on {}.",
+                post.published_date
+            )),
+        };
+        if let Some(message) = banner {
+            context["preview"] = serde_json::json!({ "status": post.status(today), "message": message });
+        }
+        let published = self.blog_store.published(today)?;
+        let navigation = related::navigation(&post, &published, blog, &self.term_cache, self.blog_store.generation());
+        context["navigation"] = serde_json::json!(navigation);
+        if let Some(cfg) = &self.config {
+            context["meta"] = serde_json::json!(PageMeta::for_post(&post, &cfg.site, blog));
+        }
+
+        self.templates.render("blog_post.html", &context)
+            .map(Some)
+            .map_err(|e| e.to_string())
+    }
+
+    /// A post as a request may see it: drafts and scheduled posts are
+    /// reported as missing unless the request is in preview mode.
+    fn visible_post(&self, slug: &str, preview: bool, today: Date) -> Result<Option<Arc<BlogPost>>, String> {
+        let post = self.blog_store.get(slug)?;
+        Ok(post.filter(|post| preview || post.is_published(today)))
+    }
+
+    /// Values every page template can rely on: `site` and `blog`.
+    fn template_context(&self, blog: &BlogConfig) -> serde_json::Value {
+        let site = self.config.as_ref().map(|cfg| &cfg.site);
+        serde_json::json!({
+            "site": {
+                "title": site.map(|s| s.title.as_str()).unwrap_or(""),
+                "author": site.map(|s| s.author.as_str()).unwrap_or("Will Vincent Parrone"),
+                "language": site.map(|s| s.language.as_str()).unwrap_or("en"),
+            },
+            "blog": {
+                "url": &blog.page_prefix,
+            },
+        })
     }
 
+    #[allow(clippy::needless_return)]
     fn generate_prompt(message: &str) -> String {
         // Read pages.json for page and section summaries
         let pages_json = fs::read_to_string("pages.json")
@@ -804,6 +1045,7 @@ IMPORTANT NAVIGATION INSTRUCTIONS:
 - The index page has the following sections with IDs: home, about, experience, competencies, soft-skills, education, organizations, certificates, awards, contact
 - The blogs page has a listing section and individual blog posts
 - Individual blog posts are accessible at /blogs/:slug (e.g., /blogs/welcome-to-my-blog, /blogs/getting-started-with-rust)
+- Sections of a blog post can be linked with the heading id as a fragment, e.g. /blogs/getting-started-with-rust#why-rust (ids are the lowercased heading text joined with hyphens)
 - If the user's question requires viewing a specific page, section, or blog post, you MUST include a navigation instruction in your response
 - Format your response as JSON with two fields:
   1. "response": Your text response to the user
@@ -858,4 +1100,200 @@ If navigation is NOT needed, set "needed" to false and both "page" and "sectionI
 
 ALWAYS respond in valid JSON format with both "response" and "navigation" fields."#, message, pages_json)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{Shutdown, TcpListener, TcpStream};
+
+    use crate::listener::Connection;
+    use crate::test_support::{get, site};
+
+    #[test]
+    fn post_sources_are_not_served_as_static_files() {
+        let site = site("sources", &[
+            ("published.md", "---\ntitle: Published\n---\nHello"),
+            ("secret.md", "---\ntitle: Secret\ndraft: true\n---\nNot yet"),
+        ], "");
+        assert_eq!(get(&site, "/blogs/secret.md").status, 404);
+        assert_eq!(get(&site, "/blogs/published.md").status, 404);
+        assert_eq!(get(&site, "/blogs/secret").status, 404);
+        assert_eq!(get(&site, "/blogs/published").status, 200);
+        let body = get(&site, "/blogs/secret.md").body;
+        assert!(!String::from_utf8_lossy(&body).contains("Not yet"));
+    }
+
+    #[test]
+    fn post_title_and_date_are_escaped() {
+        let site = site("escaping", &[(
+            "xss.md",
+            "---\ntitle: \"<script>alert('title')</script>\"\ndate: \"<img src=x onerror=alert(1)>\"\n---\nBody",
+        )], "");
+        let response = get(&site, "/blogs/xss");
+        assert_eq!(response.status, 200);
+        let html = String::from_utf8_lossy(&response.body);
+        assert!(!html.contains("<script>alert"), "{}", html);
+        assert!(!html.contains("<img src=x"), "{}", html);
+        assert!(html.contains("&lt;script&gt;alert(&#39;title&#39;)&lt;/script&gt;"));
+        assert!(html.contains("&lt;img src=x onerror=alert(1)&gt;"));
+    }
+
+    #[test]
+    fn not_found_pages_do_not_echo_the_slug() {
+        let site = site("not-found", &[], "");
+        for target in ["/blogs/<script>alert(1)</script>", "/blogs/%3Cscript%3Ealert(1)%3C%2Fscript%3E"] {
+            let response = get(&site, target);
+            assert_eq!(response.status, 404);
+            let html = String::from_utf8_lossy(&response.body);
+            assert!(response.content_type.starts_with("text/html"));
+            assert!(!html.contains("<script>"), "{}", html);
+        }
+    }
+
+    #[test]
+    fn wrong_methods_on_known_routes_are_refused() {
+        let site = site("methods", &[("post.md", "---\ntitle: Post\n---\nHello")], "");
+        for (method, target, allowed) in [
+            ("POST", "/api/blogs", "GET"),
+            ("DELETE", "/api/blog/post", "GET"),
+            ("PUT", "/blogs/post", "GET"),
+            ("POST", "/feed.xml", "GET"),
+            ("GET", "/api/chat", "POST"),
+        ] {
+            let response = site.server.dispatch(method, target, None, None);
+            assert_eq!(response.status, 405, "{} {}", method, target);
+            assert!(response.headers.iter().any(|(name, value)| name == "Allow" && value == allowed));
+        }
+        let response = site.server.dispatch("POST", "/api/blogs", None, None);
+        assert!(response.content_type.starts_with("application/json"));
+        assert_eq!(site.server.dispatch("POST", "/index.html", None, None).status, 200);
+    }
+
+    #[test]
+    fn malformed_api_requests_get_json_errors() {
+        let site = site("malformed", &[], "");
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let exchange = |request: &str| {
+            let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+            client.write_all(request.as_bytes()).unwrap();
+            client.shutdown(Shutdown::Write).unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            site.server.handle_stream(Connection::Tcp(stream));
+            let mut response = String::new();
+            client.read_to_string(&mut response).unwrap();
+            response
+        };
+
+        let api = exchange("POST /api/chat HTTP/1.1\r\nContent-Length: 50\r\n\r\n{}");
+        assert!(api.starts_with("HTTP/1.1 400"), "{}", api);
+        assert!(api.contains("application/json"), "{}", api);
+        let page = exchange("POST /contact HTTP/1.1\r\nContent-Length: 50\r\n\r\n{}");
+        assert!(page.starts_with("HTTP/1.1 400"), "{}", page);
+        assert!(page.contains("text/html"), "{}", page);
+    }
+
+    #[test]
+    fn cache_stats_need_the_preview_token() {
+        let site = site("stats", &[], "\nblog:\n  preview_token: a-long-enough-preview-token\n");
+        assert_eq!(get(&site, "/api/blogs/stats").status, 404);
+        assert_eq!(get(&site, "/api/blogs/stats?preview=wrong").status, 404);
+        let response = get(&site, "/api/blogs/stats?preview=a-long-enough-preview-token");
+        assert_eq!(response.status, 200);
+        assert!(String::from_utf8_lossy(&response.body).contains("hits"));
+    }
+
+    fn json(site: &crate::test_support::Site, target: &str) -> (u16, serde_json::Value) {
+        let response = get(site, target);
+        (response.status, serde_json::from_slice(&response.body).unwrap())
+    }
+
+    fn listed_slugs(body: &serde_json::Value) -> Vec<String> {
+        body["blogs"].as_array().unwrap().iter().map(|post| post["slug"].as_str().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn blog_list_pages_and_filters() {
+        let posts: Vec<(String, String)> = (1..=5)
+            .map(|n| (
+                format!("post-{}.md", n),
+                format!("---\ntitle: Post {}\ndate: 2024-0{}-01\ntags: [{}]\n---\nBody", n, n, if n % 2 == 0 { "even" } else { "odd" }),
+            ))
+            .collect();
+        let posts: Vec<(&str, &str)> = posts.iter().map(|(f, s)| (f.as_str(), s.as_str())).collect();
+        let site = site("list", &posts, "");
+
+        let (status, body) = json(&site, "/api/blogs?per_page=2");
+        assert_eq!(status, 200);
+        assert_eq!(listed_slugs(&body), ["post-5", "post-4"]);
+        assert_eq!((body["total"].as_u64(), body["total_pages"].as_u64()), (Some(5), Some(3)));
+        assert_eq!(body["next"], "/api/blogs?page=2&per_page=2");
+        assert!(body["prev"].is_null());
+
+        let (_, body) = json(&site, "/api/blogs?per_page=2&page=3");
+        assert_eq!(listed_slugs(&body), ["post-1"]);
+        assert!(body["next"].is_null());
+        let (status, body) = json(&site, "/api/blogs?per_page=2&page=9");
+        assert_eq!(status, 200);
+        assert!(listed_slugs(&body).is_empty());
+        let (_, body) = json(&site, "/api/blogs?per_page=100000");
+        assert_eq!(body["per_page"].as_u64(), Some(crate::blog::MAX_PER_PAGE as u64));
+
+        let (_, body) = json(&site, "/api/blogs?tag=Even&sort=oldest");
+        assert_eq!(listed_slugs(&body), ["post-2", "post-4"]);
+        let (_, body) = json(&site, "/api/blogs?year=2024&q=post%203");
+        assert_eq!(listed_slugs(&body), ["post-3"]);
+        let (_, body) = json(&site, "/api/blogs?page=&sort=");
+        assert_eq!(body["total"].as_u64(), Some(5));
+    }
+
+    #[test]
+    fn invalid_blog_list_queries_are_rejected() {
+        let site = site("list-invalid", &[("post.md", "---\ntitle: Post\n---\nBody")], "");
+        for query in ["page=0", "page=-1", "page=abc", "page=99999999999999999999999", "per_page=0", "per_page=x", "year=20x4", "sort=random"] {
+            let (status, body) = json(&site, &format!("/api/blogs?{}", query));
+            assert_eq!(status, 400, "{}", query);
+            assert_eq!(body["status"], 400);
+            assert!(body["error"].as_str().unwrap().contains("must be"), "{}", body);
+        }
+    }
+
+    #[test]
+    fn unpublished_posts_need_the_preview_token() {
+        const TOKEN: &str = "a-long-enough-preview-token";
+        let site = site("preview", &[
+            ("live.md", "---\ntitle: Live post\ndate: 2020-01-01\n---\nPublic"),
+            ("draft.md", "---\ntitle: Draft post\ndraft: true\n---\nSecret draft"),
+            ("future.md", "---\ntitle: Future post\ndate: 2999-01-01\n---\nSecret schedule"),
+        ], &format!("\nblog:\n  preview_token: {}\n", TOKEN));
+        let body = |target: &str| String::from_utf8_lossy(&get(&site, target).body).into_owned();
+
+        let (_, list) = json(&site, "/api/blogs");
+        assert_eq!(listed_slugs(&list), ["live"]);
+        for target in ["/feed.xml", "/atom.xml", "/feed.json", "/sitemap.xml", "/api/tags", "/api/search?q=secret"] {
+            let text = body(target);
+            assert!(text.contains("live") || target.starts_with("/api/"), "{}: {}", target, text);
+            assert!(!text.contains("draft") && !text.contains("future") && !text.contains("Secret"), "{}: {}", target, text);
+        }
+        for slug in ["draft", "future"] {
+            assert_eq!(get(&site, &format!("/blogs/{}", slug)).status, 404);
+            assert_eq!(get(&site, &format!("/api/blog/{}", slug)).status, 404);
+            assert_eq!(get(&site, &format!("/blogs/{}?preview=wrong-token-of-some-length", slug)).status, 404);
+        }
+
+        let (_, list) = json(&site, &format!("/api/blogs?preview={}", TOKEN));
+        assert_eq!(list["preview"], true);
+        assert_eq!(listed_slugs(&list), ["future", "live", "draft"]);
+        let statuses: Vec<&str> = list["blogs"].as_array().unwrap().iter().map(|p| p["status"].as_str().unwrap()).collect();
+        assert_eq!(statuses, ["scheduled", "published", "draft"]);
+        for slug in ["draft", "future"] {
+            assert_eq!(get(&site, &format!("/blogs/{}?preview={}", slug, TOKEN)).status, 200);
+            let (status, post) = json(&site, &format!("/api/blog/{}?preview={}", slug, TOKEN));
+            assert_eq!(status, 200);
+            assert_eq!(post["slug"], slug);
+        }
+        // Syndication never previews.
+        for target in ["/feed.xml", "/sitemap.xml"] {
+            assert!(!body(&format!("{}?preview={}", target, TOKEN)).contains("draft"), "{}", target);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::blog::BlogPost;
+use crate::config::{BlogConfig, RobotsConfig, SiteConfig};
+use crate::date::Date;
+use crate::escape::escape_xml;
+use crate::static_files::StaticFileResolver;
+use crate::tags;
+
+pub struct SitemapEntry {
+    /// Site-relative URL path, e.g. `/blogs/welcome-to-my-blog`.
+    pub path: String,
+    pub lastmod: Option<Date>,
+}
+
+/// Collects every static HTML page, blog post and tag page that crawlers are
+/// allowed to visit. Everything is read fresh from disk (and the blog store),
+/// so the result always reflects the current content.
+pub fn collect_entries(
+    resolver: &StaticFileResolver,
+    posts: &[Arc<BlogPost>],
+    blog: &BlogConfig,
+    robots: &RobotsConfig,
+) -> Vec<SitemapEntry> {
+    let mut entries: Vec<SitemapEntry> = resolver.html_pages()
+        .into_iter()
+        .map(|(path, file)| SitemapEntry { path, lastmod: file_date(&file) })
+        .collect();
+
+    for post in posts {
+        entries.push(SitemapEntry {
+            path: blog.post_url(&post.slug),
+            lastmod: post.modified.map(Date::from_system_time).or(post.date),
+        });
+    }
+
+    for tag in tags::collect_tags(posts, blog) {
+        let newest = posts.iter()
+            .filter(|post| tags::has_tag(post, &tag.slug))
+            .filter_map(|post| post.modified.map(Date::from_system_time).or(post.date))
+            .max();
+        entries.push(SitemapEntry { path: tag.url, lastmod: newest });
+    }
+
+    entries.retain(|entry| is_allowed(&entry.path, robots));
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries.dedup_by(|a, b| a.path == b.path);
+    entries
+}
+
+pub fn sitemap_xml(entries: &[SitemapEntry], site: &SiteConfig) -> String {
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for entry in entries {
+        xml.push_str("  <url>\n");
+        xml.push_str(&format!("    <loc>{}{}</loc>\n", escape_xml(&site.base_url), escape_xml(&entry.path)));
+        if let Some(date) = entry.lastmod {
+            xml.push_str(&format!("    <lastmod>{}</lastmod>\n", date.to_iso()));
+        }
+        xml.push_str("  </url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+pub fn robots_txt(robots: &RobotsConfig, site: &SiteConfig) -> String {
+    let mut txt = String::from("User-agent: *\n");
+    for path in &robots.allow {
+        txt.push_str(&format!("Allow: {}\n", path));
+    }
+    for path in &robots.disallow {
+        txt.push_str(&format!("Disallow: {}\n", path));
+    }
+    if robots.allow.is_empty() && robots.disallow.is_empty() {
+        // An empty Disallow means everything may be crawled.
+        txt.push_str("Disallow:\n");
+    }
+    txt.push_str(&format!("\nSitemap: {}/sitemap.xml\n", site.base_url));
+    txt
+}
+
+// Longest matching rule wins, as in Google's robots.txt handling.
+fn is_allowed(path: &str, robots: &RobotsConfig) -> bool {
+    let longest = |rules: &[String]| {
+        rules.iter().filter(|rule| path.starts_with(rule.as_str())).map(|r| r.len()).max()
+    };
+    match (longest(&robots.allow), longest(&robots.disallow)) {
+        (_, None) => true,
+        (None, Some(_)) => false,
+        (Some(allow), Some(disallow)) => allow >= disallow,
+    }
+}
+
+fn file_date(path: &Path) -> Option<Date> {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(Date::from_system_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::test_support::{get, site};
+
+    #[test]
+    fn symlinked_directories_are_not_followed() {
+        let site = site("sitemap-links", &[], "");
+        let public = site.dir.join("public");
+        fs::create_dir_all(site.dir.join("outside")).unwrap();
+        fs::write(site.dir.join("outside/secret.html"), "secret").unwrap();
+        fs::write(public.join("about.html"), "about").unwrap();
+        std::os::unix::fs::symlink(&public, public.join("loop")).unwrap();
+        std::os::unix::fs::symlink(site.dir.join("outside"), public.join("out")).unwrap();
+        std::os::unix::fs::symlink(site.dir.join("outside/secret.html"), public.join("leak.html")).unwrap();
+
+        let response = get(&site, "/sitemap.xml");
+        assert_eq!(response.status, 200);
+        let xml = String::from_utf8_lossy(&response.body);
+        assert!(xml.contains("/about.html"), "{}", xml);
+        assert!(!xml.contains("secret") && !xml.contains("leak") && !xml.contains("/loop/"), "{}", xml);
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::fmt;
 
 use crate::config::StaticConfig;
@@ -66,19 +66,124 @@ impl StaticFileResolver {
         self.validate_within_root(candidate)
     }
 
+    pub fn root_dir(&self) -> &Path {
+        &self.root_dir
+    }
+
+    /// Every `.html` file under the root together with the URL path that
+    /// serves it. Files are only listed if resolving that URL leads back to
+    /// the same file, so everything returned is actually reachable.
+    pub fn html_pages(&self) -> Vec<(String, PathBuf)> {
+        let mut files = Vec::new();
+        collect_files(&self.root_dir, Some("html"), &mut files);
+
+        let mut pages = Vec::new();
+        for file in files {
+            let Ok(canonical) = std::fs::canonicalize(&file) else { continue };
+            let Ok(rel) = file.strip_prefix(&self.root_dir) else { continue };
+            let rel = rel.to_string_lossy().replace('\\', "/");
+
+            let mut candidates: Vec<String> = self.routes.iter()
+                .filter(|(_, target)| target.trim_start_matches('/') == rel)
+                .map(|(route, _)| route.clone())
+                .collect();
+            candidates.sort();
+            if self.auto_index && (rel == self.index_file || rel.ends_with(&format!("/{}", self.index_file))) {
+                candidates.push(format!("/{}", &rel[..rel.len() - self.index_file.len()]));
+            }
+            candidates.push(format!("/{}", rel));
+
+            if let Some(url) = candidates.into_iter()
+                .find(|url| self.resolve(url).map(|p| p == canonical).unwrap_or(false)) {
+                pages.push((url, file));
+            }
+        }
+        pages.sort();
+        pages
+    }
+
+    /// Every file under the root with the URL path `/<relative path>`, kept
+    /// only if that URL resolves back to the file (symlinks leaving the root
+    /// and the like are dropped).
+    pub fn files(&self) -> Vec<(String, PathBuf)> {
+        let mut files = Vec::new();
+        collect_files(&self.root_dir, None, &mut files);
+
+        let mut out = Vec::new();
+        for file in files {
+            let Ok(canonical) = std::fs::canonicalize(&file) else { continue };
+            let Ok(rel) = file.strip_prefix(&self.root_dir) else { continue };
+            let url = format!("/{}", rel.to_string_lossy().replace('\\', "/"));
+            if self.resolve(&url).map(|p| p == canonical).unwrap_or(false) {
+                out.push((url, file));
+            }
+        }
+        out.sort();
+        out
+    }
+
     fn validate_within_root(&self, candidate: PathBuf) -> Result<PathBuf, ResolveError> {
-        let root_canon = match std::fs::canonicalize(&self.root_dir) {
-            Ok(p) => p,
-            Err(_) => return Err(ResolveError::NotFound),
-        };
-        let cand_canon = match std::fs::canonicalize(&candidate) {
-            Ok(p) => p,
-            Err(_) => return Err(ResolveError::NotFound),
-        };
-        if !cand_canon.starts_with(&root_canon) {
-            return Err(ResolveError::Forbidden);
+        ensure_within(&self.root_dir, &candidate)
+    }
+}
+
+/// Canonicalizes `candidate` and checks it still lies under `root`, so `..`
+/// segments and symlinks cannot escape the directory being served. Missing
+/// files are `NotFound`; anything outside the root is `Forbidden`.
+pub fn ensure_within(root: &Path, candidate: &Path) -> Result<PathBuf, ResolveError> {
+    let root_canon = match std::fs::canonicalize(root) {
+        Ok(p) => p,
+        Err(_) => return Err(ResolveError::NotFound),
+    };
+    let cand_canon = match std::fs::canonicalize(candidate) {
+        Ok(p) => p,
+        Err(_) => return Err(ResolveError::NotFound),
+    };
+    if !cand_canon.starts_with(&root_canon) {
+        return Err(ResolveError::Forbidden);
+    }
+    Ok(cand_canon)
+}
+
+/// Canonical form of `path`, which may not exist yet: the nearest existing
+/// ancestor is canonicalized and the missing components appended.
+pub fn canonicalize_missing(path: &Path) -> std::io::Result<PathBuf> {
+    let absolute = std::env::current_dir()?.join(path);
+    let mut existing = absolute.as_path();
+    let mut missing = Vec::new();
+    while !existing.exists() {
+        let Some(parent) = existing.parent() else { break };
+        missing.extend(existing.components().next_back());
+        existing = parent;
+    }
+    let mut canonical = std::fs::canonicalize(existing)?;
+    for component in missing.into_iter().rev() {
+        match component {
+            Component::ParentDir => {
+                canonical.pop();
+            }
+            Component::CurDir => {}
+            other => canonical.push(other),
+        }
+    }
+    Ok(canonical)
+}
+
+/// Files under `dir`, recursively. Symlinked directories are not entered,
+/// so a link loop cannot stall the walk; symlinked files are listed and left
+/// to the callers, which drop those resolving outside the root.
+fn collect_files(dir: &Path, extension: Option<&str>, out: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else { continue };
+        let path = entry.path();
+        if file_type.is_dir() {
+            collect_files(&path, extension, out);
+        } else if file_type.is_symlink() && path.is_dir() {
+            continue;
+        } else if extension.is_none() || path.extension().and_then(|e| e.to_str()) == extension {
+            out.push(path);
         }
-        Ok(cand_canon)
     }
 }
 
@@ -95,6 +200,7 @@ pub fn resolve_content_type(path: &Path, overrides: &std::collections::HashMap<S
             "png" => "image/png",
             "jpg" | "jpeg" => "image/jpeg",
             "gif" => "image/gif",
+            "webp" => "image/webp",
             "svg" => "image/svg+xml",
             "ico" => "image/x-icon",
             _ => "application/octet-stream",
@@ -104,3 +210,51 @@ pub fn resolve_content_type(path: &Path, overrides: &std::collections::HashMap<S
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn paths_cannot_escape_the_root() {
+        let base = std::env::temp_dir().join(format!("portfolio-test-{}-within", std::process::id()));
+        let _ = fs::remove_dir_all(&base);
+        let root = base.join("root");
+        fs::create_dir_all(root.join("blogs")).unwrap();
+        fs::write(root.join("blogs/post.md"), "post").unwrap();
+        fs::write(base.join("secret.md"), "secret").unwrap();
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(base.join("secret.md"), root.join("blogs/leak.md")).unwrap();
+            std::os::unix::fs::symlink(&base, root.join("blogs/up")).unwrap();
+            std::os::unix::fs::symlink(root.join("blogs/post.md"), root.join("blogs/alias.md")).unwrap();
+        }
+
+        let cases: &[(&str, Result<(), bool>)] = &[
+            ("blogs/post.md", Ok(())),
+            ("blogs/../blogs/post.md", Ok(())),
+            ("blogs/missing.md", Err(false)),
+            ("../secret.md", Err(true)),
+            ("blogs/../../secret.md", Err(true)),
+            ("blogs/%2e%2e/%2e%2e/secret.md", Err(false)),
+            ("blogs\\..\\..\\secret.md", Err(false)),
+            ("blogs/post.md\0", Err(false)),
+            #[cfg(unix)]
+            ("blogs/leak.md", Err(true)),
+            #[cfg(unix)]
+            ("blogs/up/secret.md", Err(true)),
+            #[cfg(unix)]
+            ("blogs/alias.md", Ok(())),
+        ];
+        for (relative, expected) in cases {
+            let result = ensure_within(&root, &root.join(relative));
+            match (expected, &result) {
+                (Ok(()), Ok(path)) => assert!(path.starts_with(fs::canonicalize(&root).unwrap())),
+                (Err(true), Err(ResolveError::Forbidden)) | (Err(false), Err(ResolveError::NotFound)) => {}
+                _ => panic!("{:?}: expected {:?}, got {:?}", relative, expected, result),
+            }
+        }
+        let _ = fs::remove_dir_all(&base);
+    }
+}
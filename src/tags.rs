@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::blog::BlogPost;
+use crate::config::BlogConfig;
+use crate::toc::slugify;
+
+/// A tag across all posts. Tags are grouped by slug, so "Rust" and "rust"
+/// are the same tag; the spelling used by most posts is the display name.
+#[derive(Debug, Clone, Serialize)]
+pub struct TagSummary {
+    pub name: String,
+    pub slug: String,
+    pub count: usize,
+    /// HTML page listing the tag's posts.
+    pub url: String,
+}
+
+pub fn tag_slug(tag: &str) -> String {
+    slugify(tag)
+}
+
+pub fn has_tag(post: &BlogPost, slug: &str) -> bool {
+    post.tags.iter().any(|tag| tag_slug(tag) == slug)
+}
+
+pub fn tag_url(blog: &BlogConfig, slug: &str) -> String {
+    format!("{}/tags/{}", blog.page_prefix, slug)
+}
+
+/// Every tag used by `posts`, most used first, then alphabetically.
+pub fn collect_tags<P: AsRef<BlogPost>>(posts: &[P], blog: &BlogConfig) -> Vec<TagSummary> {
+    // slug -> (spelling -> uses)
+    let mut spellings: HashMap<String, HashMap<&str, usize>> = HashMap::new();
+    for post in posts {
+        let post = post.as_ref();
+        let mut seen = Vec::new();
+        for tag in &post.tags {
+            let slug = tag_slug(tag);
+            // A post listing the same tag twice still counts once.
+            if seen.contains(&slug) {
+                continue;
+            }
+            *spellings.entry(slug.clone()).or_default().entry(tag.as_str()).or_insert(0) += 1;
+            seen.push(slug);
+        }
+    }
+
+    let mut tags: Vec<TagSummary> = spellings.into_iter()
+        .map(|(slug, names)| {
+            let count = names.values().sum();
+            let name = names.into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+                .map(|(name, _)| name.to_string())
+                .unwrap_or_default();
+            TagSummary { url: tag_url(blog, &slug), name, slug, count }
+        })
+        .collect();
+    tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase())));
+    tags
+}
+
+/// The tag a request path segment refers to. The segment may be the slug or
+/// the tag name itself, percent-encoded or not.
+pub fn find_tag<'a>(tags: &'a [TagSummary], segment: &str) -> Option<&'a TagSummary> {
+    let decoded = crate::http::percent_decode(segment)?;
+    let slug = tag_slug(&decoded);
+    tags.iter().find(|tag| tag.slug == slug)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::site;
+
+    fn post(slug: &str, tags: &str) -> BlogPost {
+        BlogPost::parse(slug, &format!("---\ntitle: {}\ntags: [{}]\n---\nBody", slug, tags), None)
+    }
+
+    #[test]
+    fn slugs_are_normalised() {
+        for (tag, slug) in [
+            ("Rust", "rust"),
+            ("  Web Dev  ", "web-dev"),
+            ("web_dev", "web-dev"),
+            ("web--dev", "web-dev"),
+            ("C++", "c"),
+            ("Über Café", "über-café"),
+            ("???", "section"),
+        ] {
+            assert_eq!(tag_slug(tag), slug, "{:?}", tag);
+        }
+    }
+
+    #[test]
+    fn colliding_spellings_are_one_tag() {
+        let site = site("tags", &[], "");
+        let blog = &site.server.config.as_ref().unwrap().blog;
+        let posts = [
+            post("one", "Rust, web dev"),
+            post("two", "rust, Web-Dev, rust"),
+            post("three", "rust, CLI"),
+        ];
+        let tags = collect_tags(&posts, blog);
+        let summary: Vec<(&str, &str, usize)> = tags.iter().map(|t| (t.name.as_str(), t.slug.as_str(), t.count)).collect();
+        assert_eq!(summary, [("rust", "rust", 3), ("Web-Dev", "web-dev", 2), ("CLI", "cli", 1)]);
+        assert_eq!(tags[1].url, "/blogs/tags/web-dev");
+
+        assert!(has_tag(&posts[0], "web-dev"));
+        assert!(!has_tag(&posts[2], "web-dev"));
+        for segment in ["web-dev", "Web%20Dev", "web dev", "WEB_DEV"] {
+            assert_eq!(find_tag(&tags, segment).map(|t| t.slug.as_str()), Some("web-dev"), "{:?}", segment);
+        }
+        assert!(find_tag(&tags, "go").is_none());
+        assert!(find_tag(&tags, "%zz").is_none());
+    }
+}
@@ -0,0 +1,505 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use serde_json::Value;
+
+use crate::escape::escape_html;
+
+// Guards against templates that include or extend themselves.
+const MAX_DEPTH: usize = 16;
+
+/// File-based HTML templates with a small Jinja-like syntax:
+///
+/// - `{{ post.title }}` prints a value, HTML-escaped; `{{ content | safe }}` prints it raw
+/// - `{% if post.tags %}…{% else %}…{% endif %}`, with `not` for negation
+/// - `{% for tag in post.tags %}…{% endfor %}`, exposing `loop.index`, `loop.first`, `loop.last`
+/// - `{% include "partials/header.html" %}` renders another template with the same context
+/// - `{% extends "layout.html" %}` plus `{% block name %}…{% endblock %}` for layouts
+/// - `{# comments #}`, and `{%-` / `-%}` to trim surrounding whitespace
+///
+/// Parsed templates are cached; with `reload` enabled a template is re-read
+/// whenever its file changes, which is convenient during development.
+#[derive(Debug)]
+pub struct Templates {
+    dir: PathBuf,
+    reload: bool,
+    cache: Mutex<HashMap<String, CachedTemplate>>,
+}
+
+#[derive(Debug)]
+struct CachedTemplate {
+    modified: Option<SystemTime>,
+    nodes: Arc<Vec<Node>>,
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    Io(String, std::io::Error),
+    Syntax(String, String),
+}
+
+impl std::error::Error for TemplateError {}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Io(name, e) => write!(f, "template {}: io error: {}", name, e),
+            TemplateError::Syntax(name, s) => write!(f, "template {}: syntax error: {}", name, s),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Var { path: String, raw: bool },
+    If { negate: bool, path: String, then: Vec<Node>, otherwise: Vec<Node> },
+    For { var: String, path: String, body: Vec<Node> },
+    Include(String),
+    Extends(String),
+    Block { name: String, body: Vec<Node> },
+}
+
+impl Templates {
+    pub fn new<P: AsRef<Path>>(dir: P, reload: bool) -> Templates {
+        Templates {
+            dir: dir.as_ref().to_path_buf(),
+            reload,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn render(&self, name: &str, context: &Value) -> Result<String, TemplateError> {
+        let mut out = String::new();
+        let mut scope = Scope { root: context, locals: Vec::new() };
+        self.render_template(name, &mut scope, &HashMap::new(), &mut out, 0)?;
+        Ok(out)
+    }
+
+    fn render_template(
+        &self,
+        name: &str,
+        scope: &mut Scope,
+        blocks: &HashMap<String, Arc<Vec<Node>>>,
+        out: &mut String,
+        depth: usize,
+    ) -> Result<(), TemplateError> {
+        if depth > MAX_DEPTH {
+            return Err(TemplateError::Syntax(name.to_string(), "templates nested too deeply".to_string()));
+        }
+        let nodes = self.load(name)?;
+
+        // A child template only contributes blocks to its parent layout.
+        if let Some(parent) = nodes.iter().find_map(|n| match n {
+            Node::Extends(parent) => Some(parent.clone()),
+            _ => None,
+        }) {
+            let mut merged = blocks.clone();
+            collect_blocks(&nodes, &mut merged);
+            return self.render_template(&parent, scope, &merged, out, depth + 1);
+        }
+
+        self.render_nodes(&nodes, scope, blocks, out, depth)
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        scope: &mut Scope,
+        blocks: &HashMap<String, Arc<Vec<Node>>>,
+        out: &mut String,
+        depth: usize,
+    ) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Var { path, raw } => {
+                    let text = value_to_string(scope.lookup(path));
+                    if *raw {
+                        out.push_str(&text);
+                    } else {
+                        out.push_str(&escape_html(&text));
+                    }
+                }
+                Node::If { negate, path, then, otherwise } => {
+                    let branch = if is_truthy(scope.lookup(path)) != *negate { then } else { otherwise };
+                    self.render_nodes(branch, scope, blocks, out, depth)?;
+                }
+                Node::For { var, path, body } => {
+                    let items: Vec<Value> = match scope.lookup(path) {
+                        Some(Value::Array(items)) => items.clone(),
+                        Some(Value::Object(map)) => map.values().cloned().collect(),
+                        _ => Vec::new(),
+                    };
+                    let len = items.len();
+                    for (idx, item) in items.into_iter().enumerate() {
+                        let loop_info = serde_json::json!({
+                            "index": idx + 1,
+                            "first": idx == 0,
+                            "last": idx + 1 == len,
+                        });
+                        scope.locals.push((var.clone(), item));
+                        scope.locals.push(("loop".to_string(), loop_info));
+                        let result = self.render_nodes(body, scope, blocks, out, depth);
+                        scope.locals.truncate(scope.locals.len() - 2);
+                        result?;
+                    }
+                }
+                Node::Include(name) => self.render_template(name, scope, blocks, out, depth + 1)?,
+                Node::Block { name, body } => match blocks.get(name) {
+                    Some(overridden) => self.render_nodes(overridden, scope, blocks, out, depth)?,
+                    None => self.render_nodes(body, scope, blocks, out, depth)?,
+                },
+                Node::Extends(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn load(&self, name: &str) -> Result<Arc<Vec<Node>>, TemplateError> {
+        if name.split(['/', '\\']).any(|part| part == "..") {
+            return Err(TemplateError::Syntax(name.to_string(), "template names may not contain '..'".to_string()));
+        }
+        let path = self.dir.join(name);
+        let modified = if self.reload {
+            fs::metadata(&path).and_then(|m| m.modified()).ok()
+        } else {
+            None
+        };
+
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(cached) = cache.get(name)
+            && (!self.reload || cached.modified == modified) {
+            return Ok(cached.nodes.clone());
+        }
+
+        let source = fs::read_to_string(&path).map_err(|e| TemplateError::Io(name.to_string(), e))?;
+        // Like Jinja, drop the file's final newline so includes splice in cleanly.
+        let source = source.strip_suffix('\n').unwrap_or(&source);
+        let nodes = Arc::new(parse(source).map_err(|e| TemplateError::Syntax(name.to_string(), e))?);
+        cache.insert(name.to_string(), CachedTemplate { modified, nodes: nodes.clone() });
+        Ok(nodes)
+    }
+}
+
+/// Variable lookup: loop variables shadow the root context.
+struct Scope<'a> {
+    root: &'a Value,
+    locals: Vec<(String, Value)>,
+}
+
+impl Scope<'_> {
+    fn lookup(&self, path: &str) -> Option<&Value> {
+        let mut parts = path.split('.');
+        let first = parts.next()?;
+        let mut current = match self.locals.iter().rev().find(|(name, _)| name == first) {
+            Some((_, value)) => value,
+            None => self.root.get(first)?,
+        };
+        for part in parts {
+            current = match current {
+                Value::Array(items) => items.get(part.parse::<usize>().ok()?)?,
+                other => other.get(part)?,
+            };
+        }
+        Some(current)
+    }
+}
+
+fn collect_blocks(nodes: &[Node], blocks: &mut HashMap<String, Arc<Vec<Node>>>) {
+    for node in nodes {
+        if let Node::Block { name, body } = node {
+            // Blocks already set by a more derived template win.
+            if !blocks.contains_key(name) {
+                blocks.insert(name.clone(), Arc::new(body.clone()));
+            }
+        }
+    }
+}
+
+fn is_truthy(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) => false,
+        Some(Value::Bool(b)) => *b,
+        Some(Value::String(s)) => !s.is_empty(),
+        Some(Value::Array(a)) => !a.is_empty(),
+        Some(Value::Object(o)) => !o.is_empty(),
+        Some(Value::Number(n)) => n.as_f64() != Some(0.0),
+    }
+}
+
+fn value_to_string(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+enum Token {
+    Text(String),
+    Var(String),
+    Tag(String),
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    let mut trim_next_text = false;
+
+    while !rest.is_empty() {
+        let next = ["{{", "{%", "{#"].iter()
+            .filter_map(|open| rest.find(open).map(|pos| (pos, *open)))
+            .min_by_key(|(pos, _)| *pos);
+
+        let Some((pos, open)) = next else {
+            push_text(&mut tokens, rest, trim_next_text, false);
+            break;
+        };
+
+        let close = match open {
+            "{{" => "}}",
+            "{%" => "%}",
+            _ => "#}",
+        };
+        let inner_start = pos + 2;
+        let end = rest[inner_start..].find(close)
+            .ok_or_else(|| format!("unclosed '{}'", open))? + inner_start;
+        let inner = &rest[inner_start..end];
+
+        let trim_before = inner.starts_with('-');
+        push_text(&mut tokens, &rest[..pos], trim_next_text, trim_before);
+        trim_next_text = inner.ends_with('-');
+        let inner = inner.trim_start_matches('-').trim_end_matches('-').trim().to_string();
+
+        match open {
+            "{{" => tokens.push(Token::Var(inner)),
+            "{%" => tokens.push(Token::Tag(inner)),
+            _ => {}
+        }
+        rest = &rest[end + 2..];
+    }
+    Ok(tokens)
+}
+
+fn push_text(tokens: &mut Vec<Token>, text: &str, trim_start: bool, trim_end: bool) {
+    let mut text = text;
+    if trim_start {
+        text = text.trim_start();
+    }
+    if trim_end {
+        text = text.trim_end();
+    }
+    if !text.is_empty() {
+        tokens.push(Token::Text(text.to_string()));
+    }
+}
+
+fn parse(source: &str) -> Result<Vec<Node>, String> {
+    let tokens = tokenize(source)?;
+    let mut pos = 0;
+    let (nodes, end) = parse_until(&tokens, &mut pos, &[])?;
+    if let Some(tag) = end {
+        return Err(format!("unexpected '{{% {} %}}'", tag));
+    }
+    Ok(nodes)
+}
+
+/// Parses nodes until one of the `terminators` tags (or the end of input) and
+/// returns the terminating tag, if any.
+fn parse_until(tokens: &[Token], pos: &mut usize, terminators: &[&str]) -> Result<(Vec<Node>, Option<String>), String> {
+    let mut nodes = Vec::new();
+    while *pos < tokens.len() {
+        let token = &tokens[*pos];
+        *pos += 1;
+        match token {
+            Token::Text(text) => nodes.push(Node::Text(text.clone())),
+            Token::Var(expr) => {
+                let mut parts = expr.split('|').map(str::trim);
+                let path = parts.next().unwrap_or("").to_string();
+                let mut raw = false;
+                for filter in parts {
+                    match filter {
+                        "safe" => raw = true,
+                        "escape" | "e" => raw = false,
+                        other => return Err(format!("unknown filter '{}'", other)),
+                    }
+                }
+                nodes.push(Node::Var { path, raw });
+            }
+            Token::Tag(tag) => {
+                let keyword = tag.split_whitespace().next().unwrap_or("");
+                if terminators.contains(&keyword) {
+                    return Ok((nodes, Some(tag.clone())));
+                }
+                let args = tag[keyword.len()..].trim();
+                match keyword {
+                    "if" => {
+                        let (negate, path) = match args.strip_prefix("not ") {
+                            Some(path) => (true, path.trim().to_string()),
+                            None => (false, args.to_string()),
+                        };
+                        let (then, end) = parse_until(tokens, pos, &["else", "endif"])?;
+                        let otherwise = match end.as_deref() {
+                            Some("else") => {
+                                let (otherwise, end) = parse_until(tokens, pos, &["endif"])?;
+                                expect_end(end, "endif")?;
+                                otherwise
+                            }
+                            other => {
+                                expect_end(other.map(str::to_string), "endif")?;
+                                Vec::new()
+                            }
+                        };
+                        nodes.push(Node::If { negate, path, then, otherwise });
+                    }
+                    "for" => {
+                        let parts: Vec<&str> = args.split_whitespace().collect();
+                        if parts.len() != 3 || parts[1] != "in" {
+                            return Err(format!("expected 'for <name> in <path>', got '{}'", tag));
+                        }
+                        let (body, end) = parse_until(tokens, pos, &["endfor"])?;
+                        expect_end(end, "endfor")?;
+                        nodes.push(Node::For { var: parts[0].to_string(), path: parts[2].to_string(), body });
+                    }
+                    "block" => {
+                        let (body, end) = parse_until(tokens, pos, &["endblock"])?;
+                        expect_end(end, "endblock")?;
+                        nodes.push(Node::Block { name: args.to_string(), body });
+                    }
+                    "include" => nodes.push(Node::Include(parse_string_arg(args)?)),
+                    "extends" => nodes.push(Node::Extends(parse_string_arg(args)?)),
+                    other => return Err(format!("unknown tag '{}'", other)),
+                }
+            }
+        }
+    }
+    Ok((nodes, None))
+}
+
+fn expect_end(end: Option<String>, expected: &str) -> Result<(), String> {
+    match end {
+        Some(tag) if tag == expected => Ok(()),
+        _ => Err(format!("missing '{{% {} %}}'", expected)),
+    }
+}
+
+fn parse_string_arg(args: &str) -> Result<String, String> {
+    let trimmed = args.trim();
+    if trimmed.len() >= 2
+        && ((trimmed.starts_with('"') && trimmed.ends_with('"'))
+            || (trimmed.starts_with('\'') && trimmed.ends_with('\'')))
+    {
+        Ok(trimmed[1..trimmed.len() - 1].to_string())
+    } else {
+        Err(format!("expected a quoted template name, got '{}'", args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::*;
+
+    /// A template directory under the temp dir, removed when dropped.
+    struct Dir(PathBuf);
+
+    impl Dir {
+        fn new(name: &str, files: &[(&str, &str)]) -> Dir {
+            let dir = std::env::temp_dir().join(format!("portfolio-test-{}-{}", std::process::id(), name));
+            let _ = fs::remove_dir_all(&dir);
+            for (file, source) in files {
+                let path = dir.join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, source).unwrap();
+            }
+            Dir(dir)
+        }
+    }
+
+    impl Drop for Dir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn render(files: &[(&str, &str)], context: Value) -> Result<String, TemplateError> {
+        let dir = Dir::new(&format!("template-{}", files[0].0.replace('/', "-")), files);
+        Templates::new(&dir.0, false).render(files[0].0, &context)
+    }
+
+    #[test]
+    fn variables_are_escaped_unless_safe() {
+        let context = json!({ "x": "<script>alert('x')</script>", "post": { "title": "A & B" } });
+        let html = render(&[("escape.html", "{{ x }}|{{ x | safe }}|{{ x | e }}|{{ post.title }}")], context).unwrap();
+        assert_eq!(
+            html,
+            "&lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;|<script>alert('x')</script>|\
+             &lt;script&gt;alert(&#39;x&#39;)&lt;/script&gt;|A &amp; B"
+        );
+    }
+
+    #[test]
+    fn missing_variables_render_empty() {
+        let html = render(&[("missing.html", "[{{ nope }}][{{ post.nope.deeper }}][{% if nope %}x{% else %}y{% endif %}]")], json!({ "post": {} }));
+        assert_eq!(html.unwrap(), "[][][y]");
+    }
+
+    #[test]
+    fn loops_and_conditionals() {
+        let template = "{% for tag in tags -%}\n{{ loop.index }}:{{ tag }}{% if not loop.last %}, {% endif %}\n{%- endfor %}\
+                        {% if empty %}!{% endif %}{% for x in empty %}never{% endfor %}";
+        let html = render(&[("loop.html", template)], json!({ "tags": ["<a>", "b", "c"], "empty": [] }));
+        assert_eq!(html.unwrap(), "1:&lt;a&gt;, 2:b, 3:c");
+    }
+
+    #[test]
+    fn partials_and_layouts() {
+        let files = [
+            ("page.html", "{% extends \"layout.html\" %}{% block title %}{{ title }}{% endblock %}{% block body %}Body{% endblock %}"),
+            ("layout.html", "<title>{% block title %}Default{% endblock %}</title>{% include \"partials/nav.html\" %}<main>{% block body %}{% endblock %}</main>{% block footer %}Footer{% endblock %}\n"),
+            ("partials/nav.html", "<nav>{{ title }}</nav>\n"),
+        ];
+        let html = render(&files, json!({ "title": "<Hi>" })).unwrap();
+        assert_eq!(html, "<title>&lt;Hi&gt;</title><nav>&lt;Hi&gt;</nav><main>Body</main>Footer");
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let syntax = |source: &str| match render(&[("bad.html", source)], json!({})) {
+            Err(TemplateError::Syntax(_, message)) => message,
+            other => panic!("{:?}: expected a syntax error, got {:?}", source, other),
+        };
+        assert_eq!(syntax("{% frobnicate %}"), "unknown tag 'frobnicate'");
+        assert_eq!(syntax("{{ x | upper }}"), "unknown filter 'upper'");
+        assert_eq!(syntax("{% if x %}open"), "missing '{% endif %}'");
+        assert_eq!(syntax("{% endfor %}"), "unknown tag 'endfor'");
+        assert_eq!(syntax("{{ x"), "unclosed '{{'");
+        assert_eq!(syntax("{% include \"bad.html\" %}"), "templates nested too deeply");
+        assert_eq!(syntax("{% include \"../secret.html\" %}"), "template names may not contain '..'");
+        assert!(matches!(render(&[("io.html", "{% include \"absent.html\" %}")], json!({})), Err(TemplateError::Io(..))));
+    }
+
+    #[test]
+    fn reload_picks_up_changes() {
+        let dir = Dir::new("template-reload", &[("page.html", "one")]);
+        let cached = Templates::new(&dir.0, false);
+        let reloading = Templates::new(&dir.0, true);
+        assert_eq!(cached.render("page.html", &json!({})).unwrap(), "one");
+        assert_eq!(reloading.render("page.html", &json!({})).unwrap(), "one");
+
+        let path = dir.0.join("page.html");
+        fs::write(&path, "two").unwrap();
+        fs::File::options().write(true).open(&path).unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60)).unwrap();
+        assert_eq!(cached.render("page.html", &json!({})).unwrap(), "one");
+        assert_eq!(reloading.render("page.html", &json!({})).unwrap(), "two");
+    }
+}
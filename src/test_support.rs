@@ -0,0 +1,66 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+
+use crate::blog_store::BlogStore;
+use crate::config;
+use crate::http::Response;
+use crate::related::TermCache;
+use crate::search::Searcher;
+use crate::server::Server;
+use crate::template::Templates;
+
+/// A scratch site under the temp dir, removed when dropped.
+pub struct Site {
+    pub dir: PathBuf,
+    pub server: Server,
+}
+
+impl Drop for Site {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A server over a site holding `posts` (file name and source), built the
+/// way `main` builds one. `extra` is appended to the config.
+pub fn site(name: &str, posts: &[(&str, &str)], extra: &str) -> Site {
+    let dir = std::env::temp_dir().join(format!("portfolio-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("public/blogs")).unwrap();
+    fs::write(dir.join("public/index.html"), "<h1>Home</h1>").unwrap();
+    for (file, source) in posts {
+        fs::write(dir.join("public/blogs").join(file), source).unwrap();
+    }
+    let yaml = format!(
+        "server:\n  host: 127.0.0.1\n  port: 0\n\nstatic:\n  root_dir: {root}\n  index_file: index.html\n  auto_index: true\n\n\
+         templates:\n  dir: {templates}\n\nimages:\n  enabled: false\n  cache_dir: {cache}\n{extra}",
+        root = dir.join("public").display(),
+        templates = Path::new(env!("CARGO_MANIFEST_DIR")).join("templates").display(),
+        cache = dir.join("cache").display(),
+        extra = extra,
+    );
+    let config_path = dir.join("config.yaml");
+    fs::write(&config_path, yaml).unwrap();
+    let config = config::load_config(&config_path).unwrap();
+
+    let mut server = Server {
+        host: config.server.host.clone(),
+        port: config.server.port.to_string(),
+        endpoints: Vec::new(),
+        listeners: Vec::new(),
+        resolver: None,
+        draining: AtomicBool::new(false),
+        blog_store: BlogStore::new(&config.blog.content_dir),
+        searcher: Searcher::default(),
+        term_cache: TermCache::default(),
+        templates: Templates::new(&config.templates.dir, false),
+        config: Some(config),
+    };
+    server.init_resolver();
+    Site { dir, server }
+}
+
+pub fn get(site: &Site, target: &str) -> Response {
+    site.server.dispatch("GET", target, None, None)
+}
@@ -0,0 +1,187 @@
+use std::collections::HashSet;
+
+use pulldown_cmark::{Event, HeadingLevel, Tag};
+use serde::Serialize;
+
+use crate::escape::escape_html;
+
+/// One heading in a post's table of contents, with the headings below it
+/// nested as children.
+#[derive(Debug, Clone, Serialize)]
+pub struct TocEntry {
+    pub level: u32,
+    pub id: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TocEntry>,
+}
+
+/// A heading whose inline events are still being collected.
+struct OpenHeading<'a> {
+    level: HeadingLevel,
+    explicit_id: Option<&'a str>,
+    classes: Vec<&'a str>,
+    inner: Vec<Event<'a>>,
+}
+
+/// Markdown pass giving every heading an `id` and a trailing `#` anchor
+/// link. Ids come from `{#custom-id}` heading attributes when present,
+/// otherwise from the heading text; repeats get `-1`, `-2`, … suffixes so
+/// they stay unique and stable as long as the headings above do not change.
+///
+/// Returns the rewritten events and the table of contents. Level-one headings
+/// are left out of the TOC as they normally repeat the post title.
+pub fn anchor_headings<'a, I: Iterator<Item = Event<'a>>>(events: I) -> (Vec<Event<'a>>, Vec<TocEntry>) {
+    let mut out = Vec::new();
+    let mut toc = Vec::new();
+    let mut used = HashSet::new();
+    let mut heading: Option<OpenHeading> = None;
+
+    for event in events {
+        match event {
+            Event::Start(Tag::Heading(level, id, classes)) => {
+                heading = Some(OpenHeading { level, explicit_id: id, classes, inner: Vec::new() });
+            }
+            Event::End(Tag::Heading(..)) => {
+                let Some(OpenHeading { level, explicit_id, classes, inner }) = heading.take() else { continue };
+                let title = heading_text(&inner);
+                let base = explicit_id.map(slugify).unwrap_or_else(|| slugify(&title));
+                let id = unique_id(&mut used, base);
+                let tag = level_tag(level);
+
+                let class_attr = if classes.is_empty() {
+                    String::new()
+                } else {
+                    format!(" class=\"{}\"", escape_html(&classes.join(" ")))
+                };
+                out.push(Event::Html(format!("<{} id=\"{}\"{}>", tag, escape_html(&id), class_attr).into()));
+                out.extend(inner);
+                out.push(Event::Html(format!(
+                    "<a class=\"heading-anchor\" href=\"#{}\" title=\"Link to this section\">#</a></{}>\n",
+                    escape_html(&id),
+                    tag
+                ).into()));
+
+                if level != HeadingLevel::H1 {
+                    insert_nested(&mut toc, TocEntry { level: level as u32, id, title, children: Vec::new() });
+                }
+            }
+            event => match heading.as_mut() {
+                Some(open) => open.inner.push(event),
+                None => out.push(event),
+            },
+        }
+    }
+    (out, toc)
+}
+
+/// Lowercases and keeps letters and digits, turning runs of whitespace,
+/// hyphens and underscores into single hyphens: "Why `Arc<T>`?" -> "why-arct".
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::with_capacity(text.len());
+    let mut pending_dash = false;
+    for ch in text.chars() {
+        if ch.is_alphanumeric() {
+            if pending_dash && !slug.is_empty() {
+                slug.push('-');
+            }
+            pending_dash = false;
+            slug.extend(ch.to_lowercase());
+        } else if ch.is_whitespace() || ch == '-' || ch == '_' {
+            pending_dash = true;
+        }
+    }
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+fn unique_id(used: &mut HashSet<String>, base: String) -> String {
+    let mut id = base.clone();
+    let mut n = 1;
+    while used.contains(&id) {
+        id = format!("{}-{}", base, n);
+        n += 1;
+    }
+    used.insert(id.clone());
+    id
+}
+
+fn heading_text(events: &[Event]) -> String {
+    let mut text = String::new();
+    for event in events {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(t),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+fn level_tag(level: HeadingLevel) -> &'static str {
+    match level {
+        HeadingLevel::H1 => "h1",
+        HeadingLevel::H2 => "h2",
+        HeadingLevel::H3 => "h3",
+        HeadingLevel::H4 => "h4",
+        HeadingLevel::H5 => "h5",
+        HeadingLevel::H6 => "h6",
+    }
+}
+
+fn insert_nested(entries: &mut Vec<TocEntry>, entry: TocEntry) {
+    match entries.last_mut() {
+        Some(last) if last.level < entry.level => insert_nested(&mut last.children, entry),
+        _ => entries.push(entry),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::{html, Options, Parser};
+
+    use super::*;
+
+    fn render(markdown: &str) -> (String, Vec<TocEntry>) {
+        let (events, toc) = anchor_headings(Parser::new_ext(markdown, Options::all()));
+        let mut out = String::new();
+        html::push_html(&mut out, events.into_iter());
+        (out, toc)
+    }
+
+    fn outline(entries: &[TocEntry]) -> Vec<String> {
+        entries.iter()
+            .map(|e| {
+                let children = outline(&e.children);
+                if children.is_empty() { e.id.clone() } else { format!("{}[{}]", e.id, children.join(" ")) }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn repeated_headings_get_unique_ids() {
+        let (html, toc) = render("## Intro\n## Intro\n## Intro {#intro}\n## Intro 1\n## `Arc<T>`?\n## !!!\n");
+        assert_eq!(outline(&toc), ["intro", "intro-1", "intro-2", "intro-1-1", "arct", "section"]);
+        assert!(html.contains("<h2 id=\"intro-1\">Intro<a class=\"heading-anchor\" href=\"#intro-1\""), "{}", html);
+        assert_eq!(toc[4].title, "Arc<T>?");
+        assert!(html.contains("<code>Arc&lt;T&gt;</code>?"), "{}", html);
+    }
+
+    #[test]
+    fn headings_nest_by_level() {
+        let (_, toc) = render("# Title\n## A\n### A1\n#### A1a\n### A2\n## B\n#### B1\n## C\n");
+        assert_eq!(outline(&toc), ["a[a1[a1a] a2]", "b[b1]", "c"]);
+        assert_eq!((toc[0].level, toc[0].children[0].level), (2, 3));
+    }
+
+    #[test]
+    fn explicit_ids_and_classes() {
+        let (html, toc) = render("## Setup {#install .note}\n");
+        assert_eq!(toc[0].id, "install");
+        assert_eq!(toc[0].title, "Setup");
+        assert!(html.starts_with("<h2 id=\"install\" class=\"note\">Setup"), "{}", html);
+    }
+}
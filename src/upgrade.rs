@@ -0,0 +1,144 @@
+use std::env;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use crate::listener::{Listener, LISTEN_FDS_START, UPGRADE_FROM_ENV};
+
+/// Environment variable carrying the descriptor the successor writes to once
+/// its listeners are adopted and it is ready to accept connections.
+const READY_FD_ENV: &str = "PORTFOLIO_UPGRADE_READY_FD";
+
+/// How long the successor may take to adopt the listeners and report ready
+/// before it is considered hung and killed.
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+static UPGRADE_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigusr2(_signal: libc::c_int) {
+    UPGRADE_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+pub fn install_signal_handler() {
+    let handler = on_sigusr2 as extern "C" fn(libc::c_int);
+    unsafe {
+        libc::signal(libc::SIGUSR2, handler as libc::sighandler_t);
+    }
+}
+
+/// Returns true once per received SIGUSR2.
+pub fn take_upgrade_request() -> bool {
+    UPGRADE_REQUESTED.swap(false, Ordering::SeqCst)
+}
+
+/// Re-executes the server binary with the given listeners passed as
+/// `LISTEN_FDS`, blocking until the new process reports that it is serving.
+/// A successor that does not report ready within `READY_TIMEOUT` is killed,
+/// as it may already be accepting connections it never answers. On error the
+/// caller keeps running with its listeners untouched.
+pub fn spawn_successor(listeners: &[Listener]) -> io::Result<u32> {
+    let (mut ready_rx, ready_tx) = UnixStream::pair()?;
+
+    let mut fds: Vec<RawFd> = listeners.iter().map(|l| l.raw_fd()).collect();
+    fds.push(ready_tx.as_raw_fd());
+    let ready_fd = LISTEN_FDS_START + listeners.len() as RawFd;
+
+    let mut command = Command::new(current_binary()?);
+    command
+        .args(env::args_os().skip(1))
+        .env("LISTEN_FDS", listeners.len().to_string())
+        .env_remove("LISTEN_PID")
+        .env(UPGRADE_FROM_ENV, std::process::id().to_string())
+        .env(READY_FD_ENV, ready_fd.to_string());
+
+    unsafe {
+        command.pre_exec(move || place_fds(&fds));
+    }
+
+    ready_rx.set_read_timeout(Some(READY_TIMEOUT))?;
+    let mut child = command.spawn()?;
+    // Only the child may hold the write end, so EOF means it died before becoming ready.
+    drop(ready_tx);
+
+    let mut buf = [0u8; 1];
+    let result = loop {
+        match ready_rx.read(&mut buf) {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            result => break result,
+        }
+    };
+    match result {
+        Ok(1) => Ok(child.id()),
+        Ok(_) => {
+            let status = child.wait()?;
+            Err(io::Error::other(format!("new process exited before becoming ready ({})", status)))
+        }
+        Err(err) => {
+            let _ = child.kill();
+            let _ = child.wait();
+            if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("new process not ready after {}s, killed it", READY_TIMEOUT.as_secs()),
+                ));
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Tells the process that spawned us (if any) that our listeners are up.
+pub fn notify_ready() {
+    let fd = match env::var(READY_FD_ENV).ok().and_then(|v| v.parse::<RawFd>().ok()) {
+        Some(fd) => fd,
+        None => return,
+    };
+    unsafe {
+        env::remove_var(READY_FD_ENV);
+        env::remove_var(UPGRADE_FROM_ENV);
+    }
+    let mut stream = unsafe { UnixStream::from_raw_fd(fd) };
+    if let Err(err) = stream.write_all(b"1") {
+        eprintln!("ERROR notifying previous process: {:?}", err);
+    }
+}
+
+// Runs between fork and exec: only async-signal-safe calls are allowed here.
+// Descriptors are first moved above the target range so that dup2 cannot
+// clobber a listener that already sits at a low number, then placed at 3..
+// without close-on-exec.
+fn place_fds(fds: &[RawFd]) -> io::Result<()> {
+    const SCRATCH_BASE: RawFd = 64;
+    let mut scratch = [0 as RawFd; 64];
+    if fds.len() > scratch.len() {
+        return Err(io::Error::from_raw_os_error(libc::EMFILE));
+    }
+    for (i, fd) in fds.iter().enumerate() {
+        let dup = unsafe { libc::fcntl(*fd, libc::F_DUPFD_CLOEXEC, SCRATCH_BASE) };
+        if dup < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        scratch[i] = dup;
+    }
+    for (i, dup) in scratch[..fds.len()].iter().enumerate() {
+        if unsafe { libc::dup2(*dup, LISTEN_FDS_START + i as RawFd) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+// After an upgrade the old binary on disk has usually been replaced, in which
+// case Linux reports the running executable with a " (deleted)" suffix.
+fn current_binary() -> io::Result<PathBuf> {
+    let exe = env::current_exe()?;
+    match exe.to_str().and_then(|s| s.strip_suffix(" (deleted)")) {
+        Some(stripped) => Ok(PathBuf::from(stripped)),
+        None => Ok(exe),
+    }
+}
@@ -0,0 +1,139 @@
+//! Runs the server on a socket bound by the test and passed in with
+//! `LISTEN_FDS`, as systemd socket activation does, then upgrades it with
+//! SIGUSR2 while requests keep arriving.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Kills the server and its successor (they share a process group) and
+/// removes the scratch directory.
+struct Cleanup {
+    group: i32,
+    dir: std::path::PathBuf,
+}
+
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        unsafe {
+            libc::kill(-self.group, libc::SIGTERM);
+        }
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn get(port: u16, path: &str) -> std::io::Result<String> {
+    let mut stream = TcpStream::connect(("127.0.0.1", port))?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes())?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+fn wait_for_exit(child: &mut Child, timeout: Duration) -> bool {
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if child.try_wait().unwrap().is_some() {
+            return true;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    false
+}
+
+#[test]
+fn serves_an_inherited_socket_and_hands_it_over_without_dropping_requests() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = std::env::temp_dir().join(format!("portfolio-handoff-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let config = dir.join("config.yaml");
+    fs::write(&config, format!(
+        "server:\n  host: 127.0.0.1\n  port: 1\n\nstatic:\n  root_dir: {}\n  index_file: index.html\n  auto_index: true\n\n\
+         templates:\n  dir: {}\n\nimages:\n  enabled: false\n  cache_dir: {}\n",
+        root.join("public").display(),
+        root.join("templates").display(),
+        dir.join("cache").display(),
+    )).unwrap();
+
+    let socket = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = socket.local_addr().unwrap().port();
+    let fd = socket.as_raw_fd();
+    let log = fs::File::create(dir.join("server.log")).unwrap();
+
+    // `exec` keeps the shell's pid, so LISTEN_PID=$$ names the server.
+    let mut command = Command::new("sh");
+    command
+        .args(["-c", "LISTEN_PID=$$ LISTEN_FDS=1 exec \"$0\"", env!("CARGO_BIN_EXE_portfolio_website")])
+        .env("CONFIG_PATH", &config)
+        .env_remove("LISTEN_PID")
+        .stdout(Stdio::from(log.try_clone().unwrap()))
+        .stderr(Stdio::from(log))
+        .process_group(0);
+    unsafe {
+        command.pre_exec(move || {
+            // dup2 clears close-on-exec on the copy; a descriptor already at
+            // 3 needs the flag cleared by hand.
+            let rc = if fd == 3 { libc::fcntl(3, libc::F_SETFD, 0) } else { libc::dup2(fd, 3) };
+            if rc < 0 { Err(std::io::Error::last_os_error()) } else { Ok(()) }
+        });
+    }
+    let mut server = command.spawn().unwrap();
+    let _cleanup = Cleanup { group: server.id() as i32, dir: dir.clone() };
+    // Only the server holds the socket from now on.
+    drop(socket);
+
+    let start = Instant::now();
+    loop {
+        if get(port, "/").is_ok_and(|r| r.starts_with("HTTP/1.1 200")) {
+            break;
+        }
+        assert!(start.elapsed() < Duration::from_secs(10), "server did not come up");
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    // Keep requesting throughout the upgrade; every request must succeed.
+    let stop = Arc::new(AtomicBool::new(false));
+    let served = Arc::new(AtomicUsize::new(0));
+    let clients: Vec<_> = (0..4).map(|_| {
+        let stop = Arc::clone(&stop);
+        let served = Arc::clone(&served);
+        thread::spawn(move || {
+            let mut failures = Vec::new();
+            while !stop.load(Ordering::SeqCst) {
+                match get(port, "/") {
+                    Ok(response) if response.starts_with("HTTP/1.1 200") => {
+                        served.fetch_add(1, Ordering::SeqCst);
+                    }
+                    Ok(response) => failures.push(response.lines().next().unwrap_or("").to_string()),
+                    Err(error) => failures.push(error.to_string()),
+                }
+            }
+            failures
+        })
+    }).collect();
+
+    thread::sleep(Duration::from_millis(200));
+    unsafe {
+        libc::kill(server.id() as i32, libc::SIGUSR2);
+    }
+    assert!(wait_for_exit(&mut server, Duration::from_secs(10)), "old process did not exit after the upgrade");
+    thread::sleep(Duration::from_millis(300));
+    stop.store(true, Ordering::SeqCst);
+
+    let failures: Vec<String> = clients.into_iter().flat_map(|c| c.join().unwrap()).collect();
+    let log = fs::read_to_string(dir.join("server.log")).unwrap_or_default();
+    assert!(failures.is_empty(), "failed requests: {:?}\n{}", failures, log);
+    assert!(served.load(Ordering::SeqCst) > 0);
+    assert!(log.contains("Connections drained"), "{}", log);
+    assert!(get(port, "/").unwrap().starts_with("HTTP/1.1 200"), "successor is not serving");
+}